@@ -0,0 +1,522 @@
+extern crate cgmath;
+extern crate rust_software_render;
+
+use std::env;
+use std::fs;
+use std::process;
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use cgmath::*;
+use std::ops::{Add, Sub, Mul};
+use rust_software_render::{utils, generator, Scene, Shader, ShaderType, Material, Model, TextureCube};
+use rust_software_render::{ModelObj, ModelGltf, ModelStl, ModelPly};
+use rust_software_render::{InputEvent, MouseButton, Keycode, CullMode, PolygonMode, LineRasterizer, Antialiasing};
+
+const USAGE: &'static str = "\
+usage: viewer [MODEL] [OPTIONS]
+
+MODEL - path to .obj, .gltf, .glb, .stl or .ply file or one of built-in models:
+        plane, sphere, monster, ring, skull (default)
+
+options:
+    --shader NAME    default, normal, lambert, phong, cook (default), pbr
+    --cubemap DIR    directory with posx, negx, posy, negy, posz, negz images
+    --cull MODE      back, front, none - for double-sided models, default is set by materials
+    --polygon MODE   fill (default), edges - fill with edges, line, point
+    --aa MODE        antialiasing: none (default), msaa2, msaa4, msaa8, ssaa2, ssaa3, ssaa4
+    --size WxH       window size, default 800x600
+    --fov DEG        vertical field of view, default 100
+    --ortho HEIGHT   orthographic projection, HEIGHT - half of the visible height
+    --eye X,Y,Z      camera position, default depends on the model
+    --light X,Y,Z    direction to the light, default 1,1,-1
+    --ambient VAL    ambient intensity, default 1
+    --rotate RAD     rotation per frame, default depends on the model
+    --frames N       stop after N frames
+    --output FILE    render without window and save the last frame (ppm, tga, png)
+    --help           print this message
+
+controls:
+    left mouse drag  orbit around the model, look around in fly mode
+    mouse wheel      zoom
+    Tab              switch between orbit and fly mode
+    W, A, S, D       move in fly mode, Q, E - down and up
+    N                next shader
+    L                next polygon mode: fill, edges, line, point
+    B                toggle normals, bounding box and lights
+    C                toggle cubemap reflection, lighting by the cubemap for pbr
+    Space            toggle rotation of the model
+    O                switch between perspective and orthographic projection
+    R                reset view
+    Escape           exit";
+
+const SHADERS: [&'static str; 6] = ["default", "normal", "lambert", "phong", "cook", "pbr"];
+const POLYGON_MODES: [&'static str; 4] = ["fill", "edges", "line", "point"];
+
+struct Options {
+    model: String,
+    shader: usize, // index in SHADERS
+    cubemap: Option<PathBuf>,
+    cull: Option<CullMode>,
+    polygon_mode: usize, // index in POLYGON_MODES
+    antialiasing: Antialiasing,
+    width: u32,
+    height: u32,
+    fovy: f32,
+    ortho: Option<f32>,
+    eye: Option<Point3<f32>>,
+    light: Vector3<f32>,
+    ambient: f32,
+    rotate: Option<f32>,
+    frames: Option<u32>,
+    output: Option<PathBuf>,
+}
+
+fn parse_f32(val: &str) -> Result<f32, String> {
+    val.trim().parse::<f32>().map_err(|_| format!("invalid number: \"{}\"", val))
+}
+
+fn parse_vec3(val: &str) -> Result<Vector3<f32>, String> {
+    let items: Vec<&str> = val.split(',').collect();
+    if items.len() != 3 {
+        return Err(format!("expected X,Y,Z: \"{}\"", val));
+    }
+
+    Ok(Vector3::new(try!(parse_f32(items[0])), try!(parse_f32(items[1])), try!(parse_f32(items[2]))))
+}
+
+fn parse_size(val: &str) -> Result<(u32, u32), String> {
+    let items: Vec<&str> = val.split('x').collect();
+    if items.len() != 2 {
+        return Err(format!("expected WxH: \"{}\"", val));
+    }
+    let width = try!(items[0].parse::<u32>().map_err(|_| format!("invalid width: \"{}\"", items[0])));
+    let height = try!(items[1].parse::<u32>().map_err(|_| format!("invalid height: \"{}\"", items[1])));
+    if width == 0 || height == 0 {
+        return Err(format!("empty size: \"{}\"", val));
+    }
+
+    Ok((width, height))
+}
+
+fn parse_shader(val: &str) -> Result<usize, String> {
+    match SHADERS.iter().position(|name| *name == val) {
+        Some(ind) => Ok(ind),
+        None => Err(format!("unknown shader: \"{}\"", val)),
+    }
+}
+
+fn parse_cull(val: &str) -> Result<CullMode, String> {
+    match val {
+        "back" => Ok(CullMode::Back),
+        "front" => Ok(CullMode::Front),
+        "none" => Ok(CullMode::None),
+        _ => Err(format!("unknown cull mode: \"{}\"", val)),
+    }
+}
+
+fn parse_polygon_mode(val: &str) -> Result<usize, String> {
+    match POLYGON_MODES.iter().position(|name| *name == val) {
+        Some(ind) => Ok(ind),
+        None => Err(format!("unknown polygon mode: \"{}\"", val)),
+    }
+}
+
+fn parse_antialiasing(val: &str) -> Result<Antialiasing, String> {
+    match val {
+        "none" => Ok(Antialiasing::None),
+        "msaa2" => Ok(Antialiasing::Msaa(2)),
+        "msaa4" => Ok(Antialiasing::Msaa(4)),
+        "msaa8" => Ok(Antialiasing::Msaa(8)),
+        "ssaa2" => Ok(Antialiasing::Ssaa(2)),
+        "ssaa3" => Ok(Antialiasing::Ssaa(3)),
+        "ssaa4" => Ok(Antialiasing::Ssaa(4)),
+        _ => Err(format!("unknown antialiasing: \"{}\"", val)),
+    }
+}
+
+fn polygon_mode(ind: usize) -> PolygonMode {
+    match ind {
+        0 => PolygonMode::Fill,
+        1 => PolygonMode::FillLine,
+        2 => PolygonMode::Line,
+        _ => PolygonMode::Point,
+    }
+}
+
+fn create_shader(ind: usize) -> Shader {
+    Shader::new(match ind {
+        0 => ShaderType::Default,
+        1 => ShaderType::Normal,
+        2 => ShaderType::Lambert,
+        3 => ShaderType::PhongBlinn,
+        4 => ShaderType::CookTorrance,
+        _ => ShaderType::Pbr,
+    })
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        model: String::from("skull"),
+        shader: 4, // cook
+        cubemap: None,
+        cull: None,
+        polygon_mode: 0,
+        antialiasing: Antialiasing::None,
+        width: 800,
+        height: 600,
+        fovy: 100.0_f32,
+        ortho: None,
+        eye: None,
+        light: Vector3::new(1.0_f32, 1.0_f32, -1.0_f32),
+        ambient: 1.0_f32,
+        rotate: None,
+        frames: None,
+        output: None,
+    };
+
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        if !arg.starts_with("--") {
+            options.model = arg.clone();
+            continue;
+        }
+        if arg == "--help" {
+            return Err(String::new());
+        }
+        let val = match it.next() {
+            Some(val) => val,
+            None => return Err(format!("missing value for {}", arg)),
+        };
+        match arg.as_str() {
+            "--shader" => options.shader = try!(parse_shader(val)),
+            "--cubemap" => options.cubemap = Some(PathBuf::from(val)),
+            "--cull" => options.cull = Some(try!(parse_cull(val))),
+            "--polygon" => options.polygon_mode = try!(parse_polygon_mode(val)),
+            "--aa" => options.antialiasing = try!(parse_antialiasing(val)),
+            "--size" => {
+                let (width, height) = try!(parse_size(val));
+                options.width = width;
+                options.height = height;
+            },
+            "--fov" => options.fovy = try!(parse_f32(val)),
+            "--ortho" => options.ortho = Some(try!(parse_f32(val))),
+            "--eye" => options.eye = Some(Point3::from_vec(try!(parse_vec3(val)))),
+            "--light" => options.light = try!(parse_vec3(val)),
+            "--ambient" => options.ambient = try!(parse_f32(val)),
+            "--rotate" => options.rotate = Some(try!(parse_f32(val))),
+            "--frames" => options.frames = Some(try!(val.parse::<u32>().map_err(|_| format!("invalid frames: \"{}\"", val)))),
+            "--output" => options.output = Some(PathBuf::from(val)),
+            _ => return Err(format!("unknown option: {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+// extension of "posx.*" image in the directory
+fn cubemap_extension(dir: &Path) -> Result<String, String> {
+    let entries = try!(fs::read_dir(dir).map_err(|e| format!("can't read \"{}\": {}", dir.display(), e)));
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(_) => continue,
+        };
+        if path.file_stem().map_or(false, |stem| stem == "posx") {
+            if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+                return Ok(String::from(ext));
+            }
+        }
+    }
+
+    Err(format!("not found posx image in \"{}\"", dir.display()))
+}
+
+// returns model, default eye position, world matrix and default rotation speed
+fn load_model(name: &str) -> Result<(Model, Point3<f32>, Matrix4<f32>, f32), String> {
+    match name {
+        "plane" => {
+            let mut mat = Material::new();
+            try!(mat.create_texture(try!(utils::get_base_dir()).join("lego.png").as_path()));
+            let model = try!(generator::generate_plane(mat));
+            let world = Matrix4::from(Matrix3::from_angle_x(rad(std::f32::consts::PI * 0.25_f32)));
+            Ok((model, Point3::new(0.0_f32, 0.0_f32, -0.5_f32), world, 0.0_f32))
+        },
+        "sphere" => {
+            let model = try!(generator::generate_sphere(60, Material::new()));
+            let world = model.to_center_matrix();
+            Ok((model, Point3::new(0.0_f32, 1.1_f32, -1.1_f32), world, 0.0_f32))
+        },
+        "monster" | "ring" | "skull" => {
+            let model = try!(ModelObj::load(Path::new(&format!("{0}/{0}.obj", name))));
+            let world = model.to_center_matrix();
+            let eye = match name {
+                "monster" => Point3::new(0.0_f32, 0.3_f32, 0.6_f32),
+                "ring" => Point3::new(0.0_f32, 0.25_f32, 0.75_f32),
+                _ => Point3::new(0.0_f32, 0.2_f32, 0.8_f32),
+            };
+            Ok((model, eye, world, 0.01_f32))
+        },
+        _ => {
+            // loaders search relative paths in the media directory
+            let path = Path::new(name);
+            let path = if path.exists() {
+                try!(env::current_dir().map_err(|e| e.to_string())).join(path)
+            } else {
+                PathBuf::from(path)
+            };
+            let model = match path.extension().and_then(|e| e.to_str()) {
+                Some("gltf") | Some("glb") => try!(ModelGltf::load(&path)),
+                Some("stl") => try!(ModelStl::load(&path, true)),
+                Some("ply") => try!(ModelPly::load(&path, true)),
+                _ => try!(ModelObj::load(&path)),
+            };
+            let world = model.to_center_matrix();
+            Ok((model, Point3::new(0.0_f32, 0.3_f32, 1.0_f32), world, 0.01_f32))
+        },
+    }
+}
+
+const MAX_PITCH: f32 = 1.5_f32;
+const MIN_DISTANCE: f32 = 0.01_f32;
+
+// orbit: the eye moves on a sphere around the target,
+// fly: the eye moves freely and looks in the opposite direction
+struct Camera {
+    target: Point3<f32>,
+    yaw: f32,      // rotation around y axis, rad
+    pitch: f32,    // elevation, rad
+    distance: f32, // from the eye to the target
+    fly: bool,
+    eye: Point3<f32>,
+}
+
+impl Camera {
+    fn new(eye: Point3<f32>, target: Point3<f32>) -> Camera {
+        let offset = eye.sub(target);
+        let distance = offset.length().max(MIN_DISTANCE);
+        Camera {
+            target: target,
+            yaw: offset.x.atan2(offset.z),
+            pitch: (offset.y / distance).max(-1.0_f32).min(1.0_f32).asin(),
+            distance: distance,
+            fly: false,
+            eye: eye,
+        }
+    }
+
+    // unit vector from the target to the eye
+    fn direction(&self) -> Vector3<f32> {
+        Vector3::new(self.pitch.cos() * self.yaw.sin(), self.pitch.sin(), self.pitch.cos() * self.yaw.cos())
+    }
+
+    fn eye(&self) -> Point3<f32> {
+        if self.fly {
+            self.eye
+        } else {
+            self.target.add(self.direction().mul(self.distance))
+        }
+    }
+
+    fn center(&self) -> Point3<f32> {
+        if self.fly {
+            self.eye.add(self.direction().mul(-1.0_f32))
+        } else {
+            self.target
+        }
+    }
+
+    fn set_fly(&mut self, fly: bool) {
+        if fly && !self.fly {
+            self.eye = self.eye();
+        } else if !fly && self.fly {
+            self.target = self.eye.add(self.direction().mul(-self.distance));
+        }
+        self.fly = fly;
+    }
+
+    fn rotate(&mut self, dx: f32, dy: f32) {
+        const SPEED: f32 = 0.01_f32;
+        let sign = if self.fly { 1.0_f32 } else { -1.0_f32 };
+        self.yaw += dx * SPEED * sign;
+        self.pitch = (self.pitch + dy * SPEED).max(-MAX_PITCH).min(MAX_PITCH);
+    }
+
+    fn zoom(&mut self, steps: i32) {
+        let scale = 0.9_f32.powi(steps);
+        if self.fly {
+            let forward = self.direction().mul(-self.distance * (1.0_f32 - scale));
+            self.eye = self.eye.add(forward);
+        } else {
+            self.distance = (self.distance * scale).max(MIN_DISTANCE);
+        }
+    }
+
+    // forward, right, up - in [-1; 1]
+    fn fly_move(&mut self, forward: f32, right: f32, up: f32) {
+        let speed = self.distance * 0.02_f32;
+        let dir_forward = self.direction().mul(-1.0_f32);
+        let dir_right = dir_forward.cross(Vector3::new(0.0_f32, 1.0_f32, 0.0_f32)).normalize();
+        let offset = dir_forward.mul(forward)
+            .add(dir_right.mul(right))
+            .add(Vector3::new(0.0_f32, up, 0.0_f32));
+        self.eye = self.eye.add(offset.mul(speed));
+    }
+}
+
+fn set_cubemap(model: &mut Model, texture: Option<Arc<TextureCube>>) {
+    for material in &mut model.material_list {
+        material.texture_cube = texture.clone();
+    }
+}
+
+fn is_pressed(keys: &[Keycode], key: Keycode) -> f32 {
+    if keys.contains(&key) { 1.0_f32 } else { 0.0_f32 }
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let up = Vector3::new(0.0_f32, 1.0_f32, 0.0_f32);
+    let center = Point3::new(0.0_f32, 0.0_f32, 0.0_f32);
+    let (mut model, eye, init_matrix, add_angle) = try!(load_model(&options.model));
+    if let Some(cull) = options.cull {
+        for material in &mut model.material_list {
+            material.render_state.cull = cull;
+        }
+    }
+    let eye = options.eye.unwrap_or(eye);
+    let add_angle = rad(options.rotate.unwrap_or(add_angle));
+    let cubemap_dir = match options.cubemap {
+        Some(ref dir) => dir.clone(),
+        None => try!(utils::get_base_dir()).join("cubemap"),
+    };
+    // loaded on the first use
+    let mut cubemap: Option<Arc<TextureCube>> = None;
+    let mut is_cubemap = options.cubemap.is_some();
+
+    let mut shader_ind = options.shader;
+    let mut shader = create_shader(shader_ind);
+
+    let mut scene = match options.output {
+        Some(_) => Scene::headless(options.width, options.height),
+        None => Scene::new(options.width, options.height),
+    };
+    let mut polygon_ind = options.polygon_mode;
+    let mut is_debug = false;
+    let (min, max) = model.bounds();
+    let normal_length = max.sub(min).length() * 0.02_f32;
+    scene.light(options.light)
+        .ambient_intensity(options.ambient)
+        .antialiasing(options.antialiasing)
+        .line_rasterizer(LineRasterizer::Wu)
+        .wire_color(0x202020);
+    let mut is_ortho = options.ortho.is_some();
+
+    // without window the scene is rendered once, if the count of frames is not set
+    let frames = match options.output {
+        Some(_) => Some(options.frames.unwrap_or(1)),
+        None => options.frames,
+    };
+    let mut camera = Camera::new(eye, center);
+    let mut angle = rad(0.0_f32);
+    let mut is_rotate = true;
+    let mut is_drag = false;
+    let mut keys = Vec::<Keycode>::new();
+    let mut frame = 0;
+    while frames.map_or(true, |cnt| frame < cnt) && scene.start(0xAAAAAA) {
+        for event in scene.events() {
+            match *event {
+                InputEvent::MouseDown(MouseButton::Left, _, _) => is_drag = true,
+                InputEvent::MouseUp(MouseButton::Left, _, _) => is_drag = false,
+                InputEvent::MouseMove { dx, dy, .. } if is_drag => camera.rotate(dx as f32, dy as f32),
+                InputEvent::MouseWheel(steps) => camera.zoom(steps),
+                InputEvent::KeyUp(key) => keys.retain(|k| *k != key),
+                InputEvent::KeyDown(key) => {
+                    keys.push(key);
+                    match key {
+                        Keycode::Tab => {
+                            let fly = !camera.fly;
+                            camera.set_fly(fly);
+                        },
+                        Keycode::N => {
+                            shader_ind = (shader_ind + 1) % SHADERS.len();
+                            shader = create_shader(shader_ind);
+                            println!("shader: {}", SHADERS[shader_ind]);
+                        },
+                        Keycode::L => {
+                            polygon_ind = (polygon_ind + 1) % POLYGON_MODES.len();
+                            println!("polygon mode: {}", POLYGON_MODES[polygon_ind]);
+                        },
+                        Keycode::B => is_debug = !is_debug,
+                        Keycode::C => is_cubemap = !is_cubemap,
+                        Keycode::O => is_ortho = !is_ortho,
+                        Keycode::Space => is_rotate = !is_rotate,
+                        Keycode::R => {
+                            camera = Camera::new(eye, center);
+                            angle = rad(0.0_f32);
+                        },
+                        _ => {},
+                    }
+                },
+                _ => {},
+            }
+        }
+        if camera.fly {
+            let forward = is_pressed(&keys, Keycode::W) - is_pressed(&keys, Keycode::S);
+            let right = is_pressed(&keys, Keycode::D) - is_pressed(&keys, Keycode::A);
+            let up = is_pressed(&keys, Keycode::E) - is_pressed(&keys, Keycode::Q);
+            camera.fly_move(forward, right, up);
+        }
+        if is_cubemap != model.material_list.iter().any(|m| m.texture_cube.is_some()) {
+            if is_cubemap && cubemap.is_none() {
+                let ext = try!(cubemap_extension(&cubemap_dir));
+                cubemap = Some(Arc::new(try!(TextureCube::new(&cubemap_dir, &ext))));
+            }
+            set_cubemap(&mut model, if is_cubemap { cubemap.clone() } else { None });
+        }
+
+        if is_ortho {
+            // the same size of the target as in the perspective projection
+            let height = options.ortho.unwrap_or(camera.distance * deg(options.fovy * 0.5_f32).tan());
+            scene.ortho(height, -100.0_f32, 100.0_f32);
+        } else {
+            scene.proj(deg(options.fovy), 0.1_f32, 100.0_f32);
+        }
+        scene.view(camera.eye(), camera.center(), up)
+            .polygon_mode(polygon_mode(polygon_ind));
+        let mat_world = Matrix4::from(Matrix3::from_angle_y(angle)).mul(&init_matrix);
+        scene.draw(&model, mat_world, &mut shader);
+        if is_debug {
+            scene.draw_normals(&model, mat_world, normal_length, 0x0000FF)
+                .draw_bounds(&model, mat_world, 0x00FF00)
+                .draw_lights(center, 0.5_f32, 0xFFFF00);
+        }
+        scene.present();
+        if is_rotate {
+            angle = angle + add_angle;
+        }
+        frame += 1;
+    }
+
+    match options.output {
+        Some(ref path) => scene.save(path),
+        None => Ok(()),
+    }
+}
+
+pub fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            if !msg.is_empty() {
+                println!("error: {}\n", msg);
+            }
+            println!("{}", USAGE);
+            process::exit(if msg.is_empty() { 0 } else { 1 });
+        },
+    };
+
+    if let Err(msg) = run(options) {
+        println!("error: {}", msg);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,35 @@
+use cgmath::*;
+use std::ops::{Add, Mul};
+use memory::{vector3_to_u32, u32_to_vector3};
+
+// how the output of the pixel shader is combined with the color in the framebuffer,
+// src - {r, g, b} of the shader, a - its alpha, dst - color in the framebuffer
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BlendMode {
+    Opaque,        // src, alpha is ignored
+    Alpha,         // src * a + dst * (1 - a)
+    Additive,      // dst + src * a
+    Multiply,      // dst * src / 255, mixed with dst by a
+    Premultiplied, // src + dst * (1 - a), src is already multiplied by a
+}
+
+// color {r, g, b, a} from the pixel shader over the color in the framebuffer
+#[inline]
+pub fn blend(mode: BlendMode, src: Vector4<f32>, dst: u32) -> u32 {
+    let rgb = src.truncate();
+    if mode == BlendMode::Opaque {
+        return vector3_to_u32(&rgb);
+    }
+
+    let a = src.w.max(0.0_f32).min(1.0_f32);
+    let dst = u32_to_vector3(dst);
+    let result = match mode {
+        BlendMode::Alpha => rgb.mul(a).add(&dst.mul(1.0_f32 - a)),
+        BlendMode::Additive => dst.add(&rgb.mul(a)),
+        BlendMode::Multiply => dst.mul(&rgb.mul(a / 255.0_f32).add(&Vector3::from_value(1.0_f32 - a))),
+        BlendMode::Premultiplied => rgb.add(&dst.mul(1.0_f32 - a)),
+        BlendMode::Opaque => rgb,
+    };
+
+    vector3_to_u32(&result)
+}
@@ -0,0 +1,131 @@
+use cgmath::*;
+use std::ops::{Add, Sub, Mul};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Projection {
+    Perspective(Rad<f32>), // vertical field of view
+    Orthographic(f32),     // half of the visible height in world units
+}
+
+// 6 planes {normal, distance} with normals inside the volume, a point p is inside, if dot(normal, p) + distance >= 0
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub planes: [Vector4<f32>; 6], // left, right, bottom, top, near, far
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>, // normalized, where the camera looks
+    pub up: Vector3<f32>,
+    pub projection: Projection,
+    pub aspect: f32,             // width / height
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Frustum {
+    pub fn from_matrix(mat: Matrix4<f32>) -> Frustum {
+        let row = |ind: usize| Vector4::new(mat.x[ind], mat.y[ind], mat.z[ind], mat.w[ind]);
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let mut planes = [r3.add(&r0), r3.sub(&r0), r3.add(&r1), r3.sub(&r1), r3.add(&r2), r3.sub(&r2)];
+        for plane in &mut planes {
+            let len = plane.truncate().length();
+            *plane = plane.mul(1.0_f32 / len);
+        }
+
+        Frustum { planes: planes }
+    }
+
+    pub fn contains_point(&self, point: Point3<f32>) -> bool {
+        self.planes.iter().all(|plane| plane.truncate().dot(point.to_vec()) + plane.w >= 0.0_f32)
+    }
+
+    // false only if the sphere is completely outside
+    pub fn intersects_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+        self.planes.iter().all(|plane| plane.truncate().dot(center.to_vec()) + plane.w >= -radius)
+    }
+}
+
+impl Camera {
+    // at the origin, looks to -z
+    pub fn perspective<A: Into<Rad<f32>>>(fovy: A, aspect: f32, near: f32, far: f32) -> Camera {
+        Camera {
+            position: Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+            direction: Vector3::new(0.0_f32, 0.0_f32, -1.0_f32),
+            up: Vector3::new(0.0_f32, 1.0_f32, 0.0_f32),
+            projection: Projection::Perspective(fovy.into()),
+            aspect: aspect,
+            near: near,
+            far: far,
+        }
+    }
+
+    // height - half of the visible height in world units
+    pub fn orthographic(height: f32, aspect: f32, near: f32, far: f32) -> Camera {
+        let mut camera = Camera::perspective(rad(1.0_f32), aspect, near, far);
+        camera.projection = Projection::Orthographic(height);
+
+        camera
+    }
+
+    pub fn with_look_at(mut self, eye: Point3<f32>, center: Point3<f32>, up: Vector3<f32>) -> Camera {
+        self.look_at(eye, center, up);
+
+        self
+    }
+
+    pub fn look_at(&mut self, eye: Point3<f32>, center: Point3<f32>, up: Vector3<f32>) {
+        self.position = eye;
+        self.direction = center.sub(eye).normalize();
+        self.up = up;
+    }
+
+    // the same as Matrix4::look_at
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        let f = self.direction;
+        let s = f.cross(self.up).normalize();
+        let u = s.cross(f);
+        let eye = self.position;
+
+        Matrix4::new(s.x, u.x, -f.x, 0.0_f32,
+                     s.y, u.y, -f.y, 0.0_f32,
+                     s.z, u.z, -f.z, 0.0_f32,
+                     -eye.dot(s), -eye.dot(u), eye.dot(f), 1.0_f32)
+    }
+
+    pub fn proj_matrix(&self) -> Matrix4<f32> {
+        match self.projection {
+            Projection::Perspective(fovy) => perspective(fovy, self.aspect, self.near, self.far),
+            Projection::Orthographic(height) => {
+                let width = height * self.aspect;
+                ortho(-width, width, -height, height, self.near, self.far)
+            },
+        }
+    }
+
+    pub fn proj_view_matrix(&self) -> Matrix4<f32> {
+        self.proj_matrix().mul(&self.view_matrix())
+    }
+
+    // point in normalized device coordinates [-1; 1] (z: -1 - near plane, 1 - far plane) -> world space
+    pub fn unproject(&self, ndc: Vector3<f32>) -> Point3<f32> {
+        let inverse = self.proj_view_matrix().invert().expect("camera matrix is not invertible");
+        let p = inverse.mul(ndc.extend(1.0_f32));
+
+        Point3::from_vec(p.truncate().mul(1.0_f32 / p.w))
+    }
+
+    // ray from the near plane through the point of the screen: x, y - normalized device coordinates [-1; 1], y goes up,
+    // returns start point and normalized direction
+    pub fn ray(&self, x: f32, y: f32) -> (Point3<f32>, Vector3<f32>) {
+        let near = self.unproject(Vector3::new(x, y, -1.0_f32));
+        let far = self.unproject(Vector3::new(x, y, 1.0_f32));
+
+        (near, far.sub(near).normalize())
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.proj_view_matrix())
+    }
+}
@@ -0,0 +1,148 @@
+use cgmath::*;
+use shader::MAX_OUT_VALUES;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ClipMode {
+    // only near plane, the rest of the screen is clipped by rasterization
+    Near,
+    // all six planes of the view frustum
+    Frustum,
+}
+
+// triangle + one vertex for each of six planes
+pub const MAX_CLIP_VERTICES: usize = 9;
+
+const PLANE_NEAR: usize = 0;
+const CNT_PLANES: usize = 6;
+
+#[derive(Copy, Clone)]
+pub struct ClipVertex {
+    pub position: Vector4<f32>,     // in clip space
+    pub tex: Vector2<f32>,
+    pub data: [f32; MAX_OUT_VALUES], // vertex shader output, not divided by w
+}
+
+impl ClipVertex {
+    pub fn new() -> ClipVertex {
+        ClipVertex {
+            position: Vector4::new(0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32),
+            tex: Vector2::new(0.0_f32, 0.0_f32),
+            data: [0.0_f32; MAX_OUT_VALUES],
+        }
+    }
+
+    fn lerp(&self, other: &ClipVertex, data_len: usize, k: f32) -> ClipVertex {
+        let mut result = ClipVertex {
+            position: self.position.lerp(other.position, k),
+            tex: self.tex.lerp(other.tex, k),
+            data: [0.0_f32; MAX_OUT_VALUES],
+        };
+        for ind in 0..data_len {
+            result.data[ind] = self.data[ind] + (other.data[ind] - self.data[ind]) * k;
+        }
+
+        result
+    }
+}
+
+// signed distance to plane, inside >= 0
+#[inline]
+fn distance(plane: usize, p: &Vector4<f32>) -> f32 {
+    match plane {
+        0 => p.w + p.z, // near
+        1 => p.w - p.z, // far
+        2 => p.w + p.x, // left
+        3 => p.w - p.x, // right
+        4 => p.w + p.y, // bottom
+        _ => p.w - p.y, // top
+    }
+}
+
+#[inline]
+fn outcode(p: &Vector4<f32>) -> u32 {
+    let mut code = 0;
+    for plane in 0..CNT_PLANES {
+        if distance(plane, p) < 0.0_f32 {
+            code |= 1 << plane;
+        }
+    }
+
+    code
+}
+
+fn clip_by_plane(plane: usize, data_len: usize, src: &Vec<ClipVertex>, dst: &mut Vec<ClipVertex>) {
+    dst.clear();
+    if src.is_empty() {
+        return;
+    }
+    let mut prev = &src[src.len() - 1];
+    let mut prev_dist = distance(plane, &prev.position);
+    for cur in src {
+        let cur_dist = distance(plane, &cur.position);
+        if (prev_dist >= 0.0_f32) != (cur_dist >= 0.0_f32) {
+            dst.push(prev.lerp(cur, data_len, prev_dist / (prev_dist - cur_dist)));
+        }
+        if cur_dist >= 0.0_f32 {
+            dst.push(*cur);
+        }
+        prev = cur;
+        prev_dist = cur_dist;
+    }
+}
+
+// Liang-Barsky clipping of the segment in homogeneous clip space by all planes of the view frustum,
+// returns None if the segment is not visible
+pub fn clip_line(a: Vector4<f32>, b: Vector4<f32>) -> Option<(Vector4<f32>, Vector4<f32>)> {
+    let mut t0 = 0.0_f32;
+    let mut t1 = 1.0_f32;
+    for plane in 0..CNT_PLANES {
+        let dist_a = distance(plane, &a);
+        let dist_b = distance(plane, &b);
+        if dist_a < 0.0_f32 && dist_b < 0.0_f32 {
+            return None;
+        }
+        if dist_a < 0.0_f32 {
+            t0 = t0.max(dist_a / (dist_a - dist_b));
+        } else if dist_b < 0.0_f32 {
+            t1 = t1.min(dist_a / (dist_a - dist_b));
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+
+    Some((a.lerp(b, t0), a.lerp(b, t1)))
+}
+
+// Sutherland-Hodgman clipping in homogeneous clip space,
+// result is a convex polygon (empty if triangle is not visible)
+pub fn clip_triangle(triangle: &[ClipVertex; 3],
+                     data_len: usize,
+                     mode: ClipMode,
+                     polygon: &mut Vec<ClipVertex>,
+                     tmp: &mut Vec<ClipVertex>) {
+    polygon.clear();
+    let mask = match mode {
+        ClipMode::Near => 1 << PLANE_NEAR,
+        ClipMode::Frustum => (1 << CNT_PLANES) - 1,
+    };
+
+    let code0 = outcode(&triangle[0].position) & mask;
+    let code1 = outcode(&triangle[1].position) & mask;
+    let code2 = outcode(&triangle[2].position) & mask;
+    if code0 & code1 & code2 != 0 {
+        return;
+    }
+    polygon.extend_from_slice(triangle);
+    let code = code0 | code1 | code2;
+    if code == 0 {
+        return;
+    }
+
+    for plane in 0..CNT_PLANES {
+        if code & (1 << plane) != 0 {
+            clip_by_plane(plane, data_len, polygon, tmp);
+            ::std::mem::swap(polygon, tmp);
+        }
+    }
+}
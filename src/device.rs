@@ -2,6 +2,39 @@ use std;
 use sdl2;
 use time;
 use sdl2_image;
+use framebuffer::FrameBuffer;
+
+pub use sdl2::keyboard::Keycode;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+// window events, mouse coordinates are in pixels from the top left corner of the window
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputEvent {
+    Quit,
+    KeyDown(Keycode),
+    KeyUp(Keycode),
+    MouseDown(MouseButton, i32, i32),
+    MouseUp(MouseButton, i32, i32),
+    MouseMove { x: i32, y: i32, dx: i32, dy: i32 },
+    MouseWheel(i32), // > 0 - away from the user
+}
+
+impl MouseButton {
+    fn from_sdl(button: sdl2::mouse::Mouse) -> Option<MouseButton> {
+        match button {
+            sdl2::mouse::Mouse::Left => Some(MouseButton::Left),
+            sdl2::mouse::Mouse::Middle => Some(MouseButton::Middle),
+            sdl2::mouse::Mouse::Right => Some(MouseButton::Right),
+            _ => None,
+        }
+    }
+}
 
 struct Fps {
     pub fps: f32,
@@ -55,10 +88,8 @@ pub struct Device {
     renderer: sdl2::render::Renderer<'static>,
     texture: sdl2::render::Texture,
     fps: Fps,
-    pub cbuffer: Vec<u32>,
-    pub zbuffer: Vec<f32>,
-    pub y_size: usize,
-    pub x_size: usize,
+    y_size: usize,
+    x_size: usize,
 }
 
 impl Device {
@@ -78,9 +109,6 @@ impl Device {
 
         let format = sdl2::pixels::PixelFormatEnum::ARGB8888;
         let texture = renderer.create_texture_streaming(format, width, height).unwrap();
-        let size = (width as usize)*(height as usize);
-        let cbuffer = vec![0; size];
-        let zbuffer = vec![0.0_f32; size];
 
         let mut fps = Fps::new(10);
         fps.start();
@@ -90,8 +118,6 @@ impl Device {
             renderer: renderer,
             texture: texture,
             fps: fps,
-            cbuffer: cbuffer,
-            zbuffer: zbuffer,
             y_size: height as usize,
             x_size: width as usize,
         }
@@ -109,8 +135,10 @@ impl Device {
         }
     }
 
-    pub fn present(&mut self) {
-        let cbuffer = &self.cbuffer;
+    pub fn present(&mut self, framebuffer: &FrameBuffer) {
+        assert!(framebuffer.x_size == self.x_size && framebuffer.y_size == self.y_size,
+                "framebuffer size does not match window size");
+        let cbuffer = &framebuffer.cbuffer;
         let y_size = self.y_size;
         let x_size = self.x_size;
         self.texture.with_lock(None, |buffer: &mut [u8], _: usize| {
@@ -124,31 +152,27 @@ impl Device {
         self.renderer.present();
     }
 
-    pub fn clear(&mut self, color: u32) {
-        for val in &mut self.cbuffer {
-            *val = color;
-        }
-        for val in &mut self.zbuffer {
-            *val = 0.0_f32;
-        }
-    }
-
-    pub fn keyboard(&mut self) -> bool {
-        let mut is_continue = true;
-
+    // appends events since the previous call, key repeats are skipped
+    pub fn poll_events(&mut self, events: &mut Vec<InputEvent>) {
         for event in self.events.poll_iter() {
             use sdl2::event::Event;
-            use sdl2::keyboard::Keycode;
 
-            match event {
-                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    is_continue = false
-                },
-                _ => {}
+            let input = match event {
+                Event::Quit {..} => Some(InputEvent::Quit),
+                Event::KeyDown { keycode: Some(key), repeat: false, .. } => Some(InputEvent::KeyDown(key)),
+                Event::KeyUp { keycode: Some(key), repeat: false, .. } => Some(InputEvent::KeyUp(key)),
+                Event::MouseButtonDown { mouse_btn, x, y, .. } =>
+                    MouseButton::from_sdl(mouse_btn).map(|button| InputEvent::MouseDown(button, x, y)),
+                Event::MouseButtonUp { mouse_btn, x, y, .. } =>
+                    MouseButton::from_sdl(mouse_btn).map(|button| InputEvent::MouseUp(button, x, y)),
+                Event::MouseMotion { x, y, xrel, yrel, .. } =>
+                    Some(InputEvent::MouseMove { x: x, y: y, dx: xrel, dy: yrel }),
+                Event::MouseWheel { y, .. } => Some(InputEvent::MouseWheel(y)),
+                _ => None,
+            };
+            if let Some(input) = input {
+                events.push(input);
             }
         }
-
-        is_continue
     }
-
 }
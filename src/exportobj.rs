@@ -0,0 +1,150 @@
+use std;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use cgmath::Vector3;
+use mesh::Model;
+use material::Material;
+use texture::Texture;
+
+// names of textures in the mtl file, textures without a file are saved next to the model
+struct TextureNames {
+    dir: PathBuf,
+    stem: String,
+    names: HashMap<usize, String>, // by address of the texture, every texture is written once
+}
+
+impl TextureNames {
+    // relative to the directory of the model if possible
+    fn relative(&self, path: &Path) -> String {
+        if let Ok(p) = path.strip_prefix(&self.dir) {
+            return p.to_string_lossy().replace('\\', "/");
+        }
+        if let (Ok(path), Ok(dir)) = (path.canonicalize(), self.dir.canonicalize()) {
+            if let Ok(p) = path.strip_prefix(&dir) {
+                return p.to_string_lossy().replace('\\', "/");
+            }
+        }
+
+        path.to_string_lossy().replace('\\', "/")
+    }
+
+    fn get(&mut self, texture: &Arc<Texture>) -> Result<String, String> {
+        let key = &**texture as *const Texture as usize;
+        if let Some(name) = self.names.get(&key) {
+            return Ok(name.clone());
+        }
+        let name = match texture.path() {
+            Some(path) => self.relative(path),
+            None => {
+                let name = format!("{}_texture{}.png", self.stem, self.names.len());
+                try!(texture.save(&self.dir.join(&name)));
+                name
+            },
+        };
+        self.names.insert(key, name.clone());
+
+        Ok(name)
+    }
+}
+
+// {r, g, b}, [0; 255] -> [0; 1]
+fn write_color(out: &mut String, key: &str, color: Vector3<f32>) {
+    writeln!(out, "{} {} {} {}", key, color.x / 255.0_f32, color.y / 255.0_f32, color.z / 255.0_f32).unwrap();
+}
+
+fn write_material(out: &mut String, ind: usize, material: &Material, textures: &mut TextureNames) -> Result<(), String> {
+    writeln!(out, "newmtl material{}", ind).unwrap();
+    write_color(out, "Ka", material.ambient);
+    write_color(out, "Kd", material.diffuse);
+    write_color(out, "Ks", material.specular);
+    write_color(out, "Ke", material.emissive);
+    if material.shininess_given {
+        writeln!(out, "Ns {}", material.shininess).unwrap();
+    }
+    writeln!(out, "d {}", material.alpha).unwrap();
+    writeln!(out, "illum {}", material.illumination).unwrap();
+    let maps = [("map_Kd", &material.texture),
+                ("map_Ks", &material.texture_specular),
+                ("map_bump", &material.texture_bump),
+                ("map_d", &material.texture_alpha)];
+    for &(key, texture) in &maps {
+        if let Some(ref t) = *texture {
+            if key == "map_bump" && material.bump_scale != 1.0_f32 {
+                writeln!(out, "{} -bm {} {}", key, material.bump_scale, try!(textures.get(t))).unwrap();
+            } else {
+                writeln!(out, "{} {}", key, try!(textures.get(t))).unwrap();
+            }
+        }
+    }
+    out.push('\n');
+
+    Ok(())
+}
+
+fn write_file(path: &Path, data: &str) -> Result<(), String> {
+    let mut file = match std::fs::File::create(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("can't create file \"{}\", error = \"{}\"", path.display(), e)),
+    };
+    match std::io::Write::write_all(&mut file, data.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("can't write file \"{}\", error = \"{}\"", path.display(), e)),
+    }
+}
+
+// Writes the model to path (.obj) and materials to the file with the same name and .mtl extension.
+// Every mesh is a group with own material, all vertices have position, texture coordinates and normal.
+// Textures are referenced by their files, textures loaded from memory are saved to png.
+pub fn save(model: &Model, path: &Path) -> Result<(), String> {
+    let dir = match path.parent() {
+        Some(p) => PathBuf::from(p),
+        None => return Err(format!("not found parent dir for \"{}\"", path.display())),
+    };
+    let stem = match path.file_stem().and_then(|s| s.to_str()) {
+        Some(s) => String::from(s),
+        None => return Err(format!("invalid file name \"{}\"", path.display())),
+    };
+    let mtl_name = format!("{}.mtl", stem);
+
+    let mut mtl = String::new();
+    let mut textures = TextureNames {
+        dir: dir.clone(),
+        stem: stem.clone(),
+        names: HashMap::<usize, String>::new(),
+    };
+    for (ind, material) in model.material_list.iter().enumerate() {
+        try!(write_material(&mut mtl, ind, material, &mut textures));
+    }
+
+    let mut obj = String::new();
+    writeln!(obj, "mtllib {}", mtl_name).unwrap();
+    for v in &model.vertex_buffer {
+        writeln!(obj, "v {} {} {}", v.position.x, v.position.y, v.position.z).unwrap();
+    }
+    for v in &model.vertex_buffer {
+        writeln!(obj, "vt {} {}", v.tex.x, v.tex.y).unwrap();
+    }
+    for v in &model.vertex_buffer {
+        writeln!(obj, "vn {} {} {}", v.normal.x, v.normal.y, v.normal.z).unwrap();
+    }
+    for (ind, mesh) in model.mesh_list.iter().enumerate() {
+        // empty groups are skipped by readers
+        if mesh.index_buffer.len() < 3 {
+            continue;
+        }
+        writeln!(obj, "g mesh{}", ind).unwrap();
+        writeln!(obj, "usemtl material{}", mesh.material_id).unwrap();
+        for indexes in mesh.index_buffer.chunks(3) {
+            if indexes.len() == 3 {
+                // indices start from 1
+                let (a, b, c) = (indexes[0] + 1, indexes[1] + 1, indexes[2] + 1);
+                writeln!(obj, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c).unwrap();
+            }
+        }
+    }
+
+    try!(write_file(path, &obj));
+    write_file(&dir.join(mtl_name), &mtl)
+}
@@ -0,0 +1,215 @@
+use std::path::Path;
+use cgmath::Vector4;
+use imagefile;
+
+pub const MAX_SAMPLES: usize = 8;
+
+// positions of samples in a pixel for multisampling, in 1/16 of pixel from its center
+const SAMPLES_1: [(i64, i64); 1] = [(0, 0)];
+const SAMPLES_2: [(i64, i64); 2] = [(4, 4), (-4, -4)];
+const SAMPLES_4: [(i64, i64); 4] = [(-2, -6), (6, -2), (-6, 2), (2, 6)];
+const SAMPLES_8: [(i64, i64); 8] = [(1, -3), (-1, 3), (5, 1), (-3, -5), (-5, 5), (-7, -1), (3, 7), (7, -7)];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Antialiasing {
+    None,
+    // 2, 4 or 8 samples per pixel with own coverage and depth, the pixel shader runs once per pixel
+    Msaa(usize),
+    // the frame is rendered with the size multiplied by the factor and averaged
+    Ssaa(usize),
+}
+
+// colors and depths of all samples of a pixel are stored together:
+// index of sample s of pixel (x, y) is (y * x_size + x) * samples + s
+#[derive(Clone)]
+pub struct FrameBuffer {
+    pub cbuffer: Vec<u32>,
+    pub zbuffer: Vec<f32>,
+    pub y_size: usize,
+    pub x_size: usize,
+    pub samples: usize, // per pixel, 1 - without multisampling
+}
+
+// horizontal band of rows [y_begin; y_end) of the framebuffer,
+// tiles of one framebuffer do not overlap and can be rendered in parallel
+pub struct Tile<'a> {
+    pub cbuffer: &'a mut [u32],
+    pub zbuffer: &'a mut [f32],
+    pub y_size: usize,
+    pub x_size: usize,
+    pub y_begin: usize,
+    pub y_end: usize,
+    pub samples: usize,
+}
+
+// offsets of samples for the number of samples per pixel, see FrameBuffer::multisample
+pub fn sample_offsets(samples: usize) -> &'static [(i64, i64)] {
+    match samples {
+        1 => &SAMPLES_1,
+        2 => &SAMPLES_2,
+        4 => &SAMPLES_4,
+        8 => &SAMPLES_8,
+        _ => panic!("unsupported number of samples per pixel: {}", samples),
+    }
+}
+
+impl Antialiasing {
+    // framebuffer for rendering of the frame with the size width x height
+    pub fn render_target(&self, width: u32, height: u32) -> FrameBuffer {
+        match *self {
+            Antialiasing::None => FrameBuffer::new(width, height),
+            Antialiasing::Msaa(samples) => FrameBuffer::multisample(width, height, samples),
+            Antialiasing::Ssaa(factor) => FrameBuffer::new(width * factor as u32, height * factor as u32),
+        }
+    }
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> FrameBuffer {
+        FrameBuffer::multisample(width, height, 1)
+    }
+
+    // samples: 1, 2, 4 or 8 per pixel, the result is averaged by resolve
+    pub fn multisample(width: u32, height: u32, samples: usize) -> FrameBuffer {
+        let size = (width as usize)*(height as usize)*sample_offsets(samples).len();
+
+        FrameBuffer {
+            cbuffer: vec![0; size],
+            zbuffer: vec![0.0_f32; size],
+            y_size: height as usize,
+            x_size: width as usize,
+            samples: samples,
+        }
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        for val in &mut self.cbuffer {
+            *val = color;
+        }
+        for val in &mut self.zbuffer {
+            *val = 0.0_f32;
+        }
+    }
+
+    pub fn as_tile(&mut self) -> Tile {
+        Tile {
+            cbuffer: &mut self.cbuffer,
+            zbuffer: &mut self.zbuffer,
+            y_size: self.y_size,
+            x_size: self.x_size,
+            y_begin: 0,
+            y_end: self.y_size,
+            samples: self.samples,
+        }
+    }
+
+    pub fn tiles(&mut self, tile_rows: usize) -> Vec<Tile> {
+        let x_size = self.x_size;
+        let y_size = self.y_size;
+        let samples = self.samples;
+        let chunk_size = tile_rows * x_size * samples;
+        self.cbuffer.chunks_mut(chunk_size)
+            .zip(self.zbuffer.chunks_mut(chunk_size))
+            .enumerate()
+            .map(|(ind, (cbuffer, zbuffer))| {
+                let y_begin = ind * tile_rows;
+                Tile {
+                    y_end: y_begin + cbuffer.len() / (x_size * samples),
+                    cbuffer: cbuffer,
+                    zbuffer: zbuffer,
+                    y_size: y_size,
+                    x_size: x_size,
+                    y_begin: y_begin,
+                    samples: samples,
+                }
+            }).collect()
+    }
+
+    // position in clip space -> x, y in pixels, z - depth: near plane - 1, far plane - 0, w - inverse w
+    #[inline]
+    pub fn to_screen(&self, position: Vector4<f32>) -> Vector4<f32> {
+        let inverse_w = 1.0_f32 / position.w;
+
+        Vector4::new(
+            (position.x * inverse_w + 1.0_f32) * self.x_size as f32 * 0.5_f32,
+            (position.y * inverse_w + 1.0_f32) * self.y_size as f32 * 0.5_f32,
+            0.5_f32 - position.z * inverse_w * 0.5_f32,
+            inverse_w)
+    }
+
+    // averages samples of this framebuffer into the target one, the size of this framebuffer
+    // can be bigger by an integer factor (supersampling), then its pixels are averaged too,
+    // the depth of the target is the nearest one
+    pub fn resolve(&self, target: &mut FrameBuffer) {
+        if target.x_size == 0 || target.y_size == 0 {
+            return;
+        }
+        let factor = self.x_size / target.x_size;
+        assert!(target.samples == 1 && factor > 0 &&
+                self.x_size == target.x_size * factor && self.y_size == target.y_size * factor,
+                "framebuffer size is not a multiple of the target size");
+        let cnt = (factor * factor * self.samples) as u32;
+        let row = self.x_size * self.samples;
+        for y in 0..target.y_size {
+            for x in 0..target.x_size {
+                let mut sum = [cnt / 2; 3];
+                let mut depth = 0.0_f32;
+                for sy in y * factor..(y + 1) * factor {
+                    let begin = sy * row + x * factor * self.samples;
+                    for ind in begin..begin + factor * self.samples {
+                        let color = self.cbuffer[ind];
+                        sum[0] += (color >> 16) & 0xFF;
+                        sum[1] += (color >> 8) & 0xFF;
+                        sum[2] += color & 0xFF;
+                        depth = depth.max(self.zbuffer[ind]);
+                    }
+                }
+                let ind = y * target.x_size + x;
+                target.cbuffer[ind] = ((sum[0] / cnt) << 16) | ((sum[1] / cnt) << 8) | (sum[2] / cnt);
+                target.zbuffer[ind] = depth;
+            }
+        }
+    }
+
+    // one sample per pixel, multisample framebuffers are resolved to it
+    fn single_sample(&self) -> FrameBuffer {
+        let mut target = FrameBuffer::new(self.x_size as u32, self.y_size as u32);
+        self.resolve(&mut target);
+
+        target
+    }
+
+    pub fn save_color(&self, path: &Path) -> Result<(), String> {
+        if self.samples != 1 {
+            return self.single_sample().save_color(path);
+        }
+        imagefile::save(path, self.x_size, self.y_size, &self.cbuffer)
+    }
+
+    // greyscale: the nearest point is white, background is black
+    pub fn save_depth(&self, path: &Path) -> Result<(), String> {
+        if self.samples != 1 {
+            return self.single_sample().save_depth(path);
+        }
+        let mut min = ::std::f32::MAX;
+        let mut max = 0.0_f32;
+        for val in &self.zbuffer {
+            if *val > 0.0_f32 {
+                min = min.min(*val);
+                max = max.max(*val);
+            }
+        }
+        let scale = if max > min { 1.0_f32 / (max - min) } else { 0.0_f32 };
+
+        let pixels: Vec<u32> = self.zbuffer.iter().map(|val| {
+            if *val > 0.0_f32 {
+                let grey = (((*val - min) * scale * 223.0_f32) as u32) + 32;
+                (grey << 16) + (grey << 8) + grey
+            } else {
+                0
+            }
+        }).collect();
+
+        imagefile::save(path, self.x_size, self.y_size, &pixels)
+    }
+}
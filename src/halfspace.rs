@@ -0,0 +1,161 @@
+use std::cmp;
+use cgmath::*;
+use framebuffer::{Tile, MAX_SAMPLES, sample_offsets};
+use shader::{Shader, MAX_OUT_VALUES};
+
+// vertices are snapped to 1/256 of pixel
+const SUBPIXEL_BITS: i64 = 8;
+const SUBPIXEL_ONE: i64 = 1 << SUBPIXEL_BITS;
+const SUBPIXEL_HALF: i64 = SUBPIXEL_ONE / 2;
+// bigger coordinates can overflow edge functions in i64
+const GUARD_BAND: f32 = (1 << 21) as f32;
+
+struct Edge {
+    step_x: i64,
+    step_y: i64,
+    value: i64, // at the center of the first pixel
+    bias: i64,  // 0 for top-left edge, -1 for others
+}
+
+impl Edge {
+    // y-axis is directed up, triangle is counter-clockwise
+    fn new(a: (i64, i64), b: (i64, i64), x0: i64, y0: i64) -> Edge {
+        let dx = b.0 - a.0;
+        let dy = b.1 - a.1;
+        let is_left = dy < 0;
+        let is_top = dy == 0 && dx < 0;
+        let px = x0 * SUBPIXEL_ONE + SUBPIXEL_HALF;
+        let py = y0 * SUBPIXEL_ONE + SUBPIXEL_HALF;
+
+        Edge {
+            step_x: -dy * SUBPIXEL_ONE,
+            step_y: dx * SUBPIXEL_ONE,
+            value: dx * (py - a.1) - dy * (px - a.0),
+            bias: if is_left || is_top { 0 } else { -1 },
+        }
+    }
+}
+
+#[inline]
+fn snap(val: f32) -> i64 {
+    (val * SUBPIXEL_ONE as f32).round() as i64
+}
+
+// Rasterization with edge functions in fixed point and top-left fill rule:
+// pixel center on the shared edge of two triangles belongs to exactly one of them.
+// Interpolation uses barycentric coordinates, points are the same as for rasterization::triangle.
+// With multisampling the coverage and the depth are computed for every sample of the tile,
+// but the pixel shader runs once per pixel.
+pub fn triangle(tile: &mut Tile,
+                points: [Vector4<f32>; 3],
+                vertex_data: [[f32; MAX_OUT_VALUES]; 3],
+                shader: &mut Shader) {
+    let x_size = tile.x_size;
+    for p in &points {
+        if !(p.x.abs() < GUARD_BAND && p.y.abs() < GUARD_BAND) {
+            return;
+        }
+    }
+
+    let mut v = [(snap(points[0].x), snap(points[0].y)),
+                 (snap(points[1].x), snap(points[1].y)),
+                 (snap(points[2].x), snap(points[2].y))];
+    let mut order = [0, 1, 2];
+    let mut area = (v[1].0 - v[0].0) * (v[2].1 - v[0].1) - (v[1].1 - v[0].1) * (v[2].0 - v[0].0);
+    if area == 0 {
+        return;
+    }
+    if area < 0 {
+        v.swap(1, 2);
+        order.swap(1, 2);
+        area = -area;
+    }
+
+    let offsets = sample_offsets(tile.samples);
+    let samples = offsets.len();
+    // pixels with centers in [min; max], samples are not farther than half of pixel from the center
+    let margin = if samples > 1 { SUBPIXEL_HALF } else { 0 };
+    let min_x = cmp::min(cmp::min(v[0].0, v[1].0), v[2].0);
+    let max_x = cmp::max(cmp::max(v[0].0, v[1].0), v[2].0);
+    let min_y = cmp::min(cmp::min(v[0].1, v[1].1), v[2].1);
+    let max_y = cmp::max(cmp::max(v[0].1, v[1].1), v[2].1);
+    let x_begin = cmp::max((min_x - margin - SUBPIXEL_HALF + SUBPIXEL_ONE - 1) >> SUBPIXEL_BITS, 0);
+    let x_end = cmp::min(((max_x + margin - SUBPIXEL_HALF) >> SUBPIXEL_BITS) + 1, x_size as i64);
+    let y_begin = cmp::max((min_y - margin - SUBPIXEL_HALF + SUBPIXEL_ONE - 1) >> SUBPIXEL_BITS, tile.y_begin as i64);
+    let y_end = cmp::min(((max_y + margin - SUBPIXEL_HALF) >> SUBPIXEL_BITS) + 1, tile.y_end as i64);
+    if x_begin >= x_end || y_begin >= y_end {
+        return;
+    }
+
+    // edge i is opposite to vertex i
+    let mut edges = [Edge::new(v[1], v[2], x_begin, y_begin),
+                     Edge::new(v[2], v[0], x_begin, y_begin),
+                     Edge::new(v[0], v[1], x_begin, y_begin)];
+
+    // values of edge functions at samples relative to the pixel center, offsets are in 1/16 of pixel
+    let mut sample_w = [[0_i64; 3]; MAX_SAMPLES];
+    for (s, &(ox, oy)) in offsets.iter().enumerate() {
+        for i in 0..3 {
+            sample_w[s][i] = (edges[i].step_x * ox + edges[i].step_y * oy) / 16;
+        }
+    }
+    let bias = [edges[0].bias, edges[1].bias, edges[2].bias];
+
+    let z = [points[order[0]].w, points[order[1]].w, points[order[2]].w];
+    let d = [points[order[0]].z, points[order[1]].z, points[order[2]].z];
+    let vd = [&vertex_data[order[0]], &vertex_data[order[1]], &vertex_data[order[2]]];
+    let inv_area = 1.0_f32 / area as f32;
+
+    for y in y_begin..y_end {
+        let offset = (y as usize - tile.y_begin) * x_size;
+        let mut w = [edges[0].value, edges[1].value, edges[2].value];
+        for x in x_begin..x_end {
+            let ind_buffer = (offset + x as usize) * samples;
+            // samples in the triangle which pass the depth test
+            let mut mask = 0_u32;
+            let mut depth = [0.0_f32; MAX_SAMPLES];
+            for s in 0..samples {
+                let ws = [w[0] + sample_w[s][0], w[1] + sample_w[s][1], w[2] + sample_w[s][2]];
+                if (ws[0] + bias[0]) | (ws[1] + bias[1]) | (ws[2] + bias[2]) >= 0 {
+                    let b0 = ws[0] as f32 * inv_area;
+                    let b1 = ws[1] as f32 * inv_area;
+                    let b2 = ws[2] as f32 * inv_area;
+                    let pd = b0 * d[0] + b1 * d[1] + b2 * d[2]; // depth
+                    if shader.depth_test(tile.zbuffer[ind_buffer + s], pd) {
+                        mask |= 1 << s;
+                        depth[s] = pd;
+                    }
+                }
+            }
+            if mask != 0 {
+                // varyings at the center, or at the first covered sample, if the center is out of the triangle
+                let ws = if (w[0] + bias[0]) | (w[1] + bias[1]) | (w[2] + bias[2]) >= 0 {
+                    w
+                } else {
+                    let s = mask.trailing_zeros() as usize;
+                    [w[0] + sample_w[s][0], w[1] + sample_w[s][1], w[2] + sample_w[s][2]]
+                };
+                let b0 = ws[0] as f32 * inv_area;
+                let b1 = ws[1] as f32 * inv_area;
+                let b2 = ws[2] as f32 * inv_area;
+                let pz = b0 * z[0] + b1 * z[1] + b2 * z[2]; // inverse w
+                for ind in 0..shader.vertex_out_len {
+                    shader.in_pixel_data[ind] = (b0 * vd[0][ind] + b1 * vd[1][ind] + b2 * vd[2][ind]) / pz;
+                }
+                if let Some(src) = shader.shade() {
+                    for s in 0..samples {
+                        if mask & (1 << s) != 0 {
+                            shader.write_sample(src, &mut tile.cbuffer[ind_buffer + s], &mut tile.zbuffer[ind_buffer + s], depth[s]);
+                        }
+                    }
+                }
+            }
+            for i in 0..3 {
+                w[i] += edges[i].step_x;
+            }
+        }
+        for edge in &mut edges {
+            edge.value += edge.step_y;
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std;
+use std::io::{Read, Write};
+use std::path::Path;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+    Tga,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Result<ImageFormat, String> {
+        let ext = match path.extension().and_then(|e| e.to_str()) {
+            Some(e) => e.to_lowercase(),
+            None => return Err(format!("not found image extension for \"{}\"", path.display())),
+        };
+        match ext.as_ref() {
+            "png" => Ok(ImageFormat::Png),
+            "ppm" => Ok(ImageFormat::Ppm),
+            "tga" => Ok(ImageFormat::Tga),
+            _ => Err(format!("unsupported image format \"{}\" for \"{}\"", ext, path.display())),
+        }
+    }
+}
+
+// pixels - 0xRRGGBB, rows from bottom to top (as in FrameBuffer)
+pub fn save(path: &Path, x_size: usize, y_size: usize, pixels: &[u32]) -> Result<(), String> {
+    assert!(pixels.len() == x_size * y_size);
+    let format = try!(ImageFormat::from_path(path));
+    // the TGA header stores 16-bit sizes
+    if format == ImageFormat::Tga && (x_size > 0xFFFF || y_size > 0xFFFF) {
+        return Err(format!("image size {}x{} is too big for TGA \"{}\"", x_size, y_size, path.display()));
+    }
+    let data = match format {
+        ImageFormat::Png => encode_png(x_size, y_size, pixels),
+        ImageFormat::Ppm => encode_ppm(x_size, y_size, pixels),
+        ImageFormat::Tga => encode_tga(x_size, y_size, pixels),
+    };
+
+    let mut file = match std::fs::File::create(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("can't create image \"{}\", error = \"{}\"", path.display(), e)),
+    };
+    match file.write_all(&data) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("can't write image \"{}\", error = \"{}\"", path.display(), e)),
+    }
+}
+
+// binary PPM only, pixels are returned in the same layout as for save
+pub fn load_ppm(path: &Path) -> Result<(usize, usize, Vec<u32>), String> {
+    let mut data = Vec::<u8>::new();
+    match std::fs::File::open(path) {
+        Ok(mut f) => match f.read_to_end(&mut data) {
+            Ok(_) => {},
+            Err(e) => return Err(format!("can't read image \"{}\", error = \"{}\"", path.display(), e)),
+        },
+        Err(e) => return Err(format!("can't open image \"{}\", error = \"{}\"", path.display(), e)),
+    };
+
+    // header: magic, width, height, max value
+    let mut fields = Vec::<usize>::with_capacity(3);
+    let mut pos = 2;
+    if data.len() < 2 || &data[..2] != b"P6" {
+        return Err(format!("image \"{}\" is not binary PPM", path.display()));
+    }
+    while fields.len() != 3 && pos < data.len() {
+        if data[pos] == b'#' {
+            while pos < data.len() && data[pos] != b'\n' {
+                pos += 1;
+            }
+        } else if (data[pos] as char).is_digit(10) {
+            let mut val = 0_usize;
+            while pos < data.len() && (data[pos] as char).is_digit(10) {
+                val = val * 10 + (data[pos] - b'0') as usize;
+                pos += 1;
+            }
+            fields.push(val);
+            continue;
+        }
+        pos += 1;
+    }
+    // single whitespace after max value
+    pos += 1;
+
+    if fields.len() != 3 || fields[2] != 255 {
+        return Err(format!("unsupported PPM header in \"{}\"", path.display()));
+    }
+    let (x_size, y_size) = (fields[0], fields[1]);
+    if data.len() < pos + x_size * y_size * 3 {
+        return Err(format!("unexpected end of data in \"{}\"", path.display()));
+    }
+
+    let mut pixels = vec![0_u32; x_size * y_size];
+    for (ind, rgb) in data[pos..pos + x_size * y_size * 3].chunks(3).enumerate() {
+        let (x, y) = (ind % x_size, y_size - ind / x_size - 1);
+        pixels[y * x_size + x] = ((rgb[0] as u32) << 16) + ((rgb[1] as u32) << 8) + (rgb[2] as u32);
+    }
+
+    Ok((x_size, y_size, pixels))
+}
+
+#[inline]
+fn push_rgb(dst: &mut Vec<u8>, color: u32) {
+    dst.push((color >> 16) as u8);
+    dst.push((color >> 8) as u8);
+    dst.push(color as u8);
+}
+
+pub fn encode_ppm(x_size: usize, y_size: usize, pixels: &[u32]) -> Vec<u8> {
+    let mut data = format!("P6\n{} {}\n255\n", x_size, y_size).into_bytes();
+    data.reserve(x_size * y_size * 3);
+    for y in (0..y_size).rev() {
+        for color in &pixels[y * x_size..(y + 1) * x_size] {
+            push_rgb(&mut data, *color);
+        }
+    }
+
+    data
+}
+
+// sizes are not bigger than 65535, see save
+pub fn encode_tga(x_size: usize, y_size: usize, pixels: &[u32]) -> Vec<u8> {
+    let mut data = Vec::<u8>::with_capacity(18 + x_size * y_size * 3);
+    // uncompressed true-color image, 24 bit, origin in lower left corner
+    data.extend_from_slice(&[0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    data.extend_from_slice(&[x_size as u8, (x_size >> 8) as u8, y_size as u8, (y_size >> 8) as u8]);
+    data.extend_from_slice(&[24, 0]);
+    for color in pixels {
+        data.push(*color as u8);
+        data.push((*color >> 8) as u8);
+        data.push((*color >> 16) as u8);
+    }
+
+    data
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFF_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xEDB88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let mut a = 1_u32;
+    let mut b = 0_u32;
+    for chunk in data.chunks(4096) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+
+    (b << 16) | a
+}
+
+#[inline]
+fn push_u32_be(dst: &mut Vec<u8>, val: u32) {
+    dst.extend_from_slice(&[(val >> 24) as u8, (val >> 16) as u8, (val >> 8) as u8, val as u8]);
+}
+
+fn push_png_chunk(dst: &mut Vec<u8>, name: &[u8; 4], chunk: &[u8]) {
+    push_u32_be(dst, chunk.len() as u32);
+    let start = dst.len();
+    dst.extend_from_slice(name);
+    dst.extend_from_slice(chunk);
+    let crc = crc32(&dst[start..]);
+    push_u32_be(dst, crc);
+}
+
+// zlib stream of not compressed deflate blocks
+fn zlib_store(raw: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65535;
+    let mut data = Vec::<u8>::with_capacity(raw.len() + raw.len() / MAX_BLOCK * 5 + 11);
+    data.extend_from_slice(&[0x78, 0x01]);
+    let cnt_blocks = std::cmp::max((raw.len() + MAX_BLOCK - 1) / MAX_BLOCK, 1);
+    for i in 0..cnt_blocks {
+        let block = &raw[i * MAX_BLOCK..std::cmp::min((i + 1) * MAX_BLOCK, raw.len())];
+        let len = block.len() as u16;
+        data.push(if i + 1 == cnt_blocks { 1 } else { 0 });
+        data.extend_from_slice(&[len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8]);
+        data.extend_from_slice(block);
+    }
+    push_u32_be(&mut data, adler32(raw));
+
+    data
+}
+
+pub fn encode_png(x_size: usize, y_size: usize, pixels: &[u32]) -> Vec<u8> {
+    let mut raw = Vec::<u8>::with_capacity((x_size * 3 + 1) * y_size);
+    for y in (0..y_size).rev() {
+        // filter type: none
+        raw.push(0);
+        for color in &pixels[y * x_size..(y + 1) * x_size] {
+            push_rgb(&mut raw, *color);
+        }
+    }
+
+    let mut header = Vec::<u8>::with_capacity(13);
+    push_u32_be(&mut header, x_size as u32);
+    push_u32_be(&mut header, y_size as u32);
+    // bit depth 8, color type RGB, default compression, filter and interlace methods
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut data = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    push_png_chunk(&mut data, b"IHDR", &header);
+    push_png_chunk(&mut data, b"IDAT", &zlib_store(&raw));
+    push_png_chunk(&mut data, b"IEND", &[]);
+
+    data
+}
@@ -0,0 +1,574 @@
+use std;
+use utils;
+use material;
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use cgmath::*;
+use json::Json;
+use mesh::{Model, Mesh, Vertex};
+use texture::Texture;
+use blend::BlendMode;
+use renderstate::CullMode;
+use std::ops::{Add, Mul};
+
+const GLB_MAGIC: &'static [u8] = b"glTF";
+const GLB_CHUNK_JSON: u32 = 0x4E4F534A;
+const GLB_CHUNK_BIN: u32 = 0x004E4942;
+
+const COMPONENT_I8: u32 = 5120;
+const COMPONENT_U8: u32 = 5121;
+const COMPONENT_I16: u32 = 5122;
+const COMPONENT_U16: u32 = 5123;
+const COMPONENT_U32: u32 = 5125;
+const COMPONENT_F32: u32 = 5126;
+
+const MODE_TRIANGLES: usize = 4;
+const MODE_TRIANGLE_STRIP: usize = 5;
+const MODE_TRIANGLE_FAN: usize = 6;
+
+// glTF 2.0 loader, .gltf with external or embedded (data uri) buffers and binary .glb.
+// Node transforms are applied to vertices, every primitive becomes a separate mesh.
+pub struct ModelGltf {
+    model_dir: PathBuf,
+    buffers: Vec<Vec<u8>>,
+    textures: HashMap<usize, Arc<Texture>>, // by image index, every image is loaded once
+    missing_tangents: Vec<bool>, // for every mesh of the model, the primitive has no tangents in the file
+    missing_normals: Vec<bool>, // for every mesh of the model, the primitive has no normals in the file
+    model: Model,
+}
+
+fn get_usize(json: &Json, key: &str) -> Option<usize> {
+    json.get(key).and_then(Json::as_usize)
+}
+
+fn get_f32(json: &Json, key: &str, default: f32) -> f32 {
+    json.get(key).and_then(Json::as_f64).map(|v| v as f32).unwrap_or(default)
+}
+
+fn get_floats(json: &Json, key: &str, default: &[f32]) -> Vec<f32> {
+    let values: Vec<f32> = get_array(json, key).iter().filter_map(Json::as_f64).map(|v| v as f32).collect();
+    if values.len() == default.len() { values } else { default.to_vec() }
+}
+
+// empty for the missing field
+fn get_array<'a>(json: &'a Json, key: &str) -> &'a [Json] {
+    json.get(key).and_then(Json::as_array).unwrap_or(&[])
+}
+
+fn get_item<'a>(json: &'a Json, key: &str, index: usize) -> Result<&'a Json, String> {
+    match get_array(json, key).get(index) {
+        Some(v) => Ok(v),
+        None => Err(format!("gltf: not found {}[{}]", key, index)),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    (data[offset] as u32) | (data[offset + 1] as u32) << 8 | (data[offset + 2] as u32) << 16 | (data[offset + 3] as u32) << 24
+}
+
+// normalized integers are converted to [0; 1] or [-1; 1]
+fn read_component(data: &[u8], component_type: u32, normalized: bool) -> f64 {
+    match component_type {
+        COMPONENT_I8 => {
+            let v = data[0] as i8 as f64;
+            if normalized { (v / 127.0_f64).max(-1.0_f64) } else { v }
+        },
+        COMPONENT_U8 => {
+            let v = data[0] as f64;
+            if normalized { v / 255.0_f64 } else { v }
+        },
+        COMPONENT_I16 => {
+            let v = i16::from_le_bytes([data[0], data[1]]) as f64;
+            if normalized { (v / 32767.0_f64).max(-1.0_f64) } else { v }
+        },
+        COMPONENT_U16 => {
+            let v = u16::from_le_bytes([data[0], data[1]]) as f64;
+            if normalized { v / 65535.0_f64 } else { v }
+        },
+        COMPONENT_U32 => read_u32(data, 0) as f64,
+        _ => f32::from_bits(read_u32(data, 0)) as f64,
+    }
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut result = Vec::<u8>::with_capacity(text.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return Err(format!("gltf: invalid base64 symbol '{}'", c as char)),
+        };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((acc >> bits) as u8);
+        }
+    }
+
+    Ok(result)
+}
+
+// "data:[<mime type>];base64,<data>"
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>, String> {
+    match uri.find(";base64,") {
+        Some(pos) => decode_base64(&uri[pos + 8..]),
+        None => Err(format!("gltf: unsupported data uri \"{}...\"", &uri[..std::cmp::min(uri.len(), 32)])),
+    }
+}
+
+// relative uri of a file can contain escaped symbols: "%20" instead of space
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut result = Vec::<u8>::with_capacity(bytes.len());
+    let mut ind = 0;
+    while ind < bytes.len() {
+        let code = if bytes[ind] == b'%' && ind + 2 < bytes.len() {
+            std::str::from_utf8(&bytes[ind + 1..ind + 3]).ok().and_then(|v| u8::from_str_radix(v, 16).ok())
+        } else {
+            None
+        };
+        match code {
+            Some(c) => {
+                result.push(c);
+                ind += 3;
+            },
+            None => {
+                result.push(bytes[ind]);
+                ind += 1;
+            },
+        }
+    }
+
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+fn node_matrix(node: &Json) -> Matrix4<f32> {
+    let m: Vec<f32> = get_array(node, "matrix").iter().filter_map(Json::as_f64).map(|v| v as f32).collect();
+    if m.len() == 16 {
+        // column-major order
+        return Matrix4::new(m[0], m[1], m[2], m[3],
+                            m[4], m[5], m[6], m[7],
+                            m[8], m[9], m[10], m[11],
+                            m[12], m[13], m[14], m[15]);
+    }
+    let t = get_floats(node, "translation", &[0.0_f32, 0.0_f32, 0.0_f32]);
+    let r = get_floats(node, "rotation", &[0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32]);
+    let s = get_floats(node, "scale", &[1.0_f32, 1.0_f32, 1.0_f32]);
+    let mat_translation = Matrix4::from_translation(Vector3::new(t[0], t[1], t[2]));
+    let mat_rotation = Matrix4::from(Quaternion::new(r[3], r[0], r[1], r[2]).normalize());
+    let mat_scale = Matrix4::from(Matrix3::from_diagonal(Vector3::new(s[0], s[1], s[2])));
+
+    mat_translation.mul(&mat_rotation).mul(&mat_scale)
+}
+
+impl ModelGltf {
+    // splits .glb into json and binary chunk, .gltf is json only
+    fn parse_container(data: &[u8]) -> Result<(Json, Option<Vec<u8>>), String> {
+        if !data.starts_with(GLB_MAGIC) {
+            let text = try!(std::str::from_utf8(data).map_err(|e| format!("gltf: {}", e)));
+            return Ok((try!(Json::parse(text)), None));
+        }
+
+        if data.len() < 12 || read_u32(data, 4) != 2 {
+            return Err(String::from("gltf: unsupported version of glb container"));
+        }
+        let mut json = None;
+        let mut bin = None;
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let chunk_len = read_u32(data, offset) as usize;
+            let chunk_type = read_u32(data, offset + 4);
+            let begin = offset + 8;
+            if begin + chunk_len > data.len() {
+                return Err(String::from("gltf: glb chunk is out of the file"));
+            }
+            let chunk = &data[begin..begin + chunk_len];
+            if chunk_type == GLB_CHUNK_JSON && json.is_none() {
+                let text = try!(std::str::from_utf8(chunk).map_err(|e| format!("gltf: {}", e)));
+                json = Some(try!(Json::parse(text)));
+            } else if chunk_type == GLB_CHUNK_BIN && bin.is_none() {
+                bin = Some(chunk.to_vec());
+            }
+            // chunks are aligned to 4 bytes
+            offset = begin + (chunk_len + 3) / 4 * 4;
+        }
+
+        match json {
+            Some(json) => Ok((json, bin)),
+            None => Err(String::from("gltf: not found json chunk in glb")),
+        }
+    }
+
+    fn load_uri(&self, uri: &str) -> Result<Vec<u8>, String> {
+        if uri.starts_with("data:") {
+            return decode_data_uri(uri);
+        }
+        utils::read_file(&self.model_dir.join(decode_uri(uri)))
+    }
+
+    fn load_buffers(&mut self, json: &Json, mut bin: Option<Vec<u8>>) -> Result<(), String> {
+        for (ind, buffer) in get_array(json, "buffers").iter().enumerate() {
+            let data = match buffer.get("uri").and_then(Json::as_str) {
+                Some(uri) => try!(self.load_uri(uri)),
+                // the binary chunk of glb
+                None if ind == 0 && bin.is_some() => bin.take().unwrap(),
+                None => return Err(format!("gltf: buffers[{}] has no data", ind)),
+            };
+            if data.len() < get_usize(buffer, "byteLength").unwrap_or(0) {
+                return Err(format!("gltf: buffers[{}] is shorter than byteLength", ind));
+            }
+            self.buffers.push(data);
+        }
+
+        Ok(())
+    }
+
+    fn buffer_view<'a>(&'a self, json: &Json, index: usize) -> Result<(&'a [u8], Option<usize>), String> {
+        let view = try!(get_item(json, "bufferViews", index));
+        let buffer = match get_usize(view, "buffer").and_then(|ind| self.buffers.get(ind)) {
+            Some(v) => v,
+            None => return Err(format!("gltf: not found buffer of bufferViews[{}]", index)),
+        };
+        let offset = get_usize(view, "byteOffset").unwrap_or(0);
+        let len = get_usize(view, "byteLength").unwrap_or(0);
+        if offset + len > buffer.len() {
+            return Err(format!("gltf: bufferViews[{}] is out of the buffer", index));
+        }
+
+        Ok((&buffer[offset..offset + len], get_usize(view, "byteStride")))
+    }
+
+    // returns number of components in the element and values of all elements
+    fn read_accessor(&self, json: &Json, index: usize) -> Result<(usize, Vec<f64>), String> {
+        let accessor = try!(get_item(json, "accessors", index));
+        let count = get_usize(accessor, "count").unwrap_or(0);
+        let components = match accessor.get("type").and_then(Json::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            _ => return Err(format!("gltf: unsupported type of accessors[{}]", index)),
+        };
+        let component_type = get_usize(accessor, "componentType").unwrap_or(0) as u32;
+        let component_size = match component_type {
+            COMPONENT_I8 | COMPONENT_U8 => 1,
+            COMPONENT_I16 | COMPONENT_U16 => 2,
+            COMPONENT_U32 | COMPONENT_F32 => 4,
+            _ => return Err(format!("gltf: unsupported component type of accessors[{}]", index)),
+        };
+        if accessor.get("sparse").is_some() {
+            return Err(format!("gltf: sparse accessors[{}] are not supported", index));
+        }
+        let normalized = accessor.get("normalized").and_then(Json::as_bool).unwrap_or(false);
+
+        let mut values = Vec::<f64>::with_capacity(count * components);
+        let view_ind = match get_usize(accessor, "bufferView") {
+            Some(v) => v,
+            // all values are zero
+            None => {
+                values.resize(count * components, 0.0_f64);
+                return Ok((components, values));
+            },
+        };
+        let (data, stride) = try!(self.buffer_view(json, view_ind));
+        let element_size = components * component_size;
+        let stride = stride.unwrap_or(element_size);
+        let offset = get_usize(accessor, "byteOffset").unwrap_or(0);
+        if count != 0 && offset + stride * (count - 1) + element_size > data.len() {
+            return Err(format!("gltf: accessors[{}] is out of the buffer view", index));
+        }
+        for ind in 0..count {
+            for component in 0..components {
+                let pos = offset + ind * stride + component * component_size;
+                values.push(read_component(&data[pos..], component_type, normalized));
+            }
+        }
+
+        Ok((components, values))
+    }
+
+    // values of the vertex attribute, None for the missing attribute
+    fn read_attribute(&self, json: &Json, attributes: &Json, name: &str, components: usize, count: usize)
+                      -> Result<Option<Vec<f64>>, String> {
+        let index = match get_usize(attributes, name) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let (accessor_components, values) = try!(self.read_accessor(json, index));
+        if accessor_components != components || values.len() != components * count {
+            return Err(format!("gltf: invalid size of {} attribute", name));
+        }
+
+        Ok(Some(values))
+    }
+
+    // texture_info: {"index": texture index, "texCoord": ...}, only the first set of coordinates is supported
+    fn load_texture(&mut self, json: &Json, texture_info: Option<&Json>) -> Result<Option<Arc<Texture>>, String> {
+        let texture_ind = match texture_info.and_then(|info| get_usize(info, "index")) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let image_ind = match get_usize(try!(get_item(json, "textures", texture_ind)), "source") {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        if let Some(texture) = self.textures.get(&image_ind) {
+            return Ok(Some(texture.clone()));
+        }
+
+        let image = try!(get_item(json, "images", image_ind));
+        let name = format!("images[{}]", image_ind);
+        let texture = match (image.get("uri").and_then(Json::as_str), get_usize(image, "bufferView")) {
+            (Some(uri), _) if uri.starts_with("data:") => try!(Texture::from_memory(&try!(decode_data_uri(uri)), &name)),
+            (Some(uri), _) => try!(Texture::new(&self.model_dir.join(decode_uri(uri)))),
+            (None, Some(view)) => try!(Texture::from_memory(try!(self.buffer_view(json, view)).0, &name)),
+            (None, None) => return Err(format!("gltf: {} has no data", name)),
+        };
+        let texture = Arc::new(texture);
+        self.textures.insert(image_ind, texture.clone());
+
+        Ok(Some(texture))
+    }
+
+    fn create_material(&mut self, json: &Json, m: &Json) -> Result<material::Material, String> {
+        let no_pbr = Json::Null;
+        let pbr = m.get("pbrMetallicRoughness").unwrap_or(&no_pbr);
+        let base_color = get_floats(pbr, "baseColorFactor", &[1.0_f32, 1.0_f32, 1.0_f32, 1.0_f32]);
+        let emissive = get_floats(m, "emissiveFactor", &[0.0_f32, 0.0_f32, 0.0_f32]);
+
+        let mut mat = material::Material::new();
+        mat.diffuse = Vector3::new(base_color[0], base_color[1], base_color[2]).mul(255.0_f32);
+        mat.ambient = mat.diffuse;
+        mat.emissive = Vector3::new(emissive[0], emissive[1], emissive[2]).mul(255.0_f32);
+        mat.metallic = get_f32(pbr, "metallicFactor", 1.0_f32);
+        mat.roughness = get_f32(pbr, "roughnessFactor", 1.0_f32);
+        // alpha of the base color is ignored in the default "OPAQUE" mode
+        let alpha_mode = m.get("alphaMode").and_then(Json::as_str).unwrap_or("OPAQUE");
+        if alpha_mode != "OPAQUE" {
+            mat.alpha = base_color[3];
+        }
+        match alpha_mode {
+            "BLEND" => {
+                mat.blend = BlendMode::Alpha;
+                mat.render_state.depth_write = false;
+            },
+            "MASK" => mat.alpha_cutoff = Some(get_f32(m, "alphaCutoff", material::DEFAULT_ALPHA_CUTOFF)),
+            _ => {},
+        }
+        if m.get("doubleSided").and_then(Json::as_bool).unwrap_or(false) {
+            mat.render_state.cull = CullMode::None;
+        }
+        // approximation for the shaders without metallic and roughness:
+        // reflectance of dielectrics is 4%, metals reflect the base color,
+        // the exponent of Blinn-Phong with the same width of the highlight as GGX
+        let dielectric = Vector3::from_value(0.04_f32 * 255.0_f32);
+        mat.specular = dielectric.mul(1.0_f32 - mat.metallic).add(mat.diffuse.mul(mat.metallic));
+        let a = mat.roughness * mat.roughness;
+        mat.shininess = (2.0_f32 / (a * a).max(1e-4_f32) - 2.0_f32).max(1.0_f32);
+        mat.shininess_given = true;
+
+        mat.texture = try!(self.load_texture(json, pbr.get("baseColorTexture")));
+        mat.texture_metallic_roughness = try!(self.load_texture(json, pbr.get("metallicRoughnessTexture")));
+        mat.texture_bump = try!(self.load_texture(json, m.get("normalTexture")));
+        if let Some(normal_texture) = m.get("normalTexture") {
+            mat.bump_scale = get_f32(normal_texture, "scale", 1.0_f32);
+        }
+        mat.texture_occlusion = try!(self.load_texture(json, m.get("occlusionTexture")));
+        mat.texture_emissive = try!(self.load_texture(json, m.get("emissiveTexture")));
+
+        Ok(mat)
+    }
+
+    fn add_mesh(&mut self, json: &Json, index: usize, world: Matrix4<f32>) -> Result<(), String> {
+        let mesh = try!(get_item(json, "meshes", index));
+        let mat_world = Matrix3::from_cols(world.x.truncate(), world.y.truncate(), world.z.truncate());
+        let mat_normal = mat_world.invert().unwrap_or(mat_world).transpose();
+        // a mirroring transform changes the order of vertices
+        let mirror = mat_world.determinant() < 0.0_f32;
+        let no_attributes = Json::Null;
+
+        for primitive in get_array(mesh, "primitives") {
+            let mode = get_usize(primitive, "mode").unwrap_or(MODE_TRIANGLES);
+            // points and lines are not rendered
+            if mode != MODE_TRIANGLES && mode != MODE_TRIANGLE_STRIP && mode != MODE_TRIANGLE_FAN {
+                continue;
+            }
+            let attributes = primitive.get("attributes").unwrap_or(&no_attributes);
+            let (components, positions) = match get_usize(attributes, "POSITION") {
+                Some(ind) => try!(self.read_accessor(json, ind)),
+                None => return Err(format!("gltf: meshes[{}] has primitive without positions", index)),
+            };
+            if components != 3 {
+                return Err(String::from("gltf: invalid size of POSITION attribute"));
+            }
+            let count = positions.len() / 3;
+            // zero normals can't be lit, such primitives get generated normals as primitives without them
+            let normals = try!(self.read_attribute(json, attributes, "NORMAL", 3, count)).and_then(|data| {
+                let has_zero = data.chunks(3).any(|n| n.iter().all(|v| *v == 0.0_f64));
+                if has_zero { None } else { Some(data) }
+            });
+            let texcoords = try!(self.read_attribute(json, attributes, "TEXCOORD_0", 2, count));
+            // tangents of the file are ignored with generated normals, they are calculated by new normals
+            let tangents = match normals {
+                Some(_) => try!(self.read_attribute(json, attributes, "TANGENT", 4, count)),
+                None => None,
+            };
+
+            let base = self.model.vertex_buffer.len();
+            for ind in 0..count {
+                let value = |data: &Vec<f64>, components: usize, component: usize| data[ind * components + component] as f32;
+                let position = Vector3::new(value(&positions, 3, 0), value(&positions, 3, 1), value(&positions, 3, 2));
+                let normal = match normals {
+                    Some(ref data) => {
+                        let n = mat_normal.mul(Vector3::new(value(data, 3, 0), value(data, 3, 1), value(data, 3, 2)));
+                        if n.length() > 0.0_f32 { n.normalize() } else { n }
+                    },
+                    None => Vector3::zero(),
+                };
+                // v goes down in glTF and up in Texture
+                let tex = match texcoords {
+                    Some(ref data) => Vector2::new(value(data, 2, 0), 1.0_f32 - value(data, 2, 1)),
+                    None => Vector2::zero(),
+                };
+                let mut vertex = Vertex::new(&world.mul(position.extend(1.0_f32)).truncate(), &tex, &normal);
+                if let Some(ref data) = tangents {
+                    let t = mat_world.mul(Vector3::new(value(data, 4, 0), value(data, 4, 1), value(data, 4, 2)));
+                    let t = if t.length() > 0.0_f32 { t.normalize() } else { t };
+                    // tangents are exported as computed before the flip of v
+                    let w = value(data, 4, 3) * if mirror { -1.0_f32 } else { 1.0_f32 };
+                    vertex.tangent = t.extend(w);
+                }
+                self.model.vertex_buffer.push(vertex);
+            }
+
+            let indices: Vec<usize> = match get_usize(primitive, "indices") {
+                Some(ind) => try!(self.read_accessor(json, ind)).1.iter().map(|v| *v as usize).collect(),
+                None => (0..count).collect(),
+            };
+            if indices.iter().any(|ind| *ind >= count) {
+                return Err(format!("gltf: meshes[{}] has index out of vertices", index));
+            }
+            let triangles: Vec<[usize; 3]> = match mode {
+                MODE_TRIANGLE_STRIP => (2..indices.len()).map(|i| {
+                    if i % 2 == 0 {
+                        [indices[i - 2], indices[i - 1], indices[i]]
+                    } else {
+                        [indices[i - 2], indices[i], indices[i - 1]]
+                    }
+                }).collect(),
+                MODE_TRIANGLE_FAN => (2..indices.len()).map(|i| [indices[i - 1], indices[i], indices[0]]).collect(),
+                _ => indices.chunks(3).filter(|t| t.len() == 3).map(|t| [t[0], t[1], t[2]]).collect(),
+            };
+
+            let mut mesh = Mesh::new();
+            for t in &triangles {
+                let order = if mirror { [t[0], t[2], t[1]] } else { *t };
+                for ind in &order {
+                    mesh.index_buffer.push((base + ind) as u32);
+                }
+            }
+            // the first material is the default
+            mesh.material_id = match get_usize(primitive, "material") {
+                Some(ind) if ind + 1 < self.model.material_list.len() => ind + 1,
+                Some(ind) => return Err(format!("gltf: not found materials[{}]", ind)),
+                None => 0,
+            };
+            self.model.mesh_list.push(mesh);
+            self.missing_normals.push(normals.is_none());
+            self.missing_tangents.push(tangents.is_none());
+        }
+
+        Ok(())
+    }
+
+    fn add_node(&mut self, json: &Json, index: usize, parent: Matrix4<f32>, depth: usize) -> Result<(), String> {
+        // the hierarchy is a tree, so depth is limited by the number of nodes
+        if depth > get_array(json, "nodes").len() {
+            return Err(String::from("gltf: cycle in the node hierarchy"));
+        }
+        let node = try!(get_item(json, "nodes", index));
+        let world = parent.mul(&node_matrix(node));
+        if let Some(mesh) = get_usize(node, "mesh") {
+            try!(self.add_mesh(json, mesh, world));
+        }
+        for child in get_array(node, "children").iter().filter_map(Json::as_usize) {
+            try!(self.add_node(json, child, world, depth + 1));
+        }
+
+        Ok(())
+    }
+
+    // root nodes of the default scene, without scenes - all nodes which are not children
+    fn root_nodes(json: &Json) -> Vec<usize> {
+        let scenes = get_array(json, "scenes");
+        if !scenes.is_empty() {
+            let scene = &scenes[std::cmp::min(get_usize(json, "scene").unwrap_or(0), scenes.len() - 1)];
+            return get_array(scene, "nodes").iter().filter_map(Json::as_usize).collect();
+        }
+        let nodes = get_array(json, "nodes");
+        let mut is_child = vec![false; nodes.len()];
+        for node in nodes {
+            for child in get_array(node, "children").iter().filter_map(Json::as_usize) {
+                if child < is_child.len() {
+                    is_child[child] = true;
+                }
+            }
+        }
+
+        (0..nodes.len()).filter(|ind| !is_child[*ind]).collect()
+    }
+
+    pub fn load(filename: &Path) -> Result<Model, String> {
+        let model_path = try!(utils::get_base_dir()).join(filename);
+        let model_dir = match model_path.parent() {
+            Some(p) => PathBuf::from(p),
+            None => return Err(format!("not found parent dir for \"{}\"", model_path.display())),
+        };
+
+        println!("load model: \"{}\"", model_path.display());
+        let data = try!(utils::read_file(&model_path));
+        let (json, bin) = try!(ModelGltf::parse_container(&data));
+
+        let mut this = ModelGltf {
+            model_dir: model_dir,
+            buffers: Vec::<Vec<u8>>::new(),
+            textures: HashMap::<usize, Arc<Texture>>::new(),
+            missing_tangents: Vec::<bool>::new(),
+            missing_normals: Vec::<bool>::new(),
+            model: Model::new(),
+        };
+        try!(this.load_buffers(&json, bin));
+
+        // default material of glTF
+        let def_mat = try!(this.create_material(&json, &Json::Null));
+        this.model.material_list.push(def_mat);
+        for m in get_array(&json, "materials") {
+            let mat = try!(this.create_material(&json, m));
+            this.model.material_list.push(mat);
+        }
+
+        for node in ModelGltf::root_nodes(&json) {
+            try!(this.add_node(&json, node, Matrix4::identity(), 0));
+        }
+
+        // flat normals by the specification, before tangents which depend on them
+        if this.missing_normals.iter().any(|v| *v) {
+            this.model.calc_flat_normals_of(&this.missing_normals);
+        }
+        // tangents are needed for normal maps only, tangents of the file are kept
+        if this.missing_tangents.iter().any(|v| *v) && this.model.material_list.iter().any(|m| m.texture_bump.is_some()) {
+            this.model.calc_tangents_of(&this.missing_tangents);
+        }
+        this.model.calc_bounds();
+
+        Ok(this.model)
+    }
+}
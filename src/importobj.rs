@@ -1,22 +1,27 @@
-use std;
 use utils;
 use material;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::collections::HashMap;
 use genmesh::Polygon;
 use std::path::{Path, PathBuf};
 use cgmath::{Vector2, Vector3};
-use mesh::{Model, Mesh, Vertex};
+use mesh::{Model, Mesh, Vertex, DEFAULT_CREASE_ANGLE};
+use texture::Texture;
+use blend::BlendMode;
 use obj::{Obj, Material, IndexTuple, load};
 use memory::cast_to;
 use std::ops::Mul;
 
 pub struct ModelObj<'a> {
     model_dir: PathBuf,
-    map: std::collections::HashMap<IndexTuple, u32>,
+    map: HashMap<IndexTuple, u32>,
+    textures: HashMap<PathBuf, Arc<Texture>>, // every file is loaded once
     model: Model,
     position_buffer: &'a [Vector3<f32>],
     normal_buffer: &'a [Vector3<f32>],
     tex_buffer: Vec<Vector2<f32>>,
+    missing_normals: bool, // some faces have no normals in the file
 }
 
 impl<'a> ModelObj<'a> {
@@ -33,13 +38,85 @@ impl<'a> ModelObj<'a> {
                     },
                     &match index.2 {
                         Some(n) => self.normal_buffer[n],
-                        None => zero3,
+                        None => {
+                            self.missing_normals = true;
+                            zero3
+                        },
                     }));
         };
         let len = self.model.vertex_buffer.len() as u32 - 1;
         self.map.entry(index).or_insert(len).clone()
     }
 
+    // value of map_* statement: options and file name, the file name is the last
+    fn load_texture(&mut self, value: &Option<String>) -> Result<Option<Arc<Texture>>, String> {
+        let name = match value.as_ref().and_then(|v| v.split_whitespace().last()) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let path = self.model_dir.join(name);
+        if let Some(texture) = self.textures.get(&path) {
+            return Ok(Some(texture.clone()));
+        }
+        let texture = Arc::new(try!(Texture::new(&path)));
+        self.textures.insert(path, texture.clone());
+
+        Ok(Some(texture))
+    }
+
+    // value of the option with one number, for example "-bm 0.5" in map_bump
+    fn texture_option(value: &Option<String>, name: &str) -> Option<f32> {
+        let words: Vec<&str> = value.as_ref().map(|v| v.split_whitespace().collect()).unwrap_or_default();
+        words.iter().position(|w| *w == name)
+            .and_then(|ind| words.get(ind + 1))
+            .and_then(|v| v.parse::<f32>().ok())
+    }
+
+    fn create_material(&mut self, m: &Material) -> Result<material::Material, String> {
+        let mut mat = material::Material::new();
+        if let Some(v) = m.ka {
+            mat.ambient = Vector3::new(v[0], v[1], v[2]).mul(255.0_f32);
+        }
+        if let Some(v) = m.kd {
+            mat.diffuse = Vector3::new(v[0], v[1], v[2]).mul(255.0_f32);
+        }
+        if let Some(v) = m.ks {
+            mat.specular = Vector3::new(v[0], v[1], v[2]).mul(255.0_f32);
+        }
+        if let Some(v) = m.ke {
+            mat.emissive = Vector3::new(v[0], v[1], v[2]).mul(255.0_f32);
+        }
+        if let Some(v) = m.ns {
+            mat.shininess = v;
+            mat.shininess_given = true;
+        }
+        // "d" - dissolve, "Tr" - transparency = 1 - d
+        mat.alpha = match (m.d, m.tr) {
+            (Some(d), _) => d,
+            (None, Some(tr)) => 1.0_f32 - tr,
+            (None, None) => 1.0_f32,
+        };
+        if let Some(v) = m.illum {
+            mat.illumination = v.max(0) as u32;
+        }
+        mat.texture = try!(self.load_texture(&m.map_kd));
+        mat.texture_specular = try!(self.load_texture(&m.map_ks));
+        mat.texture_bump = try!(self.load_texture(&m.map_bump));
+        if let Some(v) = ModelObj::texture_option(&m.map_bump, "-bm") {
+            mat.bump_scale = v;
+        }
+        mat.texture_alpha = try!(self.load_texture(&m.map_d));
+        if mat.alpha < 1.0_f32 || mat.texture_alpha.is_some() {
+            mat.blend = BlendMode::Alpha;
+            mat.render_state.depth_write = false;
+        } else if mat.alpha_texture().is_some() {
+            // the alpha channel of the diffuse texture cuts holes, e.g. in leaves and fences
+            mat.alpha_cutoff = Some(material::DEFAULT_ALPHA_CUTOFF);
+        }
+
+        Ok(mat)
+    }
+
     fn parse(&mut self, model_obj: &Obj<Rc<Material>>) -> Result<(), String> {
         for object in model_obj.object_iter() {
             for group in object.group_iter() {
@@ -71,23 +148,7 @@ impl<'a> ModelObj<'a> {
                 let material = group.material.clone();
                 mesh.material_id = match material {
                     Some(m) => {
-                        let mut mat = material::Material::new();
-                        match m.ka {
-                            Some(v) => mat.ambient = Vector3::new(v[0], v[1], v[2]).mul(255.0_f32),
-                            None => {},
-                        };
-                        match m.kd {
-                            Some(v) => mat.diffuse = Vector3::new(v[0], v[1], v[2]).mul(255.0_f32),
-                            None => {},
-                        };
-                        match m.ks {
-                            Some(v) => mat.specular = Vector3::new(v[0], v[1], v[2]).mul(255.0_f32),
-                            None => {},
-                        };
-                        match m.map_kd {
-                            Some(ref path) => try!(mat.create_texture(&self.model_dir.join(path).as_path())),
-                            None => {},
-                        };
+                        let mat = try!(self.create_material(&m));
                         self.model.material_list.push(mat);
                         self.model.material_list.len() - 1
                     },
@@ -128,11 +189,13 @@ impl<'a> ModelObj<'a> {
         let (min, max) = ModelObj::calc_aabb(model_obj.position());
         let mut this = ModelObj {
             model_dir: model_dir,
-            map: std::collections::HashMap::<IndexTuple, u32>::new(),
+            map: HashMap::<IndexTuple, u32>::new(),
+            textures: HashMap::<PathBuf, Arc<Texture>>::new(),
             model: Model::with_normalize(min, max),
             position_buffer: cast_to(model_obj.position()),
             normal_buffer: cast_to(model_obj.normal()),
             tex_buffer: Vec::<Vector2<f32>>::with_capacity(model_obj.texture().len()),
+            missing_normals: false,
         };
 
         for p in model_obj.texture() {
@@ -148,6 +211,12 @@ impl<'a> ModelObj<'a> {
         this.model.material_list.push(def_mat);
 
         try!(this.parse(&model_obj));
+        if this.missing_normals {
+            this.model.calc_smooth_normals(DEFAULT_CREASE_ANGLE);
+        }
+        if this.model.material_list.iter().any(|m| m.texture_bump.is_some()) {
+            this.model.calc_tangents();
+        }
 
         Ok(this.model)
     }
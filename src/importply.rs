@@ -0,0 +1,297 @@
+use utils;
+use material;
+use std::path::Path;
+use cgmath::{Vector2, Vector3};
+use mesh::{Model, Mesh, Vertex, DEFAULT_CREASE_ANGLE};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+struct Property {
+    name: String,
+    scalar: ScalarType,
+    list_count: Option<ScalarType>, // type of the length for list properties
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// values of the body in any format
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    format: Format,
+}
+
+// PLY loader: ascii and binary formats, vertices with optional normals,
+// texture coordinates and colors, faces are triangulated as fans
+pub struct ModelPly;
+
+impl ScalarType {
+    fn parse(name: &str) -> Result<ScalarType, String> {
+        match name {
+            "char" | "int8" => Ok(ScalarType::I8),
+            "uchar" | "uint8" => Ok(ScalarType::U8),
+            "short" | "int16" => Ok(ScalarType::I16),
+            "ushort" | "uint16" => Ok(ScalarType::U16),
+            "int" | "int32" => Ok(ScalarType::I32),
+            "uint" | "uint32" => Ok(ScalarType::U32),
+            "float" | "float32" => Ok(ScalarType::F32),
+            "double" | "float64" => Ok(ScalarType::F64),
+            _ => Err(format!("ply: unknown type \"{}\"", name)),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match *self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+impl<'a> Reader<'a> {
+    fn ascii(&mut self) -> Result<f64, String> {
+        while self.pos < self.data.len() && (self.data[self.pos] as char).is_whitespace() {
+            self.pos += 1;
+        }
+        let begin = self.pos;
+        while self.pos < self.data.len() && !(self.data[self.pos] as char).is_whitespace() {
+            self.pos += 1;
+        }
+        let token = String::from_utf8_lossy(&self.data[begin..self.pos]);
+        match token.parse::<f64>() {
+            Ok(v) => Ok(v),
+            Err(_) => Err(format!("ply: invalid value \"{}\"", token)),
+        }
+    }
+
+    fn value(&mut self, scalar: ScalarType) -> Result<f64, String> {
+        if self.format == Format::Ascii {
+            return self.ascii();
+        }
+        let size = scalar.size();
+        if self.pos + size > self.data.len() {
+            return Err(String::from("ply: unexpected end of the file"));
+        }
+        let mut bytes = [0_u8; 8];
+        bytes[..size].copy_from_slice(&self.data[self.pos..self.pos + size]);
+        if self.format == Format::BinaryBigEndian {
+            bytes[..size].reverse();
+        }
+        self.pos += size;
+
+        let b = bytes;
+        Ok(match scalar {
+            ScalarType::I8 => b[0] as i8 as f64,
+            ScalarType::U8 => b[0] as f64,
+            ScalarType::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            ScalarType::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            ScalarType::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            ScalarType::F64 => f64::from_le_bytes(b),
+        })
+    }
+}
+
+impl ModelPly {
+    // returns format, elements and size of the header
+    fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, usize), String> {
+        const END_HEADER: &'static [u8] = b"end_header";
+        let end = match data.windows(END_HEADER.len()).position(|w| w == END_HEADER) {
+            Some(pos) => pos,
+            None => return Err(String::from("ply: not found end_header")),
+        };
+        // the body starts after the end of the line
+        let body = match data[end..].iter().position(|c| *c == b'\n') {
+            Some(pos) => end + pos + 1,
+            None => data.len(),
+        };
+
+        let header = String::from_utf8_lossy(&data[..end]);
+        let mut lines = header.lines();
+        if lines.next().map(|l| l.trim()) != Some("ply") {
+            return Err(String::from("ply: invalid magic"));
+        }
+        let mut format = None;
+        let mut elements = Vec::<Element>::new();
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.first().cloned() {
+                Some("format") => {
+                    format = Some(match words.get(1).cloned() {
+                        Some("ascii") => Format::Ascii,
+                        Some("binary_little_endian") => Format::BinaryLittleEndian,
+                        Some("binary_big_endian") => Format::BinaryBigEndian,
+                        _ => return Err(format!("ply: unknown format \"{}\"", line)),
+                    });
+                },
+                Some("element") if words.len() == 3 => {
+                    elements.push(Element {
+                        name: String::from(words[1]),
+                        count: try!(words[2].parse::<usize>().map_err(|_| format!("ply: invalid element \"{}\"", line))),
+                        properties: Vec::<Property>::new(),
+                    });
+                },
+                Some("property") => {
+                    let property = match (words.get(1).cloned(), words.len()) {
+                        (Some("list"), 5) => Property {
+                            name: String::from(words[4]),
+                            scalar: try!(ScalarType::parse(words[3])),
+                            list_count: Some(try!(ScalarType::parse(words[2]))),
+                        },
+                        (Some(scalar), 3) => Property {
+                            name: String::from(words[2]),
+                            scalar: try!(ScalarType::parse(scalar)),
+                            list_count: None,
+                        },
+                        _ => return Err(format!("ply: invalid property \"{}\"", line)),
+                    };
+                    match elements.last_mut() {
+                        Some(element) => element.properties.push(property),
+                        None => return Err(String::from("ply: property without element")),
+                    }
+                },
+                Some("comment") | Some("obj_info") | None => {},
+                _ => return Err(format!("ply: unknown header line \"{}\"", line)),
+            }
+        }
+
+        match format {
+            Some(format) => Ok((format, elements, body)),
+            None => Err(String::from("ply: not found format")),
+        }
+    }
+
+    // color components are bytes or floats in [0; 1]
+    fn color_scale(scalar: ScalarType) -> f32 {
+        match scalar {
+            ScalarType::F32 | ScalarType::F64 => 255.0_f32,
+            ScalarType::U16 => 255.0_f32 / 65535.0_f32,
+            _ => 1.0_f32,
+        }
+    }
+
+    // smooth_normals is used if the file has no normals:
+    // normals are smoothed except sharp edges, otherwise every triangle is flat
+    pub fn load(filename: &Path, smooth_normals: bool) -> Result<Model, String> {
+        let model_path = try!(utils::get_base_dir()).join(filename);
+        println!("load model: \"{}\"", model_path.display());
+        let data = try!(utils::read_file(&model_path));
+        let (format, elements, body) = try!(ModelPly::parse_header(&data));
+
+        let mut model = Model::new();
+        let mut mesh = Mesh::new();
+        let mut has_normals = false;
+        let mut has_colors = false;
+        let mut reader = Reader {
+            data: &data[body..],
+            pos: 0,
+            format: format,
+        };
+        for element in &elements {
+            let find = |names: &[&str]| element.properties.iter().position(|p| names.contains(&p.name.as_str()));
+            let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+            let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+            let tex = [find(&["u", "s", "texture_u"]), find(&["v", "t", "texture_v"])];
+            let color = [find(&["red", "diffuse_red"]), find(&["green", "diffuse_green"]), find(&["blue", "diffuse_blue"])];
+            let indices = find(&["vertex_indices", "vertex_index"]);
+            let is_vertex = element.name == "vertex";
+            if is_vertex {
+                has_normals = normal.iter().all(Option::is_some);
+                has_colors = color.iter().all(Option::is_some);
+            }
+
+            let mut values = Vec::<f64>::with_capacity(element.properties.len());
+            let mut list = Vec::<f64>::new();
+            for _ in 0..element.count {
+                values.clear();
+                for (ind, property) in element.properties.iter().enumerate() {
+                    match property.list_count {
+                        Some(count_type) => {
+                            let count = try!(reader.value(count_type)) as usize;
+                            let is_indices = element.name == "face" && Some(ind) == indices;
+                            if is_indices {
+                                list.clear();
+                            }
+                            for _ in 0..count {
+                                let v = try!(reader.value(property.scalar));
+                                if is_indices {
+                                    list.push(v);
+                                }
+                            }
+                            values.push(0.0_f64);
+                        },
+                        None => values.push(try!(reader.value(property.scalar))),
+                    }
+                }
+
+                if is_vertex {
+                    let get = |ind: Option<usize>| ind.map(|i| values[i] as f32).unwrap_or(0.0_f32);
+                    let mut vertex = Vertex::new(&Vector3::new(get(position[0]), get(position[1]), get(position[2])),
+                                                 &Vector2::new(get(tex[0]), get(tex[1])),
+                                                 &Vector3::new(get(normal[0]), get(normal[1]), get(normal[2])));
+                    if has_colors {
+                        let scale = |ind: Option<usize>| ModelPly::color_scale(element.properties[ind.unwrap()].scalar);
+                        vertex.color = Vector3::new(get(color[0]) * scale(color[0]),
+                                                    get(color[1]) * scale(color[1]),
+                                                    get(color[2]) * scale(color[2]));
+                    }
+                    model.vertex_buffer.push(vertex);
+                } else if element.name == "face" && indices.is_some() {
+                    for ind in 2..list.len() {
+                        for v in &[list[0], list[ind - 1], list[ind]] {
+                            if *v < 0.0_f64 || *v as usize >= model.vertex_buffer.len() {
+                                return Err(format!("ply: invalid index of vertex {}", v));
+                            }
+                            mesh.index_buffer.push(*v as u32);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut def_mat = material::Material::new();
+        if has_colors {
+            def_mat.diffuse = Vector3::new(255.0_f32, 255.0_f32, 255.0_f32);
+            def_mat.vertex_color = true;
+        } else {
+            def_mat.diffuse = Vector3::new(200.0_f32, 200.0_f32, 200.0_f32);
+        }
+        def_mat.ambient = def_mat.diffuse;
+        model.material_list.push(def_mat);
+        model.mesh_list.push(mesh);
+        if !has_normals {
+            if smooth_normals {
+                model.calc_smooth_normals(DEFAULT_CREASE_ANGLE);
+            } else {
+                model.calc_flat_normals();
+            }
+        }
+        model.calc_bounds();
+
+        Ok(model)
+    }
+}
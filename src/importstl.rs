@@ -0,0 +1,120 @@
+use utils;
+use material;
+use std::collections::HashMap;
+use std::path::Path;
+use cgmath::{Vector2, Vector3};
+use mesh::{Model, Mesh, Vertex, DEFAULT_CREASE_ANGLE};
+
+const BINARY_HEADER_SIZE: usize = 84;
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+// STL loader, binary and ASCII. Normals of facets are not used,
+// they are often zero or inconsistent with the order of vertices.
+pub struct ModelStl {
+    map: HashMap<[u32; 3], u32>, // equal positions are merged into one vertex
+    model: Model,
+}
+
+fn read_f32(data: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+impl ModelStl {
+    fn add_vertex(&mut self, position: Vector3<f32>) {
+        // +0.0 and -0.0 are the same point
+        let key = [(position.x + 0.0_f32).to_bits(), (position.y + 0.0_f32).to_bits(), (position.z + 0.0_f32).to_bits()];
+        let vertex_buffer = &mut self.model.vertex_buffer;
+        let ind = *self.map.entry(key).or_insert_with(|| {
+            vertex_buffer.push(Vertex::new(&position, &Vector2::new(0.0_f32, 0.0_f32), &Vector3::new(0.0_f32, 0.0_f32, 0.0_f32)));
+            vertex_buffer.len() as u32 - 1
+        });
+        self.model.mesh_list[0].index_buffer.push(ind);
+    }
+
+    // size of the binary file is defined by the number of triangles,
+    // ASCII file starts with "solid", but some binary files too
+    fn is_binary(data: &[u8]) -> bool {
+        if data.len() >= BINARY_HEADER_SIZE {
+            let cnt = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+            if BINARY_HEADER_SIZE + cnt * BINARY_TRIANGLE_SIZE == data.len() {
+                return true;
+            }
+        }
+
+        !data.starts_with(b"solid")
+    }
+
+    fn parse_binary(&mut self, data: &[u8]) -> Result<(), String> {
+        if data.len() < BINARY_HEADER_SIZE {
+            return Err(String::from("stl: file is too short"));
+        }
+        let cnt = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+        if BINARY_HEADER_SIZE + cnt * BINARY_TRIANGLE_SIZE > data.len() {
+            return Err(format!("stl: file is too short for {} triangles", cnt));
+        }
+        for triangle in data[BINARY_HEADER_SIZE..].chunks(BINARY_TRIANGLE_SIZE).take(cnt) {
+            // normal, 3 vertices, attribute
+            for offset in &[12, 24, 36] {
+                self.add_vertex(Vector3::new(read_f32(triangle, *offset),
+                                             read_f32(triangle, offset + 4),
+                                             read_f32(triangle, offset + 8)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_ascii(&mut self, data: &[u8]) -> Result<(), String> {
+        let text = String::from_utf8_lossy(data);
+        let mut tokens = text.split_whitespace();
+        while let Some(token) = tokens.next() {
+            if token != "vertex" {
+                continue;
+            }
+            let mut position = [0.0_f32; 3];
+            for value in &mut position {
+                *value = match tokens.next().and_then(|v| v.parse::<f32>().ok()) {
+                    Some(v) => v,
+                    None => return Err(String::from("stl: invalid vertex")),
+                };
+            }
+            self.add_vertex(Vector3::new(position[0], position[1], position[2]));
+        }
+        if self.model.mesh_list[0].index_buffer.len() % 3 != 0 {
+            return Err(String::from("stl: number of vertices is not a multiple of 3"));
+        }
+
+        Ok(())
+    }
+
+    // smooth_normals: normals are smoothed except sharp edges, otherwise every triangle is flat
+    pub fn load(filename: &Path, smooth_normals: bool) -> Result<Model, String> {
+        let model_path = try!(utils::get_base_dir()).join(filename);
+        println!("load model: \"{}\"", model_path.display());
+        let data = try!(utils::read_file(&model_path));
+
+        let mut this = ModelStl {
+            map: HashMap::<[u32; 3], u32>::new(),
+            model: Model::new(),
+        };
+        this.model.mesh_list.push(Mesh::new());
+        if ModelStl::is_binary(&data) {
+            try!(this.parse_binary(&data));
+        } else {
+            try!(this.parse_ascii(&data));
+        }
+
+        let mut def_mat = material::Material::new();
+        def_mat.diffuse = Vector3::new(200.0_f32, 200.0_f32, 200.0_f32);
+        def_mat.ambient = def_mat.diffuse;
+        this.model.material_list.push(def_mat);
+        if smooth_normals {
+            this.model.calc_smooth_normals(DEFAULT_CREASE_ANGLE);
+        } else {
+            this.model.calc_flat_normals();
+        }
+        this.model.calc_bounds();
+
+        Ok(this.model)
+    }
+}
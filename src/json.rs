@@ -0,0 +1,264 @@
+use std;
+use std::collections::BTreeMap;
+
+// minimal JSON document for the importers
+#[derive(Clone, PartialEq, Debug)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            data: text.as_bytes(),
+            pos: 0,
+        };
+        let value = try!(parser.value(0));
+        parser.skip_spaces();
+        if parser.pos != parser.data.len() {
+            return Err(parser.error("unexpected data after the end of the document"));
+        }
+
+        Ok(value)
+    }
+
+    // value of the object field, None for other types
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref fields) => fields.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Json::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Json::Number(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // non-negative integer numbers only
+    pub fn as_usize(&self) -> Option<usize> {
+        match *self {
+            Json::Number(v) if v >= 0.0_f64 && v.fract() == 0.0_f64 && v <= std::usize::MAX as f64 => Some(v as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Json::String(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match *self {
+            Json::Array(ref v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+// nesting limit, protects the stack from broken files
+const MAX_DEPTH: usize = 256;
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &str) -> String {
+        format!("json: {} at byte {}", msg, self.pos)
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.pos < self.data.len() {
+            match self.data[self.pos] {
+                b' ' | b'\t' | b'\n' | b'\r' => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), String> {
+        if self.data[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected \"{}\"", literal)))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("too deep nesting"));
+        }
+        self.skip_spaces();
+        match self.peek() {
+            Some(b'{') => self.object(depth),
+            Some(b'[') => self.array(depth),
+            Some(b'"') => Ok(Json::String(try!(self.string()))),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected symbol")),
+            None => Err(self.error("unexpected end of the document")),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Json, String> {
+        let mut fields = BTreeMap::<String, Json>::new();
+        self.pos += 1;
+        self.skip_spaces();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_spaces();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected name of the field"));
+            }
+            let key = try!(self.string());
+            self.skip_spaces();
+            try!(self.expect(":"));
+            let value = try!(self.value(depth + 1));
+            fields.insert(key, value);
+            self.skip_spaces();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                },
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Json, String> {
+        let mut items = Vec::<Json>::new();
+        self.pos += 1;
+        self.skip_spaces();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(try!(self.value(depth + 1)));
+            self.skip_spaces();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                },
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let begin = self.pos;
+        while let Some(c) = self.peek() {
+            match c {
+                b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E' => self.pos += 1,
+                _ => break,
+            }
+        }
+        // the slice contains only ascii symbols
+        let text = std::str::from_utf8(&self.data[begin..self.pos]).unwrap();
+        match text.parse::<f64>() {
+            Ok(v) => Ok(Json::Number(v)),
+            Err(_) => Err(self.error(&format!("invalid number \"{}\"", text))),
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = match self.data.get(self.pos..self.pos + 4).and_then(|v| std::str::from_utf8(v).ok()) {
+            Some(v) => v,
+            None => return Err(self.error("invalid escape sequence")),
+        };
+        match u32::from_str_radix(digits, 16) {
+            Ok(v) => {
+                self.pos += 4;
+                Ok(v)
+            },
+            Err(_) => Err(self.error("invalid escape sequence")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let mut result = Vec::<u8>::new();
+        self.pos += 1;
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let escape = match self.peek() {
+                        Some(c) => c,
+                        None => return Err(self.error("unterminated string")),
+                    };
+                    self.pos += 1;
+                    let ch = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = try!(self.hex4());
+                            // surrogate pair
+                            if code >= 0xD800 && code < 0xDC00 {
+                                try!(self.expect("\\u"));
+                                let low = try!(self.hex4());
+                                if low < 0xDC00 || low >= 0xE000 {
+                                    return Err(self.error("invalid surrogate pair"));
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            match std::char::from_u32(code) {
+                                Some(ch) => ch,
+                                None => return Err(self.error("invalid escape sequence")),
+                            }
+                        },
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    let mut buf = [0_u8; 4];
+                    result.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                },
+                _ => result.push(c),
+            }
+        }
+
+        // the source is valid utf8 and escapes are encoded correctly
+        Ok(String::from_utf8(result).unwrap())
+    }
+}
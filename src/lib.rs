@@ -0,0 +1,55 @@
+extern crate obj;
+extern crate genmesh;
+extern crate sdl2;
+extern crate sdl2_image;
+extern crate time;
+extern crate cgmath;
+
+pub mod mesh;
+pub mod utils;
+pub mod scene;
+pub mod camera;
+pub mod shadow;
+mod tests;
+pub mod device;
+mod memory;
+mod json;
+pub mod imagefile;
+pub mod light;
+pub mod framebuffer;
+pub mod shader;
+pub mod texture;
+pub mod tiler;
+pub mod line;
+pub mod clipping;
+pub mod blend;
+pub mod renderstate;
+pub mod material;
+pub mod importobj;
+pub mod importgltf;
+pub mod importstl;
+pub mod importply;
+pub mod exportobj;
+pub mod generator;
+mod halfspace;
+mod rasterization;
+
+pub use scene::Scene;
+pub use camera::{Camera, Projection, Frustum};
+pub use device::{InputEvent, MouseButton, Keycode};
+pub use mesh::{Model, Mesh, Vertex};
+pub use material::Material;
+pub use texture::{Texture, TextureCube};
+pub use light::{Light, LightType};
+pub use shadow::ShadowMap;
+pub use framebuffer::{FrameBuffer, Antialiasing};
+pub use clipping::ClipMode;
+pub use blend::BlendMode;
+pub use renderstate::{RenderState, CullMode, Winding, DepthFunc};
+pub use tiler::{RasterizerType, PolygonMode};
+pub use line::LineRasterizer;
+pub use importobj::ModelObj;
+pub use importgltf::ModelGltf;
+pub use importstl::ModelStl;
+pub use importply::ModelPly;
+pub use shader::{Shader, ShaderType, Uniforms, VertexShader, PixelShader, Varyings};
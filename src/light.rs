@@ -0,0 +1,132 @@
+use cgmath::*;
+use std::sync::Arc;
+use std::ops::{Sub, Mul};
+use shadow::ShadowMap;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LightType {
+    // parallel rays along direction, without attenuation
+    Directional,
+    // from position in all directions, with attenuation
+    Point,
+    // from position in the cone around direction, with attenuation
+    Spot,
+}
+
+#[derive(Clone)]
+pub struct Light {
+    pub light_type: LightType,
+    pub position: Vector3<f32>,    // in world space, for Point and Spot
+    pub direction: Vector3<f32>,   // normalized direction of rays, for Directional and Spot
+    pub color: Vector3<f32>,       // {r, g, b}, [0; 1]
+    pub intensity: f32,
+    pub attenuation: Vector3<f32>, // {constant, linear, quadratic}
+    pub cos_inner: f32,            // full intensity inside the inner cone
+    pub cos_outer: f32,            // no light outside the outer cone
+    pub shadow: Option<Arc<ShadowMap>>,
+}
+
+impl Light {
+    fn new(light_type: LightType) -> Light {
+        Light {
+            light_type: light_type,
+            position: Vector3::<f32>::zero(),
+            direction: Vector3::new(0.0_f32, 0.0_f32, -1.0_f32),
+            color: Vector3::new(1.0_f32, 1.0_f32, 1.0_f32),
+            intensity: 1.0_f32,
+            attenuation: Vector3::new(1.0_f32, 0.0_f32, 0.0_f32),
+            cos_inner: -1.0_f32,
+            cos_outer: -1.0_f32,
+            shadow: None,
+        }
+    }
+
+    pub fn directional(direction: Vector3<f32>, color: Vector3<f32>, intensity: f32) -> Light {
+        let mut light = Light::new(LightType::Directional);
+        light.direction = direction.normalize();
+        light.color = color;
+        light.intensity = intensity;
+
+        light
+    }
+
+    pub fn point(position: Vector3<f32>, color: Vector3<f32>, intensity: f32) -> Light {
+        let mut light = Light::new(LightType::Point);
+        light.position = position;
+        light.color = color;
+        light.intensity = intensity;
+
+        light
+    }
+
+    // inner and outer are half-angles of the cones
+    pub fn spot<A: Into<Rad<f32>>>(position: Vector3<f32>,
+                                   direction: Vector3<f32>,
+                                   inner: A,
+                                   outer: A,
+                                   color: Vector3<f32>,
+                                   intensity: f32) -> Light {
+        let inner: Rad<f32> = inner.into();
+        let outer: Rad<f32> = outer.into();
+        let mut light = Light::new(LightType::Spot);
+        light.position = position;
+        light.direction = direction.normalize();
+        light.cos_inner = inner.cos();
+        light.cos_outer = outer.cos().min(light.cos_inner);
+        light.color = color;
+        light.intensity = intensity;
+
+        light
+    }
+
+    // intensity = 1 / (constant + linear * distance + quadratic * distance^2)
+    pub fn with_attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> Light {
+        self.attenuation = Vector3::new(constant, linear, quadratic);
+
+        self
+    }
+
+    // only for directional and spot lights, see Scene::draw_shadow
+    pub fn with_shadow(mut self, shadow: ShadowMap) -> Light {
+        assert!(self.light_type != LightType::Point, "shadow is not supported for point light");
+        self.shadow = Some(Arc::new(shadow));
+
+        self
+    }
+
+    #[inline]
+    fn visibility(&self, pos: Vector3<f32>, to_light: Vector3<f32>) -> f32 {
+        match self.shadow {
+            Some(ref shadow) => shadow.visibility(pos, to_light),
+            None => 1.0_f32,
+        }
+    }
+
+    // returns normalized direction from the point to the light and color of the light at the point
+    pub fn illuminate(&self, pos: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        if self.light_type == LightType::Directional {
+            let to_light = self.direction.mul(-1.0_f32);
+            let visibility = self.visibility(pos, to_light);
+            return (to_light, self.color.mul(self.intensity * visibility));
+        }
+
+        let to_light = self.position.sub(&pos);
+        let distance = to_light.length();
+        let to_light = to_light.mul(1.0_f32 / distance.max(0.0000001_f32));
+        let attenuation = self.attenuation.x + (self.attenuation.y + self.attenuation.z * distance) * distance;
+        let mut k = self.intensity * self.visibility(pos, to_light) / attenuation.max(0.0000001_f32);
+        if self.light_type == LightType::Spot {
+            let cos_angle = -to_light.dot(self.direction);
+            k *= if cos_angle >= self.cos_inner {
+                1.0_f32
+            } else if cos_angle <= self.cos_outer {
+                0.0_f32
+            } else {
+                let t = (cos_angle - self.cos_outer) / (self.cos_inner - self.cos_outer);
+                t * t * (3.0_f32 - 2.0_f32 * t)
+            };
+        }
+
+        (to_light, self.color.mul(k))
+    }
+}
@@ -0,0 +1,171 @@
+use std::cmp;
+use cgmath::*;
+use framebuffer::Tile;
+use blend::{blend, BlendMode};
+use memory::u32_to_vector3;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LineRasterizer {
+    // one pixel per step of the major axis, see bresenham
+    Bresenham,
+    // anti-aliased, two pixels per step blended by coverage, see wu
+    Wu,
+}
+
+// lines and points are drawn a bit nearer than they are,
+// so edges of PolygonMode::FillLine are not hidden by their own triangles
+const DEPTH_BIAS: f32 = 0.0001_f32;
+
+// clips the segment by the rectangle [0; x_size] x [0; y_size] (Liang-Barsky)
+fn clip(a: Vector4<f32>, b: Vector4<f32>, x_size: f32, y_size: f32) -> Option<(Vector4<f32>, Vector4<f32>)> {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let mut t0 = 0.0_f32;
+    let mut t1 = 1.0_f32;
+    for &(p, q) in &[(-dx, a.x), (dx, x_size - a.x), (-dy, a.y), (dy, y_size - a.y)] {
+        if p == 0.0_f32 {
+            if q < 0.0_f32 {
+                return None;
+            }
+        } else if p < 0.0_f32 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+        if t0 > t1 {
+            return None;
+        }
+    }
+
+    Some((a.lerp(b, t0), a.lerp(b, t1)))
+}
+
+// index of the pixel with the coordinate val, the right border of the framebuffer is included
+#[inline]
+fn pixel(val: f32, size: usize) -> i64 {
+    cmp::min(val.floor() as i64, size as i64 - 1)
+}
+
+// steps [begin; end) of [0; n] which can touch rows of the tile, y(i) = y0 + i * y_step
+fn tile_steps(tile: &Tile, y0: f32, y_step: f32, n: i64) -> (i64, i64) {
+    if y_step == 0.0_f32 {
+        let y = y0.floor() as i64;
+        return if y >= tile.y_begin as i64 && y < tile.y_end as i64 { (0, n + 1) } else { (0, 0) };
+    }
+    let i0 = (tile.y_begin as f32 - 1.0_f32 - y0) / y_step;
+    let i1 = (tile.y_end as f32 + 1.0_f32 - y0) / y_step;
+
+    (cmp::max(i0.min(i1).floor() as i64, 0), cmp::min(i0.max(i1).ceil() as i64 + 1, n + 1))
+}
+
+// depth test without the depth write, coverage in [0; 1] blends the color with the framebuffer,
+// with multisampling all samples of the pixel are tested and written
+#[inline]
+fn plot(tile: &mut Tile, x: i64, y: i64, depth: f32, color: u32, coverage: f32) {
+    if x < 0 || x >= tile.x_size as i64 || y < tile.y_begin as i64 || y >= tile.y_end as i64 || coverage <= 0.0_f32 {
+        return;
+    }
+    let begin = ((y as usize - tile.y_begin) * tile.x_size + x as usize) * tile.samples;
+    for ind in begin..begin + tile.samples {
+        if depth + DEPTH_BIAS >= tile.zbuffer[ind] {
+            tile.cbuffer[ind] = if coverage >= 1.0_f32 {
+                color
+            } else {
+                blend(BlendMode::Alpha, u32_to_vector3(color).extend(coverage), tile.cbuffer[ind])
+            };
+        }
+    }
+}
+
+// Bresenham line between pixels with points a and b, points are the same as for rasterization::triangle.
+// The pixel of the minor axis is computed for every step by the midpoint rule,
+// so a tile can start from its first row and the result does not depend on tiles.
+pub fn bresenham(tile: &mut Tile, a: Vector4<f32>, b: Vector4<f32>, color: u32) {
+    let (a, b) = match clip(a, b, tile.x_size as f32, tile.y_size as f32) {
+        Some(v) => v,
+        None => return,
+    };
+    let x0 = pixel(a.x, tile.x_size);
+    let y0 = pixel(a.y, tile.y_size);
+    let dx = pixel(b.x, tile.x_size) - x0;
+    let dy = pixel(b.y, tile.y_size) - y0;
+    let n = cmp::max(dx.abs(), dy.abs());
+    if n == 0 {
+        plot(tile, x0, y0, a.z.max(b.z), color, 1.0_f32);
+        return;
+    }
+
+    let is_steep = dy.abs() > dx.abs();
+    let (major, minor) = if is_steep { (dy, dx) } else { (dx, dy) };
+    let (begin, end) = tile_steps(tile, y0 as f32, dy as f32 / n as f32, n);
+    for i in begin..end {
+        let offset = minor.signum() * ((2 * i * minor.abs() + n) / (2 * n));
+        let (x, y) = if is_steep {
+            (x0 + offset, y0 + major.signum() * i)
+        } else {
+            (x0 + major.signum() * i, y0 + offset)
+        };
+        plot(tile, x, y, a.z + (b.z - a.z) * (i as f32 / n as f32), color, 1.0_f32);
+    }
+}
+
+// Xiaolin Wu anti-aliased line, the coverage of two pixels across the line
+// is proportional to the distance of the line from their centers
+pub fn wu(tile: &mut Tile, a: Vector4<f32>, b: Vector4<f32>, color: u32) {
+    let (a, b) = match clip(a, b, tile.x_size as f32, tile.y_size as f32) {
+        Some(v) => v,
+        None => return,
+    };
+    // pixel centers are at integer coordinates
+    let is_steep = (b.y - a.y).abs() > (b.x - a.x).abs();
+    let (mut a, mut b) = if is_steep {
+        (Vector3::new(a.y - 0.5_f32, a.x - 0.5_f32, a.z), Vector3::new(b.y - 0.5_f32, b.x - 0.5_f32, b.z))
+    } else {
+        (Vector3::new(a.x - 0.5_f32, a.y - 0.5_f32, a.z), Vector3::new(b.x - 0.5_f32, b.y - 0.5_f32, b.z))
+    };
+    if a.x > b.x {
+        ::std::mem::swap(&mut a, &mut b);
+    }
+    let length = b.x - a.x;
+    let gradient = if length > 0.0_f32 { (b.y - a.y) / length } else { 0.0_f32 };
+    let start = a.x.round() as i64;
+    let n = b.x.round() as i64 - start;
+    let minor0 = a.y + gradient * (start as f32 - a.x);
+
+    let (begin, end) = if is_steep {
+        tile_steps(tile, start as f32, 1.0_f32, n)
+    } else {
+        tile_steps(tile, minor0, gradient, n)
+    };
+    for i in begin..end {
+        let major = start + i;
+        let minor = minor0 + gradient * i as f32;
+        let minor_int = minor.floor();
+        let frac = minor - minor_int;
+        let t = if length > 0.0_f32 { ((major as f32 - a.x) / length).max(0.0_f32).min(1.0_f32) } else { 0.0_f32 };
+        let depth = a.z + (b.z - a.z) * t;
+        let minor_int = minor_int as i64;
+        if is_steep {
+            plot(tile, minor_int, major, depth, color, 1.0_f32 - frac);
+            plot(tile, minor_int + 1, major, depth, color, frac);
+        } else {
+            plot(tile, major, minor_int, depth, color, 1.0_f32 - frac);
+            plot(tile, major, minor_int + 1, depth, color, frac);
+        }
+    }
+}
+
+// square of size x size pixels around the pixel with the point p
+pub fn point(tile: &mut Tile, p: Vector4<f32>, size: u32, color: u32) {
+    if !(p.x >= 0.0_f32 && p.x <= tile.x_size as f32 && p.y >= 0.0_f32 && p.y <= tile.y_size as f32) {
+        return;
+    }
+    let x = pixel(p.x, tile.x_size);
+    let y = pixel(p.y, tile.y_size);
+    let size = size as i64;
+    for py in (y - (size - 1) / 2)..(y + size / 2 + 1) {
+        for px in (x - (size - 1) / 2)..(x + size / 2 + 1) {
+            plot(tile, px, py, p.z, color, 1.0_f32);
+        }
+    }
+}
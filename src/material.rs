@@ -1,15 +1,38 @@
 use std;
 use cgmath::*;
-use std::rc::Rc;
+use std::sync::Arc;
 use texture::{Texture, TextureCube};
+use blend::BlendMode;
+use renderstate::RenderState;
+
+// threshold of alpha for cutout textures, the same as the default in glTF
+pub const DEFAULT_ALPHA_CUTOFF: f32 = 0.5_f32;
 
 #[derive(Clone)]
 pub struct Material {
-    pub ambient: Vector3<f32>,
-    pub diffuse: Vector3<f32>,
-    pub specular: Vector3<f32>,
-    pub texture: Option<Rc<Texture>>,
-    pub texture_cube: Option<Rc<TextureCube>>,
+    pub ambient: Vector3<f32>,                            // {r, g, b}, [0; 255]
+    pub diffuse: Vector3<f32>,                            // {r, g, b}, [0; 255]
+    pub specular: Vector3<f32>,                           // {r, g, b}, [0; 255]
+    pub emissive: Vector3<f32>,                           // {r, g, b}, [0; 255], added to the lit color
+    pub shininess: f32,                                   // specular exponent
+    pub shininess_given: bool,                            // set by the file, otherwise Cook-Torrance uses its roughness
+    pub alpha: f32,                                       // [0; 1], 1 - opaque
+    pub illumination: u32,                                // 0 - color, 1 - ambient and diffuse, 2 - with specular
+    pub metallic: f32,                                    // [0; 1]
+    pub roughness: f32,                                   // [0; 1]
+    pub vertex_color: bool,                               // ambient and diffuse colors are multiplied by Vertex::color
+    pub bump_scale: f32,                                  // multiplies x and y of the normal map
+    pub blend: BlendMode,                                 // not opaque meshes are drawn after opaque ones
+    pub render_state: RenderState,                        // culling, depth test and writes
+    pub alpha_cutoff: Option<f32>,                        // pixels with lower alpha are discarded, e.g. leaves
+    pub texture: Option<Arc<Texture>>,                    // diffuse color
+    pub texture_specular: Option<Arc<Texture>>,           // multiplies specular color
+    pub texture_bump: Option<Arc<Texture>>,               // normal map in tangent space, see Vertex::tangent
+    pub texture_alpha: Option<Arc<Texture>>,              // red channel multiplies alpha, or alpha of texture
+    pub texture_metallic_roughness: Option<Arc<Texture>>, // green - roughness, blue - metallic, multiply the factors
+    pub texture_occlusion: Option<Arc<Texture>>,          // red channel - ambient occlusion
+    pub texture_emissive: Option<Arc<Texture>>,           // multiplies emissive color
+    pub texture_cube: Option<Arc<TextureCube>>,
 }
 
 impl Material {
@@ -18,18 +41,51 @@ impl Material {
             ambient: Vector3::<f32>::zero(),
             diffuse: Vector3::<f32>::zero(),
             specular: Vector3::<f32>::zero(),
+            emissive: Vector3::<f32>::zero(),
+            shininess: 5.0_f32,
+            shininess_given: false,
+            alpha: 1.0_f32,
+            illumination: 2,
+            metallic: 0.0_f32,
+            roughness: 1.0_f32,
+            vertex_color: false,
+            bump_scale: 1.0_f32,
+            blend: BlendMode::Opaque,
+            render_state: RenderState::new(),
+            alpha_cutoff: None,
             texture: None,
+            texture_specular: None,
+            texture_bump: None,
+            texture_alpha: None,
+            texture_metallic_roughness: None,
+            texture_occlusion: None,
+            texture_emissive: None,
             texture_cube: None,
         }
     }
 
     pub fn create_texture(&mut self, path: &std::path::Path) -> Result<(), String> {
-        self.texture = Some(Rc::new(try!(Texture::new(path))));
+        self.texture = Some(Arc::new(try!(Texture::new(path))));
 
         Ok(())
     }
 
-    pub fn add_texture_cube(&mut self, texture: Rc<TextureCube>) {
+    pub fn add_texture_cube(&mut self, texture: Arc<TextureCube>) {
         self.texture_cube = Some(texture);
-        }
+    }
+
+    // drawn in the transparent pass, see Tiler::flush_transparent
+    pub fn is_transparent(&self) -> bool {
+        self.blend != BlendMode::Opaque
+    }
+
+    // alpha map: texture_alpha or the alpha channel of the texture
+    pub fn alpha_texture(&self) -> Option<&Arc<Texture>> {
+        self.texture_alpha.as_ref().or(self.texture.as_ref().and_then(|t| t.alpha()))
+    }
+
+    // the texture which defines mip level of the triangle
+    pub fn base_texture(&self) -> Option<&Arc<Texture>> {
+        self.texture.as_ref().or(self.texture_specular.as_ref())
+    }
 }
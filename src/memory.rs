@@ -32,3 +32,10 @@ pub fn vector3_to_u32(vec: &Vector3<f32>) -> u32 {
      (std::cmp::min(std::cmp::max((vec.z as i32), 0), 0xFF))
      ) as u32
 }
+
+#[inline]
+pub fn u32_to_vector3(val: u32) -> Vector3<f32> {
+    Vector3::new(((val >> 16) & 0xFF) as f32,
+                 ((val >> 8) & 0xFF) as f32,
+                 (val & 0xFF) as f32)
+}
@@ -1,10 +1,13 @@
 use std;
 use cgmath::*;
 use shader::*;
-use device::Device;
+use framebuffer::FrameBuffer;
 use material::Material;
-use rasterization::triangle;
+use tiler::{Tiler, ScreenTriangle};
+use clipping::{ClipMode, ClipVertex, MAX_CLIP_VERTICES, clip_triangle};
 use texture::TextureCube;
+use std::hash::Hash;
+use std::collections::HashMap;
 use std::ops::{Sub, Add, Mul};
 
 #[derive(Copy,Clone)]
@@ -12,6 +15,8 @@ pub struct Vertex {
     pub position: Vector3<f32>,
     pub normal: Vector3<f32>,
     pub tex: Vector2<f32>,
+    pub tangent: Vector4<f32>, // xyz - tangent, w - handedness: bitangent = cross(normal, tangent) * w
+    pub color: Vector3<f32>,   // {r, g, b}, [0; 255], used if Material::vertex_color is set
 }
 
 pub struct Mesh {
@@ -28,14 +33,61 @@ pub struct Model {
     normalize: bool,
 }
 
+// smoothing of normals in the loaders for files without normals
+pub const DEFAULT_CREASE_ANGLE: Deg<f32> = Deg { s: 60.0_f32 };
+
+// +0.0 and -0.0 are the same
+fn vector_key(v: Vector3<f32>) -> [u32; 3] {
+    [(v.x + 0.0_f32).to_bits(), (v.y + 0.0_f32).to_bits(), (v.z + 0.0_f32).to_bits()]
+}
+
+fn normalize_or_zero(v: Vector3<f32>) -> Vector3<f32> {
+    if v.length() > 0.0_f32 { v.normalize() } else { v }
+}
+
+// angle of the triangle at the vertex a
+fn corner_angle(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> f32 {
+    let ab = b.sub(&a);
+    let ac = c.sub(&a);
+    let len = ab.length() * ac.length();
+    if len > 0.0_f32 {
+        (ab.dot(ac) / len).max(-1.0_f32).min(1.0_f32).acos()
+    } else {
+        0.0_f32
+    }
+}
+
+// the part of the tangent orthogonal to the normal, any orthogonal vector if the tangent is zero
+fn orthogonal_tangent(tangent: Vector3<f32>, normal: Vector3<f32>) -> Vector3<f32> {
+    let t = normalize_or_zero(tangent.sub(&normal.mul(normal.dot(tangent))));
+    if t.length() > 0.0_f32 {
+        return t;
+    }
+    let axis = if normal.x.abs() < 0.9_f32 {
+        Vector3::new(1.0_f32, 0.0_f32, 0.0_f32)
+    } else {
+        Vector3::new(0.0_f32, 1.0_f32, 0.0_f32)
+    };
+    let t = normalize_or_zero(axis.sub(&normal.mul(normal.dot(axis))));
+
+    if t.length() > 0.0_f32 { t } else { axis }
+}
+
 impl Vertex {
     pub fn new(position: &Vector3<f32>, tex: &Vector2<f32>, normal: &Vector3<f32>) -> Vertex {
         Vertex {
             position: position.clone(),
             normal: normal.clone(),
             tex: tex.clone(),
+            tangent: Vector4::zero(),
+            color: Vector3::from_value(255.0_f32),
         }
     }
+
+    // goes along v of texture coordinates, see Model::calc_tangents
+    pub fn bitangent(&self) -> Vector3<f32> {
+        self.normal.cross(self.tangent.truncate()).mul(self.tangent.w)
+    }
 }
 
 impl Mesh {
@@ -46,85 +98,96 @@ impl Mesh {
         }
     }
 
+    fn draw_triangle(data_len: usize,
+                     state: usize,
+                     material: &Material,
+                     clip_vertices: [&ClipVertex; 3],
+                     framebuffer: &FrameBuffer,
+                     tiler: &mut Tiler) {
+        let mut points_2d: [Vector4<f32>; 3] = [Vector4::<f32>::new(0.0, 0.0, 0.0, 0.0); 3];
+        let mut vertex_out = [[0.0_f32;MAX_OUT_VALUES];3];
+        for i in 0..3 {
+            points_2d[i] = framebuffer.to_screen(clip_vertices[i].position);
+            for ind in 0..data_len {
+                vertex_out[i][ind] = clip_vertices[i].data[ind] * points_2d[i].w;
+            }
+        }
+
+        let col0 = Vector3::new(points_2d[0].x, points_2d[1].x, points_2d[2].x);
+        let col1 = Vector3::new(points_2d[0].y, points_2d[1].y, points_2d[2].y);
+        let col2 = Vector3::new(1.0_f32,     1.0_f32,     1.0_f32    );
+        let front_facing = match material.render_state.face(Matrix3::from_cols(col0, col1, col2).determinant()) {
+            Some(v) => v,
+            None => return,
+        };
+
+        // calc mip level:
+        let lod = match material.base_texture() {
+            Some(texture) => {
+                let ba_pixel = Vector2::new(points_2d[1].x, points_2d[1].y)
+                    .sub(Vector2::new(points_2d[0].x, points_2d[0].y));
+                let ca_pixel = Vector2::new(points_2d[2].x, points_2d[2].y)
+                    .sub(Vector2::new(points_2d[0].x, points_2d[0].y));
+
+                let tex_size = texture.size;
+                let ba_texel = clip_vertices[1].tex.sub(clip_vertices[0].tex).mul(&tex_size);
+                let ca_texel = clip_vertices[2].tex.sub(clip_vertices[0].tex).mul(&tex_size);
+                // cross product in 2d = 2 * square of triangle
+                let sq_pixel = ba_pixel.x * ca_pixel.y - ba_pixel.y * ca_pixel.x;
+                let sq_texel = ba_texel.x * ca_texel.y - ba_texel.y * ca_texel.x;
+                (sq_texel / sq_pixel).abs().sqrt().max(1.0_f32).log2() as usize
+            },
+            None => 0,
+        };
+
+        tiler.add_triangle(ScreenTriangle {
+            points: points_2d,
+            vertex_data: vertex_out,
+            lod: lod,
+            state: state,
+            front_facing: front_facing,
+        });
+    }
+
     fn draw(&self, shader: &mut Shader,
             material: &Material,
             vertex_buffer: &Vec<Vertex>,
-            device: &mut Device) -> u32 {
+            framebuffer: &FrameBuffer,
+            tiler: &mut Tiler,
+            clip_mode: ClipMode) -> u32 {
         shader.set_material(material);
-        let vertex_func = match material.texture {
-            None => shader.vertex_func[0],
-            Some(_) => shader.vertex_func[1],
-        };
-        let is_cubemap = match material.texture_cube {
-            None => false,
-            Some(_) => true,
-        };
-        shader.texture_cube = match material.texture_cube {
-            None => None,
-            Some(ref t) => Some(t.clone()),
-        };
         let cnt_triangle = self.index_buffer.len() / 3;
+        let mut polygon = Vec::<ClipVertex>::with_capacity(MAX_CLIP_VERTICES);
+        let mut polygon_tmp = Vec::<ClipVertex>::with_capacity(MAX_CLIP_VERTICES);
+        let mut state: Option<usize> = None;
         for indexes in self.index_buffer.chunks(3) {
-            let mut points_2d: [Point3<f32>; 3] = [Point3::<f32>::new(0.0, 0.0, 0.0); 3];
-            let mut tex_coord: [Vector2<f32>; 3] = [Vector2::<f32>::new(0.0, 0.0); 3];
-            let mut vertex_out = [[0.0_f32;MAX_OUT_VALUES];3];
+            let mut clip_vertices = [ClipVertex::new(); 3];
             for i in 0..3 {
-                let p = vertex_buffer[indexes[i] as usize];
-                let v = p.position;
-                let n = p.normal;
-                let t = p.tex;
-
-                shader.reset(Vector4::<f32>::new(v.x, v.y, v.z, 1.0_f32), Vector4::<f32>::new(n.x, n.y, n.z, 0.0_f32), t);
-                let p_screen = vertex_func(shader);
-                if is_cubemap {
-                    shader.vertex_out2_base = shader.vertex_out_len;
-                    shader.vertex_cubemap();
-                }
-                let inverse_w = 1.0_f32 / p_screen.w;
-
+                let p = &vertex_buffer[indexes[i] as usize];
+                clip_vertices[i].position = shader.vertex(p);
                 for ind in 0..shader.vertex_out_len {
-                    vertex_out[i][ind] = shader.out_vertex_data[ind] * inverse_w;
+                    clip_vertices[i].data[ind] = shader.out_vertex_data[ind];
                 }
-
-                tex_coord[i] = t;
-                points_2d[i] = Point3::new(
-                    (p_screen.x * inverse_w + 1.0_f32) * device.x_size as f32 * 0.5_f32,
-                    (p_screen.y * inverse_w + 1.0_f32) * device.y_size as f32 * 0.5_f32,
-                    inverse_w);
+                clip_vertices[i].tex = p.tex;
             }
 
-            let col0 = Vector3::new(points_2d[0].x, points_2d[1].x, points_2d[2].x);
-            let col1 = Vector3::new(points_2d[0].y, points_2d[1].y, points_2d[2].y);
-            let col2 = Vector3::new(1.0_f32,     1.0_f32,     1.0_f32    );
-            if Matrix3::from_cols(col0, col1, col2).determinant() < 0.0_f32 {
-                continue;
-            }
-
-            // calc mip level:
-            shader.texture = match material.texture {
-                Some(ref texture) => {
-                    let ba_pixel = Vector2::new(points_2d[1].x, points_2d[1].y)
-                        .sub(Vector2::new(points_2d[0].x, points_2d[0].y));
-                    let ca_pixel = Vector2::new(points_2d[2].x, points_2d[2].y)
-                        .sub(Vector2::new(points_2d[0].x, points_2d[0].y));
-
-                    let tex_size = texture.size;
-                    let ba_texel = tex_coord[1].sub(tex_coord[0]).mul(&tex_size);
-                    let ca_texel = tex_coord[2].sub(tex_coord[0]).mul(&tex_size);
-                    // cross product in 2d = 2 * square of triangle
-                    let sq_pixel = ba_pixel.x * ca_pixel.y - ba_pixel.y * ca_pixel.x;
-                    let sq_texel = ba_texel.x * ca_texel.y - ba_texel.y * ca_texel.x;
-                    let lod = (sq_texel / sq_pixel).abs().sqrt().max(1.0_f32).log2() as usize;
-                    Some(texture.get_surface(lod))
+            // shader state is known only after the first vertex
+            let state_ind = match state {
+                Some(ind) => ind,
+                None => {
+                    let ind = tiler.add_state(shader);
+                    state = Some(ind);
+                    ind
                 },
-                None => None,
             };
 
-            triangle(&mut device.cbuffer,
-                     &mut device.zbuffer,
-                     device.x_size,
-                     device.y_size,
-                     points_2d, vertex_out, shader);
+            let data_len = shader.vertex_out_len;
+            clip_triangle(&clip_vertices, data_len, clip_mode, &mut polygon, &mut polygon_tmp);
+            for ind in 2..polygon.len() {
+                Mesh::draw_triangle(data_len, state_ind, material,
+                                    [&polygon[0], &polygon[ind - 1], &polygon[ind]],
+                                    framebuffer, tiler);
+            }
         }
 
         cnt_triangle as u32
@@ -155,7 +218,7 @@ impl Model {
     }
 
     pub fn add_texture_cube(&mut self, dir_path: &std::path::Path, image_extension: &str) -> Result<(), String> {
-        let texture = std::rc::Rc::new(try!(TextureCube::new(dir_path, image_extension)));
+        let texture = std::sync::Arc::new(try!(TextureCube::new(dir_path, image_extension)));
         for material in &mut self.material_list {
             material.add_texture_cube(texture.clone());
         }
@@ -163,14 +226,232 @@ impl Model {
         Ok(())
     }
 
-    pub fn draw(&self, shader: &mut Shader, device: &mut Device) -> u32 {
+    // min and max of positions in the vertex buffer
+    pub fn bounds(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let mut min = self.vertex_buffer.first().map(|v| v.position).unwrap_or(Vector3::zero());
+        let mut max = min;
+        for v in &self.vertex_buffer {
+            min.x = min.x.min(v.position.x);
+            max.x = max.x.max(v.position.x);
+            min.y = min.y.min(v.position.y);
+            max.y = max.y.max(v.position.y);
+            min.z = min.z.min(v.position.z);
+            max.z = max.z.max(v.position.z);
+        }
+
+        (min, max)
+    }
+
+    // bounds for to_center_matrix from the vertex buffer
+    pub fn calc_bounds(&mut self) {
+        let (min, max) = self.bounds();
+        self.min = min;
+        self.max = max;
+        self.normalize = true;
+    }
+
+    // every index of the index buffers gets the vertex from corners (in the order of meshes and indexes),
+    // corners with equal keys share one vertex, vertices without corners are removed
+    fn rebuild_vertices<K: Hash + Eq>(&mut self, corners: Vec<(K, Vertex)>) {
+        let mut map = HashMap::<K, u32>::with_capacity(corners.len());
+        let mut vertex_buffer = Vec::<Vertex>::with_capacity(self.vertex_buffer.len());
+        let mut corners = corners.into_iter();
+        for mesh in &mut self.mesh_list {
+            for ind in &mut mesh.index_buffer {
+                let (key, vertex) = corners.next().unwrap();
+                *ind = *map.entry(key).or_insert_with(|| {
+                    vertex_buffer.push(vertex);
+                    vertex_buffer.len() as u32 - 1
+                });
+            }
+        }
+        self.vertex_buffer = vertex_buffer;
+    }
+
+    fn triangle_positions(&self, indexes: &[u32]) -> [Vector3<f32>; 3] {
+        [self.vertex_buffer[indexes[0] as usize].position,
+         self.vertex_buffer[indexes[1] as usize].position,
+         self.vertex_buffer[indexes[2] as usize].position]
+    }
+
+    // Normal of the vertex is the sum of normals of its triangles weighted by angles at the vertex.
+    // Triangles with the same position of the vertex are smoothed, if the angle between them is not bigger
+    // than crease_angle, so vertices on sharp edges are split, 180 degrees - everything is smooth.
+    pub fn calc_smooth_normals<A: Into<Rad<f32>>>(&mut self, crease_angle: A) {
+        let cos_crease = crease_angle.into().cos() - 0.000001_f32;
+        // normal of the triangle and angle for every corner
+        let mut corners = Vec::<(Vector3<f32>, f32)>::new();
+        let mut groups = HashMap::<[u32; 3], Vec<usize>>::new();
+        for mesh in &self.mesh_list {
+            for indexes in mesh.index_buffer.chunks(3) {
+                let p = self.triangle_positions(indexes);
+                let normal = normalize_or_zero(p[1].sub(&p[0]).cross(p[2].sub(&p[0])));
+                for k in 0..3 {
+                    groups.entry(vector_key(p[k])).or_insert_with(Vec::new).push(corners.len());
+                    corners.push((normal, corner_angle(p[k], p[(k + 1) % 3], p[(k + 2) % 3])));
+                }
+            }
+        }
+
+        let mut normals = vec![Vector3::<f32>::zero(); corners.len()];
+        for group in groups.values() {
+            for &c in group {
+                let normal = corners[c].0;
+                let mut sum = Vector3::<f32>::zero();
+                for &d in group {
+                    let (n, angle) = corners[d];
+                    // degenerate triangles take the normal of neighbours
+                    if normal.dot(n) >= cos_crease || normal == Vector3::zero() {
+                        sum = sum.add(&n.mul(angle));
+                    }
+                }
+                normals[c] = if sum.length() > 0.0_f32 { sum.normalize() } else { normal };
+            }
+        }
+
+        let mut vertices = Vec::<((u32, [u32; 3]), Vertex)>::with_capacity(normals.len());
+        for mesh in &self.mesh_list {
+            for ind in &mesh.index_buffer {
+                let mut v = self.vertex_buffer[*ind as usize];
+                v.normal = normals[vertices.len()];
+                vertices.push(((*ind, vector_key(v.normal)), v));
+            }
+        }
+        self.rebuild_vertices(vertices);
+    }
+
+    // every triangle gets own vertices with the normal of the triangle
+    pub fn calc_flat_normals(&mut self) {
+        let is_flat = vec![true; self.mesh_list.len()];
+        self.calc_flat_normals_of(&is_flat);
+    }
+
+    // flat normals for meshes with is_flat[ind], vertices of other meshes are not changed
+    pub fn calc_flat_normals_of(&mut self, is_flat: &[bool]) {
+        // (index of the corner or the vertex, is flat)
+        let mut vertices = Vec::<((usize, bool), Vertex)>::new();
+        for (mesh_ind, mesh) in self.mesh_list.iter().enumerate() {
+            for indexes in mesh.index_buffer.chunks(3) {
+                if !is_flat.get(mesh_ind).cloned().unwrap_or(false) {
+                    for ind in indexes {
+                        vertices.push(((*ind as usize, false), self.vertex_buffer[*ind as usize]));
+                    }
+                    continue;
+                }
+                let p = self.triangle_positions(indexes);
+                let normal = normalize_or_zero(p[1].sub(&p[0]).cross(p[2].sub(&p[0])));
+                for ind in indexes {
+                    let mut v = self.vertex_buffer[*ind as usize];
+                    v.normal = normal;
+                    vertices.push(((vertices.len(), true), v));
+                }
+            }
+        }
+        self.rebuild_vertices(vertices);
+    }
+
+    // Tangents by texture coordinates per triangle (Lengyel's method): the tangent goes along u and is orthogonal
+    // to the normal, the bitangent goes along v, see Vertex::bitangent. Tangents of triangles are weighted
+    // by angles at the vertex. Vertices shared by triangles with mirrored texture coordinates are split.
+    // Normals must be calculated before.
+    pub fn calc_tangents(&mut self) {
+        let is_calculated = vec![true; self.mesh_list.len()];
+        self.calc_tangents_of(&is_calculated);
+    }
+
+    // tangents for meshes with is_calculated[ind], vertices of other meshes keep their tangents
+    pub fn calc_tangents_of(&mut self, is_calculated: &[bool]) {
+        // vertex, is mirrored, is calculated
+        let mut corners = Vec::<(u32, bool, bool)>::new();
+        let mut sums = HashMap::<(u32, bool), Vector3<f32>>::new();
+        for (mesh_ind, mesh) in self.mesh_list.iter().enumerate() {
+            for indexes in mesh.index_buffer.chunks(3) {
+                if !is_calculated.get(mesh_ind).cloned().unwrap_or(false) {
+                    for ind in indexes {
+                        corners.push((*ind, false, false));
+                    }
+                    continue;
+                }
+                let v = [&self.vertex_buffer[indexes[0] as usize],
+                         &self.vertex_buffer[indexes[1] as usize],
+                         &self.vertex_buffer[indexes[2] as usize]];
+                let e1 = v[1].position.sub(&v[0].position);
+                let e2 = v[2].position.sub(&v[0].position);
+                let duv1 = v[1].tex.sub(&v[0].tex);
+                let duv2 = v[2].tex.sub(&v[0].tex);
+                let r = duv1.x * duv2.y - duv2.x * duv1.y;
+                // degenerate texture coordinates give nothing
+                let (tangent, bitangent) = if r != 0.0_f32 && r.is_finite() {
+                    (e1.mul(duv2.y).sub(&e2.mul(duv1.y)).mul(1.0_f32 / r),
+                     e2.mul(duv1.x).sub(&e1.mul(duv2.x)).mul(1.0_f32 / r))
+                } else {
+                    (Vector3::zero(), Vector3::zero())
+                };
+
+                for k in 0..3 {
+                    let n = v[k].normal;
+                    let t = normalize_or_zero(tangent.sub(&n.mul(n.dot(tangent))));
+                    let mirrored = n.cross(t).dot(bitangent) < 0.0_f32;
+                    let angle = corner_angle(v[k].position, v[(k + 1) % 3].position, v[(k + 2) % 3].position);
+                    let sum = sums.entry((indexes[k], mirrored)).or_insert(Vector3::zero());
+                    *sum = sum.add(&t.mul(angle));
+                    corners.push((indexes[k], mirrored, true));
+                }
+            }
+        }
+
+        for (&(ind, _), sum) in &mut sums {
+            *sum = orthogonal_tangent(*sum, self.vertex_buffer[ind as usize].normal);
+        }
+        let mut vertices = Vec::<((u32, bool, bool), Vertex)>::with_capacity(corners.len());
+        for key in corners {
+            let mut v = self.vertex_buffer[key.0 as usize];
+            if key.2 {
+                v.tangent = sums[&(key.0, key.1)].extend(if key.1 { -1.0_f32 } else { 1.0_f32 });
+            }
+            vertices.push((key, v));
+        }
+        self.rebuild_vertices(vertices);
+    }
+
+    // opaque meshes are drawn at once, transparent ones (see Material::blend)
+    // wait in the tiler for Tiler::flush_transparent
+    pub fn draw(&self, shader: &mut Shader, framebuffer: &mut FrameBuffer, tiler: &mut Tiler, clip_mode: ClipMode) -> u32 {
+        self.draw_meshes(shader, framebuffer, tiler, clip_mode, true)
+    }
+
+    // only opaque meshes, e.g. for shadow maps
+    pub fn draw_opaque(&self, shader: &mut Shader, framebuffer: &mut FrameBuffer, tiler: &mut Tiler, clip_mode: ClipMode) -> u32 {
+        self.draw_meshes(shader, framebuffer, tiler, clip_mode, false)
+    }
+
+    fn draw_meshes(&self,
+                   shader: &mut Shader,
+                   framebuffer: &mut FrameBuffer,
+                   tiler: &mut Tiler,
+                   clip_mode: ClipMode,
+                   with_transparent: bool) -> u32 {
         let mut triangle_cnt: u32 = 0;
         for mesh in &self.mesh_list {
+            let material = &self.material_list[mesh.material_id];
+            let is_transparent = material.is_transparent();
+            if is_transparent {
+                if !with_transparent {
+                    continue;
+                }
+                tiler.begin_transparent();
+            }
             triangle_cnt += mesh.draw(shader,
-                                      &self.material_list[mesh.material_id],
+                                      material,
                                       &self.vertex_buffer,
-                                      device);
+                                      framebuffer,
+                                      tiler,
+                                      clip_mode);
+            if is_transparent {
+                tiler.end_transparent();
+            }
         }
+        tiler.flush(framebuffer);
 
         triangle_cnt
     }
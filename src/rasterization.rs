@@ -1,15 +1,14 @@
 use std::mem;
 use std::cmp;
-use std::boxed::Box;
 use cgmath::*;
 use shader::{Shader, MAX_OUT_VALUES};
-use memory::vector3_to_u32;
+use framebuffer::Tile;
 
 const EPSILON: f32 = 0.0001_f32;
 
 #[inline]
-fn sort_by_y<'a>(points: &'a[Point3<f32>; 3], vertex_data: &'a[[f32;MAX_OUT_VALUES]; 3]) ->
-    (&'a Point3<f32>, &'a Point3<f32>, &'a Point3<f32>, &'a [f32;MAX_OUT_VALUES], &'a [f32;MAX_OUT_VALUES], &'a [f32;MAX_OUT_VALUES]) {
+fn sort_by_y<'a>(points: &'a[Vector4<f32>; 3], vertex_data: &'a[[f32;MAX_OUT_VALUES]; 3]) ->
+    (&'a Vector4<f32>, &'a Vector4<f32>, &'a Vector4<f32>, &'a [f32;MAX_OUT_VALUES], &'a [f32;MAX_OUT_VALUES], &'a [f32;MAX_OUT_VALUES]) {
         let mut a = &points[0];
         let mut b = &points[1];
         let mut c = &points[2];
@@ -35,17 +34,16 @@ fn sort_by_y<'a>(points: &'a[Point3<f32>; 3], vertex_data: &'a[[f32;MAX_OUT_VALU
     }
 
 #[inline]
-fn half_triangle(cbuffer: &mut Vec<u32>,
-                 zbuffer: &mut Vec<f32>,
-                 x_size: usize,
+fn half_triangle(tile: &mut Tile,
                  shader: &mut Shader,
-                 point_base: &Point3<f32>,
+                 point_base: &Vector4<f32>,
                  vd_base: &[f32;MAX_OUT_VALUES],
                  y_begin: usize,
                  y_end: usize,
-                 step0: &[f32; MAX_OUT_VALUES + 2],
-                 step1: &[f32; MAX_OUT_VALUES + 2]
+                 step0: &[f32; MAX_OUT_VALUES + 3],
+                 step1: &[f32; MAX_OUT_VALUES + 3]
                  ) {
+    let x_size = tile.x_size;
     let mut vdata0_step = [0.0_f32; MAX_OUT_VALUES];
     let mut vdata0      = [0.0_f32; MAX_OUT_VALUES];
     let mut dvdata_step = [0.0_f32; MAX_OUT_VALUES];
@@ -53,48 +51,46 @@ fn half_triangle(cbuffer: &mut Vec<u32>,
     let mut vdata_step  = [0.0_f32; MAX_OUT_VALUES];
     let mut vdata       = [0.0_f32; MAX_OUT_VALUES];
 
-    let y_step = y_begin as f32 + 0.5_f32 - point_base.y;
     let x0_step = step0[0];
     let x1_step = step1[0];
     let z0_step = step0[1];
     let z1_step = step1[1];
-
-    let mut x1 = point_base.x + y_step * x0_step + 0.5_f32 - EPSILON;
-    let mut z1 = point_base.z + y_step * z0_step; // inverse z
-    let mut x2 = point_base.x + y_step * x1_step + 0.5_f32 - EPSILON;
+    let d0_step = step0[2];
+    let d1_step = step1[2];
 
     let dx_step = x0_step - x1_step;
     let dz_step = z0_step - z1_step;
-
-    let mut dx = y_step * dx_step;
-    let mut dz = y_step * dz_step;
+    let dd_step = d0_step - d1_step;
 
     for ind in 0..shader.vertex_out_len {
-        vdata0_step[ind] = step0[ind + 2];
-        let vdata1_step = step1[ind + 2];
-
-        vdata0[ind] = vd_base[ind] + y_step * vdata0_step[ind];
-        dvdata_step[ind] = vdata0_step[ind] - vdata1_step;
-        dvdata[ind] = y_step * dvdata_step[ind];
+        vdata0_step[ind] = step0[ind + 3];
+        dvdata_step[ind] = vdata0_step[ind] - step1[ind + 3];
     }
 
-    let pixel_func_base = match shader.texture {
-        None => shader.pixel_func[0],
-        Some(_) => shader.pixel_func[1],
-    };
-
-    let pixel_func: Box<Fn(&Shader) -> Vector3<f32>> = match shader.texture_cube {
-        None => Box::new(pixel_func_base),
-        Some(_) => Box::new(|shader| shader.pixel_cubemap(pixel_func_base(shader))),
-    };
+    // values of every row are computed from the base point, not stepped from the previous row,
+    // so rows before the tile are skipped and the result does not depend on tiles
+    for y in cmp::max(y_begin, tile.y_begin)..cmp::min(y_end, tile.y_end) {
+        let y_step = y as f32 + 0.5_f32 - point_base.y;
+        let x1 = point_base.x + y_step * x0_step + 0.5_f32 - EPSILON;
+        let z1 = point_base.w + y_step * z0_step; // inverse w
+        let d1 = point_base.z + y_step * d0_step; // depth
+        let x2 = point_base.x + y_step * x1_step + 0.5_f32 - EPSILON;
+        let dx = y_step * dx_step;
+        let dz = y_step * dz_step;
+        let dd = y_step * dd_step;
+        for ind in 0..shader.vertex_out_len {
+            vdata0[ind] = vd_base[ind] + y_step * vdata0_step[ind];
+            dvdata[ind] = y_step * dvdata_step[ind];
+        }
 
-    let mut offset = y_begin * x_size;
-    for _ in y_begin..y_end {
+        let offset = (y - tile.y_begin) * x_size;
         let x1_int = cmp::min(cmp::max(x1 as i32, 0) as usize, x_size - 1);
         let x2_int = cmp::min(cmp::max(x2 as i32, 0) as usize, x_size);
         if x2_int > x1_int {
             let z_step = dz / dx;
-            let mut z = z1 + z_step * (x1_int as f32 - x1 - EPSILON); // inverse z
+            let mut z = z1 + z_step * (x1_int as f32 - x1 - EPSILON); // inverse w
+            let d_step = dd / dx;
+            let mut d = d1 + d_step * (x1_int as f32 - x1 - EPSILON); // depth
             for ind in 0..shader.vertex_out_len {
                 vdata_step[ind] = dvdata[ind] / dx;
                 vdata[ind] = vdata0[ind] + vdata_step[ind] * (x1_int as f32 - x1 - EPSILON);
@@ -102,39 +98,31 @@ fn half_triangle(cbuffer: &mut Vec<u32>,
 
             for x in x1_int..x2_int {
                 z += z_step;
+                d += d_step;
                 for ind in 0..shader.vertex_out_len {
                     vdata[ind] += vdata_step[ind];
                 }
-                if zbuffer[offset + x] < z {
+                if shader.depth_test(tile.zbuffer[offset + x], d) {
                     for ind in 0..shader.vertex_out_len {
                         shader.in_pixel_data[ind] = vdata[ind] / z;
                     }
-                    cbuffer[offset + x] = vector3_to_u32(&pixel_func(shader));
-                    zbuffer[offset + x] = z;
+                    shader.write_pixel(&mut tile.cbuffer[offset + x], &mut tile.zbuffer[offset + x], d);
                 }
             }
         }
-        offset += x_size;
-        x1 += x0_step;
-        x2 += x1_step;
-        dx += dx_step;
-        z1 += z0_step;
-        dz += dz_step;
-        for ind in 0..shader.vertex_out_len {
-            vdata0[ind] += vdata0_step[ind];
-            dvdata[ind] += dvdata_step[ind];
-        }
     }
 }
 
+// points: x, y - in pixels, z - depth (bigger is nearer), w - inverse w from clip space,
+// the tile is without multisampling, see halfspace::triangle
 #[inline]
-pub fn triangle(cbuffer: &mut Vec<u32>,
-                zbuffer: &mut Vec<f32>,
-                x_size: usize,
-                y_size: usize,
-                points: [Point3<f32>; 3],
+pub fn triangle(tile: &mut Tile,
+                points: [Vector4<f32>; 3],
                 vertex_data: [[f32;MAX_OUT_VALUES]; 3],
                 shader: &mut Shader) {
+    let x_size = tile.x_size;
+    let y_size = tile.y_size;
+
     // a.y > b.y > c.y
     let (a, b, c, va, vb, vc) = sort_by_y(&points, &vertex_data);
 
@@ -150,20 +138,28 @@ pub fn triangle(cbuffer: &mut Vec<u32>,
             return;
         }
 
+    // y-ranges: [y0; y1) + [y1; y2)
+    let y0 = cmp::min(cmp::max((c.y + 0.5_f32) as i32, 0) as usize, y_size - 1);
+    let y1 = cmp::min(cmp::max((b.y + 0.5_f32) as i32, 0) as usize, y_size);
+    let y2 = cmp::min(cmp::max((a.y + 0.5_f32) as i32, 0) as usize, y_size);
+    if y2 <= tile.y_begin || y0 >= tile.y_end {
+        return;
+    }
+
     // steps for line
-    let mut step_ab = [0.0_f32; MAX_OUT_VALUES + 2];
+    let mut step_ab = [0.0_f32; MAX_OUT_VALUES + 3];
     let inv_dy_ab = if a.y - b.y > EPSILON {
         1.0_f32 / (a.y - b.y)
     } else {
         1.0_f32 / EPSILON
     };
-    let mut step_ac = [0.0_f32; MAX_OUT_VALUES + 2];
+    let mut step_ac = [0.0_f32; MAX_OUT_VALUES + 3];
     let inv_dy_ac = if a.y - c.y > EPSILON {
         1.0_f32 / (a.y - c.y)
     } else {
         1.0_f32 / EPSILON
     };
-    let mut step_bc = [0.0_f32; MAX_OUT_VALUES + 2];
+    let mut step_bc = [0.0_f32; MAX_OUT_VALUES + 3];
     let inv_dy_bc = if b.y - c.y > EPSILON {
         1.0_f32 / (b.y - c.y)
     } else {
@@ -171,25 +167,23 @@ pub fn triangle(cbuffer: &mut Vec<u32>,
     };
 
     step_ab[0] = (a.x - b.x) * inv_dy_ab;
-    step_ab[1] = (a.z - b.z) * inv_dy_ab;
+    step_ab[1] = (a.w - b.w) * inv_dy_ab;
+    step_ab[2] = (a.z - b.z) * inv_dy_ab;
 
     step_ac[0] = (a.x - c.x) * inv_dy_ac;
-    step_ac[1] = (a.z - c.z) * inv_dy_ac;
+    step_ac[1] = (a.w - c.w) * inv_dy_ac;
+    step_ac[2] = (a.z - c.z) * inv_dy_ac;
 
     step_bc[0] = (b.x - c.x) * inv_dy_bc;
-    step_bc[1] = (b.z - c.z) * inv_dy_bc;
+    step_bc[1] = (b.w - c.w) * inv_dy_bc;
+    step_bc[2] = (b.z - c.z) * inv_dy_bc;
 
     for i in 0..shader.vertex_out_len {
-        step_ab[i + 2] = (va[i] - vb[i]) * inv_dy_ab;
-        step_ac[i + 2] = (va[i] - vc[i]) * inv_dy_ac;
-        step_bc[i + 2] = (vb[i] - vc[i]) * inv_dy_bc;
+        step_ab[i + 3] = (va[i] - vb[i]) * inv_dy_ab;
+        step_ac[i + 3] = (va[i] - vc[i]) * inv_dy_ac;
+        step_bc[i + 3] = (vb[i] - vc[i]) * inv_dy_bc;
     }
 
-    // y-ranges: [y0; y1) + [y1; y2)
-    let y0 = cmp::min(cmp::max((c.y + 0.5_f32) as i32, 0) as usize, y_size - 1);
-    let y1 = cmp::min(cmp::max((b.y + 0.5_f32) as i32, 0) as usize, y_size);
-    let y2 = cmp::min(cmp::max((a.y + 0.5_f32) as i32, 0) as usize, y_size);
-
     let step0 = if step_bc[0] > step_ac[0] {
         (&step_ac, &step_bc)
     } else {
@@ -202,9 +196,7 @@ pub fn triangle(cbuffer: &mut Vec<u32>,
     };
 
     if y0 < y1 {
-        half_triangle(cbuffer,
-                      zbuffer,
-                      x_size,
+        half_triangle(tile,
                       shader,
                       c, vc, y0, y1,
                       step0.0,
@@ -212,9 +204,7 @@ pub fn triangle(cbuffer: &mut Vec<u32>,
                       );
     }
     if y1 < y2 {
-        half_triangle(cbuffer,
-                      zbuffer,
-                      x_size,
+        half_triangle(tile,
                       shader,
                       a, va, y1, y2,
                       step1.0,
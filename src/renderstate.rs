@@ -0,0 +1,88 @@
+// which side of triangles is not drawn
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CullMode {
+    Back,
+    Front,
+    None, // double-sided, back faces are lit with the flipped normal
+}
+
+// order of vertices of the front face on the screen
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Winding {
+    CounterClockwise,
+    Clockwise,
+}
+
+// depth test, the pixel is drawn if it is <func> than the depth in the framebuffer by distance to the camera
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DepthFunc {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    Always,
+}
+
+// all channels of the framebuffer color, see RenderState::color_mask
+pub const COLOR_MASK_ALL: u32 = 0xFFFFFF;
+
+// fixed function state of the pipeline for meshes with one material
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RenderState {
+    pub cull: CullMode,
+    pub front_face: Winding,
+    pub depth_func: DepthFunc,
+    pub depth_write: bool, // false - the mesh does not hide meshes behind it
+    pub color_mask: u32,   // bits of the framebuffer color which are written, 0 - only depth
+}
+
+impl DepthFunc {
+    // depth: near plane - 1, far plane - 0, so the nearer pixel has the bigger value
+    #[inline]
+    pub fn test(&self, depth: f32, buffer: f32) -> bool {
+        match *self {
+            DepthFunc::Less => depth > buffer,
+            DepthFunc::LessEqual => depth >= buffer,
+            DepthFunc::Greater => depth < buffer,
+            DepthFunc::GreaterEqual => depth <= buffer,
+            DepthFunc::Equal => depth == buffer,
+            DepthFunc::Always => true,
+        }
+    }
+}
+
+impl RenderState {
+    pub fn new() -> RenderState {
+        RenderState {
+            cull: CullMode::Back,
+            front_face: Winding::CounterClockwise,
+            depth_func: DepthFunc::Less,
+            depth_write: true,
+            color_mask: COLOR_MASK_ALL,
+        }
+    }
+
+    // no culling, e.g. for leaves and other models without thickness
+    pub fn double_sided() -> RenderState {
+        RenderState { cull: CullMode::None, ..RenderState::new() }
+    }
+
+    // area: signed doubled area of the triangle on the screen, positive for counter-clockwise,
+    // returns None if the triangle is culled, otherwise true for the front face
+    #[inline]
+    pub fn face(&self, area: f32) -> Option<bool> {
+        let is_front = (area >= 0.0_f32) == (self.front_face == Winding::CounterClockwise);
+        match self.cull {
+            CullMode::Back if !is_front => None,
+            CullMode::Front if is_front => None,
+            _ => Some(is_front),
+        }
+    }
+
+    // new color in the framebuffer with channels out of color_mask unchanged
+    #[inline]
+    pub fn mask_color(&self, color: u32, dst: u32) -> u32 {
+        (color & self.color_mask) | (dst & !self.color_mask)
+    }
+}
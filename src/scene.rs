@@ -1,47 +1,142 @@
 use cgmath::*;
-use std::ops::Mul;
+use std::ops::{Add, Sub, Mul};
+use std::path::Path;
+use std::sync::Arc;
 use mesh::Model;
-use device::Device;
+use device::{Device, InputEvent, Keycode};
+use framebuffer::{FrameBuffer, Antialiasing};
+use clipping::ClipMode;
+use light::Light;
+use camera::{Camera, Projection};
+use tiler::{Tiler, RasterizerType, PolygonMode};
+use line::LineRasterizer;
+use clipping::clip_line;
+use light::LightType;
 use shader::*;
 
 pub struct Scene {
-    device: Device,
-    mat_proj: Matrix4<f32>,
-    mat_view: Matrix4<f32>,
-    vec_light: Vector4<f32>,
-    vec_eye_pos: Vector4<f32>,
+    device: Option<Device>,
+    events: Vec<InputEvent>,
+    framebuffer: FrameBuffer, // of draw calls
+    frame: Option<FrameBuffer>, // with antialiasing framebuffer is resolved into it
+    tiler: Tiler,
+    camera: Camera,
+    lights: Vec<Light>,
+    shadow_shader: Shader,
     ambient_intensity: f32, // [0; 1]
+    clip_mode: ClipMode,
     cnt_triangle: u32,
 }
 
 impl Scene {
     pub fn new(width: u32, height: u32) -> Scene {
-        Scene {
-            device: Device::new("rust software render", width, height),
-            mat_proj: Matrix4::<f32>::zero(),
-            mat_view: Matrix4::<f32>::zero(),
-            vec_light: Vector4::<f32>::zero(),
-            vec_eye_pos: Vector4::<f32>::zero(),
+        Scene::with_device(Some(Device::new("rust software render", width, height)), width, height)
+    }
+
+    // render only into the framebuffer, without window and SDL video subsystem
+    pub fn headless(width: u32, height: u32) -> Scene {
+        Scene::with_device(None, width, height)
+    }
+
+    fn with_device(device: Option<Device>, width: u32, height: u32) -> Scene {
+        let mut scene = Scene {
+            device: device,
+            events: Vec::<InputEvent>::new(),
+            framebuffer: FrameBuffer::new(width, height),
+            frame: None,
+            tiler: Tiler::new(Tiler::default_threads()),
+            camera: Camera::perspective(deg(60.0_f32), 1.0_f32, 0.1_f32, 100.0_f32),
+            lights: Vec::<Light>::new(),
+            shadow_shader: Shader::new(ShaderType::Depth),
             ambient_intensity: 1.0_f32,
+            clip_mode: ClipMode::Near,
             cnt_triangle: 0,
-        }
+        };
+        scene.camera.aspect = scene.aspect();
+
+        scene
+    }
+
+    // the frame with the size of the window, with antialiasing it is updated by resolve
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        self.frame.as_ref().unwrap_or(&self.framebuffer)
+    }
+
+    pub fn camera(&mut self, camera: Camera) -> &mut Scene {
+        self.camera = camera;
+
+        self
+    }
+
+    pub fn get_camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
     }
 
+    // perspective projection with aspect of the framebuffer
     pub fn proj<A: Into<Rad<f32>>>(&mut self, fovy: A, near: f32, far: f32) -> &mut Scene {
-        let aspect = self.device.x_size as f32 / self.device.y_size as f32;
-        self.mat_proj = perspective(fovy, aspect, near, far);
+        self.camera.projection = Projection::Perspective(fovy.into());
+        self.camera.aspect = self.aspect();
+        self.camera.near = near;
+        self.camera.far = far;
+
+        self
+    }
+
+    // orthographic projection with aspect of the framebuffer, height - half of the visible height
+    pub fn ortho(&mut self, height: f32, near: f32, far: f32) -> &mut Scene {
+        self.camera.projection = Projection::Orthographic(height);
+        self.camera.aspect = self.aspect();
+        self.camera.near = near;
+        self.camera.far = far;
+
         self
     }
 
     pub fn view(&mut self, eye: Point3<f32>, center: Point3<f32>, up: Vector3<f32>) -> &mut Scene {
-        self.mat_view = Matrix4::<f32>::look_at(eye, center, up);
-        self.vec_eye_pos = Vector4::new(eye.x, eye.y, eye.z, 1.0_f32);
+        self.camera.look_at(eye, center, up);
 
         self
     }
 
+    // 1 for an empty framebuffer
+    fn aspect(&self) -> f32 {
+        let framebuffer = self.framebuffer();
+        if framebuffer.x_size == 0 || framebuffer.y_size == 0 {
+            return 1.0_f32;
+        }
+
+        framebuffer.x_size as f32 / framebuffer.y_size as f32
+    }
+
+    // ray from the camera through the pixel, y goes down as in InputEvent,
+    // returns start point and normalized direction in world space
+    pub fn ray(&self, x: i32, y: i32) -> (Point3<f32>, Vector3<f32>) {
+        let ndc_x = (x as f32 + 0.5_f32) * 2.0_f32 / self.framebuffer().x_size as f32 - 1.0_f32;
+        let ndc_y = 1.0_f32 - (y as f32 + 0.5_f32) * 2.0_f32 / self.framebuffer().y_size as f32;
+
+        self.camera.ray(ndc_x, ndc_y)
+    }
+
+    // replaces all lights with one white directional light, vec - direction to the light
     pub fn light(&mut self, vec: Vector3<f32>) -> &mut Scene {
-        self.vec_light = Vector4::new(vec.x, vec.y, vec.z, 0.0_f32).normalize();
+        self.lights.clear();
+        self.lights.push(Light::directional(vec.mul(-1.0_f32), Vector3::new(1.0_f32, 1.0_f32, 1.0_f32), 1.0_f32));
+
+        self
+    }
+
+    pub fn add_light(&mut self, light: Light) -> &mut Scene {
+        self.lights.push(light);
+
+        self
+    }
+
+    pub fn clear_lights(&mut self) -> &mut Scene {
+        self.lights.clear();
 
         self
     }
@@ -52,31 +147,253 @@ impl Scene {
         self
     }
 
+    pub fn clip_mode(&mut self, mode: ClipMode) -> &mut Scene {
+        self.clip_mode = mode;
+
+        self
+    }
+
+    // number of threads for rasterization, 1 - render in the calling thread
+    pub fn threads(&mut self, cnt: usize) -> &mut Scene {
+        self.tiler.set_threads(cnt);
+
+        self
+    }
+
+    // height of bands of rows rasterized by threads, the frame height - without splitting
+    pub fn tile_rows(&mut self, rows: usize) -> &mut Scene {
+        self.tiler.set_tile_rows(rows);
+
+        self
+    }
+
+    pub fn rasterizer(&mut self, rasterizer: RasterizerType) -> &mut Scene {
+        self.tiler.set_rasterizer(rasterizer);
+
+        self
+    }
+
+    // with Antialiasing::Msaa or Ssaa draw calls render into a separate framebuffer,
+    // which is averaged into the frame by resolve
+    pub fn antialiasing(&mut self, mode: Antialiasing) -> &mut Scene {
+        let frame = self.frame.take().unwrap_or_else(|| self.framebuffer.clone());
+        self.framebuffer = mode.render_target(frame.x_size as u32, frame.y_size as u32);
+        if mode != Antialiasing::None {
+            self.frame = Some(frame);
+        }
+
+        self
+    }
+
+    // fill, edges or vertices of triangles in the next draw calls
+    pub fn polygon_mode(&mut self, mode: PolygonMode) -> &mut Scene {
+        self.tiler.set_polygon_mode(mode);
+
+        self
+    }
+
+    pub fn line_rasterizer(&mut self, rasterizer: LineRasterizer) -> &mut Scene {
+        self.tiler.set_line_rasterizer(rasterizer);
+
+        self
+    }
+
+    // color of edges and vertices in polygon modes except PolygonMode::Fill
+    pub fn wire_color(&mut self, color: u32) -> &mut Scene {
+        self.tiler.set_wire_color(color);
+
+        self
+    }
+
+    pub fn point_size(&mut self, size: u32) -> &mut Scene {
+        self.tiler.set_point_size(size);
+
+        self
+    }
+
+    // input events of the window since the previous frame
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    // returns false, if the window is closed or Escape is pressed
     pub fn start(&mut self, color: u32) -> bool {
-        if self.device.keyboard() {
-            self.device.clear(color);
+        self.events.clear();
+        if let Some(ref mut device) = self.device {
+            device.poll_events(&mut self.events);
+        }
+        let is_continue = !self.events.iter().any(|event| match *event {
+            InputEvent::Quit | InputEvent::KeyDown(Keycode::Escape) => true,
+            _ => false,
+        });
+        if is_continue {
+            self.framebuffer.clear(color);
+            if let Some(ref mut frame) = self.frame {
+                frame.clear(color);
+            }
+            for light in &mut self.lights {
+                if let Some(ref mut shadow) = light.shadow {
+                    Arc::make_mut(shadow).framebuffer.clear(0);
+                }
+            }
+            self.tiler.clear_transparent();
             self.cnt_triangle = 0;
-            true
-        } else {
-            false
         }
+
+        is_continue
+    }
+
+    // depth pass into shadow maps of all lights, transparent meshes do not cast shadows,
+    // every shadow caster is drawn with draw_shadow after start and before draw
+    pub fn draw_shadow(&mut self, mesh: &Model, mat_world: Matrix4<f32>) -> &mut Scene {
+        // shadow maps are always filled
+        let polygon_mode = self.tiler.polygon_mode();
+        self.tiler.set_polygon_mode(PolygonMode::Fill);
+        for light in &mut self.lights {
+            if let Some(mut shadow) = light.shadow.take() {
+                {
+                    let map = Arc::make_mut(&mut shadow);
+                    map.update_matrix(light);
+                    self.shadow_shader.uniforms.mat_proj_view_world = map.mat_light.mul(&mat_world);
+                    mesh.draw_opaque(&mut self.shadow_shader, &mut map.framebuffer, &mut self.tiler, ClipMode::Frustum);
+                }
+                light.shadow = Some(shadow);
+            }
+        }
+        self.tiler.set_polygon_mode(polygon_mode);
+
+        self
     }
 
     pub fn draw(&mut self, mesh: &Model, mat_world: Matrix4<f32>, shader: &mut Shader) -> &mut Scene {
-        shader.set_matrix(MATRIX_PROJ_VIEW_WORLD, self.mat_proj.mul(&self.mat_view).mul(&mat_world));
-        shader.set_matrix(MATRIX_VIEW_WORLD, self.mat_view.mul(&mat_world));
-        shader.set_matrix(MATRIX_WORLD, mat_world);
-        shader.set_vec4(IN_VS_VEC_NEG_LIGHT, self.vec_light);
-        shader.set_vec4(IN_VS_VEC_EYE_POS, self.vec_eye_pos);
-        shader.ambient_intensity = self.ambient_intensity;
+        let mat_view = self.camera.view_matrix();
+        shader.uniforms.mat_proj_view_world = self.camera.proj_matrix().mul(&mat_view).mul(&mat_world);
+        shader.uniforms.mat_view_world = mat_view.mul(&mat_world);
+        shader.uniforms.mat_world = mat_world;
+        shader.uniforms.lights.clone_from(&self.lights);
+        shader.uniforms.eye_pos = self.camera.position.to_vec();
+        shader.uniforms.ambient_intensity = self.ambient_intensity;
+
+        self.cnt_triangle += mesh.draw(shader, &mut self.framebuffer, &mut self.tiler, self.clip_mode);
+        // release shadow maps, so the next frame clears them without copying
+        shader.uniforms.lights.clear();
+
+        self
+    }
+
+    // debug line in world space, it is tested with the depth of drawn meshes, but does not write it
+    fn add_line(&mut self, a: Vector3<f32>, b: Vector3<f32>, color: u32) {
+        let mat_proj_view = self.camera.proj_matrix().mul(&self.camera.view_matrix());
+        let a = mat_proj_view.mul(&a.extend(1.0_f32));
+        let b = mat_proj_view.mul(&b.extend(1.0_f32));
+        if let Some((a, b)) = clip_line(a, b) {
+            let points = [self.framebuffer.to_screen(a), self.framebuffer.to_screen(b)];
+            self.tiler.add_line(points, color);
+        }
+    }
+
+    fn add_point(&mut self, position: Vector3<f32>, color: u32) {
+        let mat_proj_view = self.camera.proj_matrix().mul(&self.camera.view_matrix());
+        let p = mat_proj_view.mul(&position.extend(1.0_f32));
+        if let Some((p, _)) = clip_line(p, p) {
+            let position = self.framebuffer.to_screen(p);
+            self.tiler.add_point(position, color);
+        }
+    }
+
+    pub fn draw_line(&mut self, a: Point3<f32>, b: Point3<f32>, color: u32) -> &mut Scene {
+        self.add_line(a.to_vec(), b.to_vec(), color);
+        self.tiler.flush(&mut self.framebuffer);
+
+        self
+    }
+
+    // normals of vertices with the length in the model space
+    pub fn draw_normals(&mut self, mesh: &Model, mat_world: Matrix4<f32>, length: f32, color: u32) -> &mut Scene {
+        for v in &mesh.vertex_buffer {
+            let a = mat_world.mul(&v.position.extend(1.0_f32)).truncate();
+            let b = mat_world.mul(&v.position.add(&v.normal.mul(length)).extend(1.0_f32)).truncate();
+            self.add_line(a, b, color);
+        }
+        self.tiler.flush(&mut self.framebuffer);
+
+        self
+    }
+
+    // axis aligned box of the model in the model space
+    pub fn draw_bounds(&mut self, mesh: &Model, mat_world: Matrix4<f32>, color: u32) -> &mut Scene {
+        let (min, max) = mesh.bounds();
+        // bit 0 - x, 1 - y, 2 - z of the corner is max
+        let corner = |ind: usize| Vector3::new(if ind & 1 == 0 { min.x } else { max.x },
+                                               if ind & 2 == 0 { min.y } else { max.y },
+                                               if ind & 4 == 0 { min.z } else { max.z });
+        for ind in 0..8 {
+            for axis in &[1, 2, 4] {
+                if ind & axis == 0 {
+                    let a = mat_world.mul(&corner(ind).extend(1.0_f32)).truncate();
+                    let b = mat_world.mul(&corner(ind | axis).extend(1.0_f32)).truncate();
+                    self.add_line(a, b, color);
+                }
+            }
+        }
+        self.tiler.flush(&mut self.framebuffer);
+
+        self
+    }
 
-        self.cnt_triangle += mesh.draw(shader, &mut self.device);
+    // lines to directional lights from the center, positions of point and spot lights,
+    // spot lights with the direction of the cone
+    pub fn draw_lights(&mut self, center: Point3<f32>, length: f32, color: u32) -> &mut Scene {
+        let lights = self.lights.iter()
+            .map(|l| (l.light_type, l.position, l.direction))
+            .collect::<Vec<_>>();
+        for (light_type, position, direction) in lights {
+            match light_type {
+                LightType::Directional => self.add_line(center.to_vec(), center.to_vec().sub(&direction.mul(length)), color),
+                LightType::Point => self.add_point(position, color),
+                LightType::Spot => {
+                    self.add_point(position, color);
+                    self.add_line(position, position.add(&direction.mul(length)), color);
+                },
+            }
+        }
+        self.tiler.flush(&mut self.framebuffer);
+
+        self
+    }
+
+    // transparent meshes of all draw calls and their triangles are drawn from back to front after the opaque ones,
+    // called by resolve
+    pub fn flush_transparent(&mut self) -> &mut Scene {
+        self.tiler.flush_transparent(&mut self.framebuffer);
+
+        self
+    }
+
+    // finishes the frame: draws transparent meshes and averages samples with antialiasing,
+    // called by present, without a window call it before framebuffer or save
+    pub fn resolve(&mut self) -> &mut Scene {
+        self.flush_transparent();
+        if let Some(ref mut frame) = self.frame {
+            self.framebuffer.resolve(frame);
+        }
 
         self
     }
 
     pub fn present(&mut self) {
-        self.device.present();
-        self.device.update_fps(self.cnt_triangle);
+        self.resolve();
+        if let Some(ref mut device) = self.device {
+            device.present(self.frame.as_ref().unwrap_or(&self.framebuffer));
+            device.update_fps(self.cnt_triangle);
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        self.framebuffer().save_color(path)
+    }
+
+    pub fn save_depth(&self, path: &Path) -> Result<(), String> {
+        self.framebuffer().save_depth(path)
     }
 }
@@ -1,45 +1,106 @@
-use std;
+use std::sync::Arc;
 use cgmath::{Vector3, Vector4, Matrix4};
-use texture::{Surface, TextureCube};
+use texture::Surface;
+use material::Material;
+use light::Light;
+use mesh::Vertex;
+use shader::varyings::Varyings;
 
-pub const MATRIX_PROJ_VIEW_WORLD: usize = 0;
-pub const MATRIX_VIEW_WORLD: usize = 1;
-pub const MATRIX_WORLD: usize = 2;
+pub const MAX_OUT_VALUES: usize = 24;
+// reflection vector for cubemap is added after varyings of the program
+pub const CUBEMAP_OUT_VALUES: usize = 3;
 
-pub const IN_VS_VEC_POS: usize = 0;
-pub const IN_VS_VEC_NORM: usize = 4;
-pub const IN_VS_VEC_TEX: usize = 8;
-pub const IN_VS_VEC_NEG_LIGHT: usize = 10;
-pub const IN_VS_VEC_EYE_POS: usize = 14;
+// the same values for all vertices and pixels of one draw call
+#[derive(Clone)]
+pub struct Uniforms {
+    pub mat_proj_view_world: Matrix4<f32>,
+    pub mat_view_world: Matrix4<f32>,
+    pub mat_world: Matrix4<f32>,
+    pub eye_pos: Vector3<f32>,         // in world space
+    pub ambient_intensity: f32,        // [0; 1]
+    pub lights: Vec<Light>,
+    pub material: Material,
+    pub texture: Option<Arc<Surface>>, // mip level of material.texture for the current triangle
+    pub texture_specular: Option<Arc<Surface>>, // the same for material.texture_specular
+    pub texture_bump: Option<Arc<Surface>>,     // the same for material.texture_bump
+    pub texture_metallic_roughness: Option<Arc<Surface>>, // the same for material.texture_metallic_roughness
+    pub texture_occlusion: Option<Arc<Surface>>, // the same for material.texture_occlusion
+    pub texture_emissive: Option<Arc<Surface>>, // the same for material.texture_emissive
+    pub texture_alpha: Option<Arc<Surface>>,    // the same for Material::alpha_texture
+    pub front_facing: bool,                     // false for back faces of the current triangle
+}
+
+pub trait VertexShader {
+    type Varyings: Varyings;
+
+    // returns position in clip space and values for interpolation
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f32>, Self::Varyings);
+}
+
+pub trait PixelShader {
+    type Varyings: Varyings;
+
+    // returns color {r, g, b}, [0; 255] and alpha [0; 1], alpha is used by Material::blend
+    fn pixel(&self, uniforms: &Uniforms, varyings: Self::Varyings) -> Vector4<f32>;
+
+    // true if the shader lights by material.texture_cube itself,
+    // otherwise the reflection of the cubemap is mixed with the result
+    fn uses_cubemap(&self) -> bool {
+        false
+    }
+}
+
+// vertex and pixel shaders without types of varyings, as they are seen by the rasterizer
+pub trait Program: Send + Sync {
+    // returns position in clip space and count of values written to out
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex, out: &mut [f32]) -> (Vector4<f32>, usize);
 
-pub const MAX_OUT_VALUES: usize = 16;
+    fn pixel(&self, uniforms: &Uniforms, data: &[f32]) -> Vector4<f32>;
 
-pub type ShadersVertex = [fn(&mut Shader) -> Vector4<f32>; 2];
-pub type ShadersPixel = [fn(&Shader) -> Vector3<f32>; 2];
-pub type ShadersType = (ShadersVertex, ShadersPixel);
+    fn uses_cubemap(&self) -> bool;
+}
+
+pub struct ShaderProgram<V, P> {
+    pub vertex_shader: V,
+    pub pixel_shader: P,
+}
+
+impl<V, P> Program for ShaderProgram<V, P>
+    where V: VertexShader + Send + Sync,
+          P: PixelShader<Varyings = V::Varyings> + Send + Sync {
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex, out: &mut [f32]) -> (Vector4<f32>, usize) {
+        let (pos, varyings) = self.vertex_shader.vertex(uniforms, vertex);
+        varyings.write(out);
+
+        (pos, <V::Varyings as Varyings>::len())
+    }
+
+    fn pixel(&self, uniforms: &Uniforms, data: &[f32]) -> Vector4<f32> {
+        self.pixel_shader.pixel(uniforms, <P::Varyings as Varyings>::read(data))
+    }
+
+    fn uses_cubemap(&self) -> bool {
+        self.pixel_shader.uses_cubemap()
+    }
+}
 
+#[derive(Clone)]
 pub struct Shader {
-    pub matrix_arr: [Matrix4<f32>; 3], // see MATRIX_*
-    pub in_vertex_data: Vec<f32>,      // see IN_VS_*
+    pub uniforms: Uniforms,
+    pub program: Arc<Program>,
     pub out_vertex_data: [f32; MAX_OUT_VALUES],
     pub in_pixel_data: [f32; MAX_OUT_VALUES],
-    pub texture: Option<std::rc::Rc<Surface>>,
-    pub texture_cube: Option<std::rc::Rc<TextureCube>>,
-    pub ambient: Vector3<f32>,           // {r, g, b}
-    pub diffuse: Vector3<f32>,           // {r, g, b}
-    pub specular: Vector3<f32>,          // {r, g, b}
-    pub ambient_intensity: f32,          // [0; 1]
     pub vertex_out_len: usize,
-    pub vertex_out2_base: usize,
-    pub vertex_func: ShadersVertex,
-    pub pixel_func: ShadersPixel,
+    pub vertex_out2_base: usize, // offset of values for cubemap
 }
 
 #[allow(dead_code)]
 pub enum ShaderType {
     Default,
+    Depth,
     Normal,
     Lambert,
     PhongBlinn,
     CookTorrance,
+    Pbr,
 }
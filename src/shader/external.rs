@@ -1,62 +1,231 @@
-use cgmath::{Vector2, Vector3, Vector4, Matrix, Matrix4};
+use std::sync::Arc;
+use cgmath::{Vector, EuclideanVector, Vector2, Vector3, Vector4, Matrix, Matrix4};
+use std::ops::{Add, Sub, Mul};
 use shader::base::*;
+use shader::varyings::Varyings;
+use shader::{DefaultShader, DepthShader, NormalShader, LambertShader, PhongBlinnShader, CookTorranceShader, PbrShader};
 use material::Material;
+use light::Light;
+use blend::blend;
+use mesh::Vertex;
+
+impl Uniforms {
+    pub fn new() -> Uniforms {
+        Uniforms {
+            mat_proj_view_world: Matrix4::<f32>::zero(),
+            mat_view_world: Matrix4::<f32>::zero(),
+            mat_world: Matrix4::<f32>::zero(),
+            eye_pos: Vector3::new(0.0_f32, 0.0_f32, 0.0_f32),
+            ambient_intensity: 0.0_f32,
+            lights: Vec::<Light>::new(),
+            material: Material::new(),
+            texture: None,
+            texture_specular: None,
+            texture_bump: None,
+            texture_metallic_roughness: None,
+            texture_occlusion: None,
+            texture_emissive: None,
+            texture_alpha: None,
+            front_facing: true,
+        }
+    }
+
+    // ambient and diffuse colors: from the texture or the material, multiplied by the vertex color
+    pub fn base_colors(&self, tex: Vector2<f32>, color: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        let (ambient, diffuse) = match self.texture {
+            Some(ref t) => {
+                let color = t.tex_2d_bilinear(tex);
+                (color, color)
+            },
+            None => (self.material.ambient, self.material.diffuse),
+        };
+        if self.material.vertex_color {
+            let k = color.mul(1.0_f32 / 255.0_f32);
+            (ambient.mul(&k), diffuse.mul(&k))
+        } else {
+            (ambient, diffuse)
+        }
+    }
+
+    // Vertex::tangent in world space, the handedness is not changed
+    pub fn world_tangent(&self, tangent: Vector4<f32>) -> Vector4<f32> {
+        self.mat_world.mul(&tangent.truncate().extend(0.0_f32)).truncate().extend(tangent.w)
+    }
+
+    // normalized normal in world space of the visible side, perturbed by the normal map if it is set:
+    // norm and tangent are interpolated varyings, tangent.w - handedness of the bitangent
+    pub fn normal(&self, tex: Vector2<f32>, norm: Vector3<f32>, tangent: Vector4<f32>) -> Vector3<f32> {
+        // back faces of double-sided meshes
+        let norm = if self.front_facing { norm.normalize() } else { norm.normalize().mul(-1.0_f32) };
+        let texture = match self.texture_bump {
+            Some(ref t) => t,
+            None => return norm,
+        };
+        // the tangent can lose orthogonality after the interpolation
+        let tangent_xyz = tangent.truncate();
+        let tangent_xyz = tangent_xyz.sub(&norm.mul(norm.dot(tangent_xyz)));
+        if tangent_xyz.length() == 0.0_f32 {
+            return norm;
+        }
+        let tangent_xyz = tangent_xyz.normalize();
+        let bitangent = norm.cross(tangent_xyz).mul(if tangent.w < 0.0_f32 { -1.0_f32 } else { 1.0_f32 });
+
+        // [0; 255] -> [-1; 1]
+        let value = texture.tex_2d_bilinear(tex).mul(2.0_f32 / 255.0_f32).sub(&Vector3::from_value(1.0_f32));
+        let result = tangent_xyz.mul(value.x * self.material.bump_scale)
+            .add(&bitangent.mul(value.y * self.material.bump_scale))
+            .add(&norm.mul(value.z));
+
+        if result.length() > 0.0_f32 { result.normalize() } else { norm }
+    }
+
+    // specular color of the material multiplied by the specular map
+    pub fn specular(&self, tex: Vector2<f32>) -> Vector3<f32> {
+        match self.texture_specular {
+            Some(ref t) => self.material.specular.mul(&t.tex_2d_bilinear(tex)).mul(1.0_f32 / 255.0_f32),
+            None => self.material.specular,
+        }
+    }
+
+    // metallic and roughness of the material multiplied by blue and green channels of the texture
+    pub fn metallic_roughness(&self, tex: Vector2<f32>) -> (f32, f32) {
+        match self.texture_metallic_roughness {
+            Some(ref t) => {
+                let value = t.tex_2d_bilinear(tex).mul(1.0_f32 / 255.0_f32);
+                (self.material.metallic * value.z, self.material.roughness * value.y)
+            },
+            None => (self.material.metallic, self.material.roughness),
+        }
+    }
+
+    // [0; 1], multiplies the ambient light
+    pub fn occlusion(&self, tex: Vector2<f32>) -> f32 {
+        match self.texture_occlusion {
+            Some(ref t) => t.tex_2d_bilinear(tex).x / 255.0_f32,
+            None => 1.0_f32,
+        }
+    }
+
+    // [0; 1], alpha of the material multiplied by the alpha map
+    pub fn alpha(&self, tex: Vector2<f32>) -> f32 {
+        match self.texture_alpha {
+            Some(ref t) => self.material.alpha * t.tex_2d_bilinear(tex).x / 255.0_f32,
+            None => self.material.alpha,
+        }
+    }
+
+    // emissive color of the material multiplied by the emissive map
+    pub fn emissive(&self, tex: Vector2<f32>) -> Vector3<f32> {
+        match self.texture_emissive {
+            Some(ref t) => self.material.emissive.mul(&t.tex_2d_bilinear(tex)).mul(1.0_f32 / 255.0_f32),
+            None => self.material.emissive,
+        }
+    }
+}
 
 impl Shader {
     pub fn new(shader_type: ShaderType) -> Shader {
-        let (vertex_func, pixel_func) = match shader_type {
-            ShaderType::Default => Shader::shader_default(),
-            ShaderType::Normal => Shader::shader_normal(),
-            ShaderType::Lambert => Shader::shader_lambert(),
-            ShaderType::PhongBlinn => Shader::shader_phong_blinn(),
-            ShaderType::CookTorrance => Shader::shader_cook_torrance(),
-        };
+        match shader_type {
+            ShaderType::Default => Shader::with_shaders(DefaultShader, DefaultShader),
+            ShaderType::Depth => Shader::with_shaders(DepthShader, DepthShader),
+            ShaderType::Normal => Shader::with_shaders(NormalShader, NormalShader),
+            ShaderType::Lambert => Shader::with_shaders(LambertShader, LambertShader),
+            ShaderType::PhongBlinn => Shader::with_shaders(PhongBlinnShader, PhongBlinnShader),
+            ShaderType::CookTorrance => Shader::with_shaders(CookTorranceShader, CookTorranceShader),
+            ShaderType::Pbr => Shader::with_shaders(PbrShader, PbrShader),
+        }
+    }
+
+    pub fn with_shaders<V, P>(vertex_shader: V, pixel_shader: P) -> Shader
+        where V: VertexShader + Send + Sync + 'static,
+              P: PixelShader<Varyings = V::Varyings> + Send + Sync + 'static {
+        let len = <V::Varyings as Varyings>::len();
+        assert!(len + CUBEMAP_OUT_VALUES <= MAX_OUT_VALUES,
+                "too many varyings: {}, max = {}", len, MAX_OUT_VALUES - CUBEMAP_OUT_VALUES);
 
         Shader {
-            matrix_arr: [Matrix4::<f32>::zero(); 3],
-            in_vertex_data: vec![0.0_f32; 18],
+            uniforms: Uniforms::new(),
+            program: Arc::new(ShaderProgram {
+                vertex_shader: vertex_shader,
+                pixel_shader: pixel_shader,
+            }),
             out_vertex_data: [0.0_f32; MAX_OUT_VALUES],
             in_pixel_data: [0.0_f32; MAX_OUT_VALUES],
-            texture: None,
-            texture_cube: None,
-            ambient: Vector3::new(0.0_f32, 0.0_f32, 0.0_f32),
-            diffuse: Vector3::new(0.0_f32, 0.0_f32, 0.0_f32),
-            specular: Vector3::new(0.0_f32, 0.0_f32, 0.0_f32),
-            ambient_intensity: 0.0_f32,
             vertex_out_len: 0,
             vertex_out2_base: 0,
-            vertex_func: vertex_func,
-            pixel_func: pixel_func,
         }
     }
 
-    pub fn reset(&mut self, position: Vector4<f32>, normal: Vector4<f32>, tex: Vector2<f32>) {
-        self.vertex_out_len = 0;
-        self.vertex_out2_base = 0;
-        self.set_vec4(IN_VS_VEC_POS, position);
-        self.set_vec4(IN_VS_VEC_NORM, normal);
-        self.set_vec2(IN_VS_VEC_TEX, tex);
+    pub fn set_material(&mut self, material: &Material) {
+        self.uniforms.material = material.clone();
+    }
+
+    // selects mip level of the material textures for the next triangle
+    pub fn set_mip_level(&mut self, lod: usize) {
+        self.uniforms.texture = self.uniforms.material.texture.as_ref().map(|t| t.get_surface(lod));
+        self.uniforms.texture_specular = self.uniforms.material.texture_specular.as_ref().map(|t| t.get_surface(lod));
+        self.uniforms.texture_bump = self.uniforms.material.texture_bump.as_ref().map(|t| t.get_surface(lod));
+        self.uniforms.texture_metallic_roughness =
+            self.uniforms.material.texture_metallic_roughness.as_ref().map(|t| t.get_surface(lod));
+        self.uniforms.texture_occlusion = self.uniforms.material.texture_occlusion.as_ref().map(|t| t.get_surface(lod));
+        self.uniforms.texture_emissive = self.uniforms.material.texture_emissive.as_ref().map(|t| t.get_surface(lod));
+        self.uniforms.texture_alpha = self.uniforms.material.alpha_texture().map(|t| t.get_surface(lod));
+    }
+
+    // result: position in clip space, varyings in out_vertex_data[..vertex_out_len]
+    pub fn vertex(&mut self, vertex: &Vertex) -> Vector4<f32> {
+        let (pos, len) = self.program.vertex(&self.uniforms, vertex, &mut self.out_vertex_data);
+        self.vertex_out_len = len;
+        self.vertex_out2_base = len;
+        if self.uniforms.material.texture_cube.is_some() && !self.program.uses_cubemap() {
+            self.vertex_cubemap(vertex);
+        }
+
+        pos
+    }
+
+    // input: interpolated varyings in in_pixel_data[..vertex_out_len]
+    pub fn pixel(&self) -> Vector4<f32> {
+        let color = self.program.pixel(&self.uniforms, &self.in_pixel_data[..self.vertex_out2_base]);
+        match self.uniforms.material.texture_cube {
+            Some(_) if !self.program.uses_cubemap() => self.pixel_cubemap(color.truncate()).extend(color.w),
+            _ => color,
+        }
     }
 
-    pub fn set_material(&mut self, material: &Material) {
-        self.ambient = material.ambient;
-        self.diffuse = material.diffuse;
-        self.specular = material.specular;
+    // depth test of the pixel with depth d by Material::render_state
+    #[inline]
+    pub fn depth_test(&self, buffer: f32, d: f32) -> bool {
+        self.uniforms.material.render_state.depth_func.test(d, buffer)
     }
 
-    pub fn set_matrix(&mut self, ind: usize, matrix: Matrix4<f32>) {
-        self.matrix_arr[ind] = matrix;
+    // runs the pixel shader, None if the pixel is discarded by Material::alpha_cutoff
+    #[inline]
+    pub fn shade(&self) -> Option<Vector4<f32>> {
+        let src = self.pixel();
+        match self.uniforms.material.alpha_cutoff {
+            Some(cutoff) if src.w < cutoff => None,
+            _ => Some(src),
+        }
     }
 
-    pub fn set_vec4(&mut self, sm: usize, vector: Vector4<f32>) {
-        self.in_vertex_data[sm + 0] = vector.x;
-        self.in_vertex_data[sm + 1] = vector.y;
-        self.in_vertex_data[sm + 2] = vector.z;
-        self.in_vertex_data[sm + 3] = vector.w;
+    // combines the shaded color with one sample of the framebuffer by Material::blend
+    #[inline]
+    pub fn write_sample(&self, src: Vector4<f32>, color: &mut u32, depth: &mut f32, d: f32) {
+        let material = &self.uniforms.material;
+        let state = &material.render_state;
+        *color = state.mask_color(blend(material.blend, src, *color), *color);
+        if state.depth_write {
+            *depth = d;
+        }
     }
 
-    pub fn set_vec2(&mut self, sm: usize, vector: Vector2<f32>) {
-        self.in_vertex_data[sm + 0] = vector.x;
-        self.in_vertex_data[sm + 1] = vector.y;
+    // runs the pixel shader and combines its output with the framebuffer,
+    // the pixel is discarded without the depth write if its alpha is below Material::alpha_cutoff
+    #[inline]
+    pub fn write_pixel(&self, color: &mut u32, depth: &mut f32, d: f32) {
+        if let Some(src) = self.shade() {
+            self.write_sample(src, color, depth, d);
+        }
     }
 }
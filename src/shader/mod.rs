@@ -1,11 +1,21 @@
 mod base;
+mod varyings;
 mod external;
-mod internal;
 mod sh_default;
+mod sh_depth;
 mod sh_normal;
 mod sh_lambert;
 mod sh_phong_blinn;
 mod sh_cook_torrance;
+mod sh_pbr;
 mod sh_cubemap;
 
 pub use shader::base::*;
+pub use shader::varyings::Varyings;
+pub use shader::sh_default::DefaultShader;
+pub use shader::sh_depth::DepthShader;
+pub use shader::sh_normal::NormalShader;
+pub use shader::sh_lambert::LambertShader;
+pub use shader::sh_phong_blinn::PhongBlinnShader;
+pub use shader::sh_cook_torrance::CookTorranceShader;
+pub use shader::sh_pbr::PbrShader;
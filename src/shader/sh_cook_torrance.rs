@@ -1,112 +1,97 @@
 use cgmath::{Vector, EuclideanVector, Vector2, Vector3, Vector4};
 use shader::base::*;
+use light::Light;
+use material::Material;
+use mesh::Vertex;
 use std::ops::{Sub, Add, Mul};
 
+// microfacet specular with Beckmann distribution per pixel
+pub struct CookTorranceShader;
 
-impl Shader {
-    pub fn shader_cook_torrance() -> ShadersType {
-        ([Shader::vertex_cook_torrance_color, Shader::vertex_cook_torrance_texture],
-         [Shader::pixel_cook_torrance_color, Shader::pixel_cook_torrance_texture])
-    }
-
-    // out:
-    // 0 - Vector3 view
-    // 3 - Vector3 norm
-    fn vertex_cook_torrance_color(&mut self) -> Vector4<f32> {
-        let pos_pvw = self.matrix_arr[MATRIX_PROJ_VIEW_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS));
-        let pos_w = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS));
-        let norm = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_NORM).normalize()).normalize();
-        let view = self.read_vec4(IN_VS_VEC_EYE_POS).sub(&pos_w).normalize();
+// roughness of materials without the specular exponent in the file
+const DEFAULT_ROUGHNESS: f32 = 0.3_f32;
 
-        self.out_vec3_from4(&view);
-        self.out_vec3_from4(&norm);
-        pos_pvw
+// square of Beckmann roughness, m = sqrt(2 / (Ns + 2)) gives the same highlight as the Blinn-Phong exponent Ns
+fn roughness_sq(material: &Material) -> f32 {
+    if material.shininess_given {
+        2.0_f32 / (material.shininess.max(0.0_f32) + 2.0_f32)
+    } else {
+        DEFAULT_ROUGHNESS * DEFAULT_ROUGHNESS
     }
+}
 
-    // in:
-    // 0 - Vector3 view
-    // 3 - Vector3 norm
-    fn pixel_cook_torrance_color(&self) -> Vector3<f32> {
-        let view = Vector3::new(self.in_pixel_data[0], self.in_pixel_data[1], self.in_pixel_data[2]).normalize();
-        let norm = Vector3::new(self.in_pixel_data[3], self.in_pixel_data[4], self.in_pixel_data[5]).normalize();
-        let light = self.read_vec3(IN_VS_VEC_NEG_LIGHT);
-        let half = view.add(&light).normalize();
-
-        const ROUGHNESS: f32 = 0.3_f32;
-        const ROUGHNESS_SQ: f32 = ROUGHNESS * ROUGHNESS;
+// sum of diffuse and specular light from all lights
+fn cook_torrance_light(lights: &[Light], pos: Vector3<f32>, view: Vector3<f32>, norm: Vector3<f32>,
+                       diffuse: Vector3<f32>, specular: Vector3<f32>, roughness_sq: f32) -> Vector3<f32> {
+    let cos_vn = view.dot(norm).max(0.0_f32);
+    let frenel = 1.0_f32 / (1.0_f32 + cos_vn);
+    let mut result = Vector3::new(0.0_f32, 0.0_f32, 0.0_f32);
+    for light in lights {
+        let (light_dir, light_color) = light.illuminate(pos);
+        let half = view.add(&light_dir).normalize();
 
         let cos_hn = half.dot(norm).max(0.0000001_f32);
         let cos_hn_sq = cos_hn * cos_hn;
-        let cos_vn = view.dot(norm).max(0.0_f32);
-        let cos_ln = light.dot(norm).max(0.0_f32);
+        let cos_ln = light_dir.dot(norm).max(0.0_f32);
         let cos_vh = view.dot(half).max(0.0_f32);
 
         let geometric = 1.0_f32.min((2.0_f32 * cos_hn * cos_vn.min(cos_ln)) / cos_vh);
-        let frenel = 1.0_f32 / (1.0_f32 + cos_vn);
-        let pow_val = (cos_hn_sq - 1.0_f32) / (ROUGHNESS_SQ * cos_hn_sq);
-        let d = pow_val.exp() / (4.0_f32 * ROUGHNESS_SQ * cos_hn_sq * cos_hn_sq);
+        let pow_val = (cos_hn_sq - 1.0_f32) / (roughness_sq * cos_hn_sq);
+        let d = pow_val.exp() / (4.0_f32 * roughness_sq * cos_hn_sq * cos_hn_sq);
         let k = (geometric * frenel * d) / (cos_vn * cos_ln + 0.0000001_f32);
 
-        let ambient = self.ambient.mul(self.ambient_intensity);
-        let specular = self.specular.mul(k);
-        let diffuse_specular = self.diffuse.add(&specular).mul(cos_ln.max(0.0_f32));
-
-        ambient.add(&diffuse_specular)
+        result = result.add(&diffuse.add(&specular.mul(k)).mul(cos_ln).mul(&light_color));
     }
 
-    // out:
-    // 0 - Vector2 tex
-    // 2 - Vector3 view
-    // 5 - Vector3 norm
-    fn vertex_cook_torrance_texture(&mut self) -> Vector4<f32> {
-        let pos_pvw = self.matrix_arr[MATRIX_PROJ_VIEW_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS));
-        let pos_w = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS));
-        let mut norm = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_NORM)).normalize();
-        norm.neg_self();
-        let view = self.read_vec4(IN_VS_VEC_EYE_POS).sub(&pos_w).normalize();
-        let tex = self.read_vec2(IN_VS_VEC_TEX);
-
-        self.out_vec2(&tex);
-        self.out_vec3_from4(&view);
-        self.out_vec3_from4(&norm);
-        pos_pvw
-    }
-
-    // in:
-    // 0 - Vector2 tex
-    // 2 - Vector3 view
-    // 5 - Vector3 norm
-    fn pixel_cook_torrance_texture(&self) -> Vector3<f32> {
-        let tex = Vector2::new(self.in_pixel_data[0], self.in_pixel_data[1]);
-        let view = Vector3::new(self.in_pixel_data[2], self.in_pixel_data[3], self.in_pixel_data[4]).normalize();
-        let norm = Vector3::new(self.in_pixel_data[5], self.in_pixel_data[6], self.in_pixel_data[7]).normalize();
-        let light = self.read_vec3(IN_VS_VEC_NEG_LIGHT);
-        let half = view.add(&light).normalize();
-
-        const ROUGHNESS: f32 = 0.3_f32;
-        const ROUGHNESS_SQ: f32 = ROUGHNESS * ROUGHNESS;
+    result
+}
 
-        let cos_hn = half.dot(norm).max(0.0000001_f32);
-        let cos_hn_sq = cos_hn * cos_hn;
-        let cos_vn = view.dot(norm).max(0.0_f32);
-        let cos_ln = light.dot(norm).max(0.0_f32);
-        let cos_vh = view.dot(half).max(0.0_f32);
+impl VertexShader for CookTorranceShader {
+    // tex, view, norm, pos, color, tangent
+    type Varyings = (Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>);
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f32>, Self::Varyings) {
+        let pos_pvw = uniforms.mat_proj_view_world.mul(&vertex.position.extend(1.0_f32));
+        let pos_w = uniforms.mat_world.mul(&vertex.position.extend(1.0_f32));
+        let mut tangent = uniforms.world_tangent(vertex.tangent);
+        let norm = if uniforms.material.texture.is_some() {
+            let mut norm = uniforms.mat_world.mul(&vertex.normal.extend(0.0_f32)).normalize();
+            // the whole tangent space is flipped with the normal
+            norm.neg_self();
+            tangent.neg_self();
+            norm
+        } else {
+            uniforms.mat_world.mul(&vertex.normal.extend(0.0_f32).normalize()).normalize()
+        };
+        let view = uniforms.eye_pos.extend(1.0_f32).sub(&pos_w).normalize();
 
-        let geometric = 1.0_f32.min((2.0_f32 * cos_hn * cos_vn.min(cos_ln)) / cos_vh);
-        let frenel = 1.0_f32 / (1.0_f32 + cos_vn);
-        let pow_val = (cos_hn_sq - 1.0_f32) / (ROUGHNESS_SQ * cos_hn_sq);
-        let d = pow_val.exp() / (4.0_f32 * ROUGHNESS_SQ * cos_hn_sq * cos_hn_sq);
-        let k = (geometric * frenel * d) / (cos_vn * cos_ln + 0.0000001_f32);
+        (pos_pvw, (vertex.tex, view.truncate(), norm.truncate(), pos_w.truncate(), vertex.color, tangent))
+    }
+}
 
-        let color = match self.texture {
-            Some(ref t) => t.tex_2d_bilinear(tex),
-            None => panic!("texture is none"),
+impl PixelShader for CookTorranceShader {
+    type Varyings = (Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>);
+
+    fn pixel(&self, uniforms: &Uniforms, (tex, view, norm, pos, color, tangent): Self::Varyings) -> Vector4<f32> {
+        let material = &uniforms.material;
+        let alpha = uniforms.alpha(tex);
+        let (ambient_color, diffuse_color) = uniforms.base_colors(tex, color);
+        let emissive = uniforms.emissive(tex);
+        if material.illumination == 0 {
+            return diffuse_color.add(&emissive).extend(alpha);
+        }
+
+        let view = view.normalize();
+        let norm = uniforms.normal(tex, norm, tangent);
+        let specular = if material.illumination == 1 {
+            Vector3::new(0.0_f32, 0.0_f32, 0.0_f32)
+        } else {
+            uniforms.specular(tex)
         };
+        let ambient = ambient_color.mul(uniforms.ambient_intensity);
+        let diffuse_specular = cook_torrance_light(&uniforms.lights, pos, view, norm, diffuse_color, specular,
+                                                   roughness_sq(material));
 
-        let ambient = color.mul(self.ambient_intensity);
-        let specular = self.specular.mul(k);
-        let diffuse_specular = color.add(&specular).mul(cos_ln.max(0.0_f32));
-
-        ambient.add(&diffuse_specular)
+        ambient.add(&diffuse_specular).add(&emissive).extend(alpha)
     }
 }
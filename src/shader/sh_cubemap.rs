@@ -1,17 +1,23 @@
-use cgmath::{Vector, Vector2, Vector3, EuclideanVector};
+use cgmath::{Vector, Vector3, EuclideanVector};
 use shader::base::*;
+use mesh::Vertex;
+use texture::TextureCube;
 use std::ops::{Sub, Mul};
 
 impl Shader {
     // out:
     // 0 - Vector3 reflection
-    pub fn vertex_cubemap(&mut self) {
-        let eye = self.matrix_arr[MATRIX_VIEW_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS)).normalize();
-        let norm = self.matrix_arr[MATRIX_VIEW_WORLD]
-            .mul(&self.read_vec4(IN_VS_VEC_NORM).normalize()).normalize();
+    pub fn vertex_cubemap(&mut self, vertex: &Vertex) {
+        let mat_view_world = self.uniforms.mat_view_world;
+        let eye = mat_view_world.mul(&vertex.position.extend(1.0_f32)).normalize();
+        let norm = mat_view_world.mul(&vertex.normal.extend(0.0_f32).normalize()).normalize();
         let reflection = eye.sub(&norm.mul(norm.dot(eye) * 2.0_f32));
 
-        self.out_vec3_from4(&reflection)
+        let offset = self.vertex_out2_base;
+        self.out_vertex_data[offset + 0] = reflection.x;
+        self.out_vertex_data[offset + 1] = reflection.y;
+        self.out_vertex_data[offset + 2] = reflection.z;
+        self.vertex_out_len = offset + CUBEMAP_OUT_VALUES;
     }
 
     // in:
@@ -23,41 +29,9 @@ impl Shader {
                                       self.in_pixel_data[offset + 1],
                                       self.in_pixel_data[offset + 2]).normalize();
 
-        let irx = reflection.x;
-        let iry = reflection.y;
-        let irz = reflection.z;
-        let urx = irx.abs();
-        let ury = iry.abs();
-        let urz = irz.abs();
-
-        let (index, sc, tc, ma) =
-            // x plane
-            if (urx > urz) && (urx > ury) {
-                if irx.is_sign_positive() {
-                    (0, -irz,  iry, urx)
-                } else {
-                    (1,  irz,  iry, urx)
-                }
-                // y plane
-            } else if ury > urz {
-                if iry > 0.0_f32 {
-                    (2,  irx, -irz, ury)
-                } else {
-                    (3,  irx,  irz, ury)
-                }
-                // z plane
-            } else {
-                if irz.is_sign_positive() {
-                    (4,  irx,  iry, urz)
-                } else {
-                    (5, -irx,  iry, urz)
-                }
-            };
-
-        let coord = Vector2::new(( sc/ma + 1.0_f32 ) / 2.0_f32,
-                                 ( tc/ma + 1.0_f32 ) / 2.0_f32);
+        let (index, coord) = TextureCube::face_coord(reflection);
 
-        match self.texture_cube {
+        match self.uniforms.material.texture_cube {
             Some(ref t) => t
                 .get_texture(index)
                 .get_surface(lod)
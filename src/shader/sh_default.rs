@@ -1,22 +1,25 @@
-use cgmath::{Vector3, Vector4};
+use cgmath::Vector4;
 use shader::base::*;
+use mesh::Vertex;
 use std::ops::Mul;
 
-impl Shader {
-    pub fn shader_default() -> ShadersType {
-        ([Shader::vertex_default_color, Shader::vertex_default_color],
-         [Shader::pixel_default_color, Shader::pixel_default_color])
-    }
+// white color without lighting
+pub struct DefaultShader;
+
+impl VertexShader for DefaultShader {
+    type Varyings = ();
 
-    // out:
-    fn vertex_default_color(&mut self) -> Vector4<f32> {
-        let pos = self.matrix_arr[MATRIX_PROJ_VIEW_WORLD].mul(self.read_vec4(IN_VS_VEC_POS));
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f32>, ()) {
+        let pos = uniforms.mat_proj_view_world.mul(vertex.position.extend(1.0_f32));
 
-        pos
+        (pos, ())
     }
+}
+
+impl PixelShader for DefaultShader {
+    type Varyings = ();
 
-    // in:
-    fn pixel_default_color(&self) -> Vector3<f32> {
-        Vector3::new(255.0_f32, 255.0_f32, 255.0_f32)
+    fn pixel(&self, _: &Uniforms, _: ()) -> Vector4<f32> {
+        Vector4::new(255.0_f32, 255.0_f32, 255.0_f32, 1.0_f32)
     }
 }
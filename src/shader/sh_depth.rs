@@ -0,0 +1,27 @@
+use cgmath::{Vector2, Vector4};
+use shader::base::*;
+use mesh::Vertex;
+use std::ops::Mul;
+
+// white color with the alpha of the material, for depth passes:
+// cutout pixels of materials with the alpha test are discarded as in the color pass
+pub struct DepthShader;
+
+impl VertexShader for DepthShader {
+    // tex
+    type Varyings = Vector2<f32>;
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f32>, Vector2<f32>) {
+        let pos = uniforms.mat_proj_view_world.mul(vertex.position.extend(1.0_f32));
+
+        (pos, vertex.tex)
+    }
+}
+
+impl PixelShader for DepthShader {
+    type Varyings = Vector2<f32>;
+
+    fn pixel(&self, uniforms: &Uniforms, tex: Vector2<f32>) -> Vector4<f32> {
+        Vector4::new(255.0_f32, 255.0_f32, 255.0_f32, uniforms.alpha(tex))
+    }
+}
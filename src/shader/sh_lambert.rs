@@ -1,64 +1,55 @@
 use cgmath::{Vector, EuclideanVector, Vector2, Vector3, Vector4};
 use shader::base::*;
+use light::Light;
+use mesh::Vertex;
 use std::ops::{Add, Mul};
 
+// diffuse lighting per pixel
+pub struct LambertShader;
 
-impl Shader {
-    pub fn shader_lambert() -> ShadersType {
-        ([Shader::vertex_lambert_color, Shader::vertex_lambert_texture],
-         [Shader::pixel_lambert_color, Shader::pixel_lambert_texture])
+// sum of diffuse light from all lights
+fn lambert_light(lights: &[Light], pos: Vector3<f32>, norm: Vector3<f32>) -> Vector3<f32> {
+    let mut result = Vector3::new(0.0_f32, 0.0_f32, 0.0_f32);
+    for light in lights {
+        let (light_dir, light_color) = light.illuminate(pos);
+        let cos_nl = norm.dot(light_dir).max(0.0_f32);
+        result = result.add(&light_color.mul(cos_nl));
     }
 
-    // out:
-    // 0 - f32 cos_nl
-    fn vertex_lambert_color(&mut self) -> Vector4<f32> {
-        let pos = self.matrix_arr[MATRIX_PROJ_VIEW_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS));
-        let norm = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_NORM)).normalize();
-        let cos_nl = norm.dot(self.read_vec4(IN_VS_VEC_NEG_LIGHT));
-
-        self.out_f32(cos_nl);
-        pos
-    }
-
-    // in:
-    // 0 - f32 cos_nl
-    fn pixel_lambert_color(&self) -> Vector3<f32> {
-        let cos_nl = self.in_pixel_data[0];
-        let ambient = self.ambient.mul(self.ambient_intensity);
-        let diffuse = self.diffuse.mul(cos_nl.max(0.0_f32));
+    result
+}
 
-        ambient.add(&diffuse)
-    }
+impl VertexShader for LambertShader {
+    // tex, pos, norm, color, tangent
+    type Varyings = (Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>);
 
-    // out:
-    // 0 - Vector2 tex
-    // 2 - f32 cos_nl
-    fn vertex_lambert_texture(&mut self) -> Vector4<f32> {
-        let pos = self.matrix_arr[MATRIX_PROJ_VIEW_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS));
-        let norm = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_NORM)).normalize();
-        let tex = self.read_vec2(IN_VS_VEC_TEX);
-        let cos_nl = norm.dot(self.read_vec4(IN_VS_VEC_NEG_LIGHT));
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f32>, Self::Varyings) {
+        let pos = uniforms.mat_proj_view_world.mul(&vertex.position.extend(1.0_f32));
+        let pos_w = uniforms.mat_world.mul(&vertex.position.extend(1.0_f32));
+        let norm = uniforms.mat_world.mul(&vertex.normal.extend(0.0_f32)).normalize();
+        let tangent = uniforms.world_tangent(vertex.tangent);
 
-        self.out_vec2(&tex);
-        self.out_f32(cos_nl);
-        pos
+        (pos, (vertex.tex, pos_w.truncate(), norm.truncate(), vertex.color, tangent))
     }
+}
 
-    // in:
-    // 0 - Vector2 tex
-    // 2 - f32 cos_nl
-    fn pixel_lambert_texture(&self) -> Vector3<f32> {
-        let tex = Vector2::new(self.in_pixel_data[0], self.in_pixel_data[1]);
-        let cos_nl = self.in_pixel_data[2];
+impl PixelShader for LambertShader {
+    type Varyings = (Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>);
 
-        let color = match self.texture {
-            Some(ref t) => t.tex_2d_bilinear(tex),
-            None => panic!("texture is none"),
-        };
+    fn pixel(&self, uniforms: &Uniforms, (tex, pos, norm, color, tangent): Self::Varyings) -> Vector4<f32> {
+        let material = &uniforms.material;
+        let alpha = uniforms.alpha(tex);
+        let (ambient_color, diffuse_color) = uniforms.base_colors(tex, color);
+        let emissive = uniforms.emissive(tex);
+        if material.illumination == 0 {
+            return diffuse_color.add(&emissive).extend(alpha);
+        }
 
-        let ambient = color.mul(self.ambient_intensity);
-        let diffuse = color.mul(cos_nl.max(0.0_f32));
+        let norm = uniforms.normal(tex, norm, tangent);
+        let light = lambert_light(&uniforms.lights, pos, norm);
+        let ambient = ambient_color.mul(uniforms.ambient_intensity);
+        let diffuse = diffuse_color.mul(&light);
 
-        ambient.add(&diffuse)
+        ambient.add(&diffuse).add(&emissive).extend(alpha)
     }
 }
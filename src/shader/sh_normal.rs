@@ -1,30 +1,27 @@
 use cgmath::{EuclideanVector, Vector3, Vector4};
 use shader::base::*;
+use mesh::Vertex;
 use std::ops::{Add, Mul};
 
-impl Shader {
-    pub fn shader_normal() -> ShadersType {
-        ([Shader::vertex_normal_color, Shader::vertex_normal_color],
-         [Shader::pixel_normal_color, Shader::pixel_normal_color])
-    }
+// world space normal as color
+pub struct NormalShader;
+
+impl VertexShader for NormalShader {
+    // normal
+    type Varyings = Vector3<f32>;
 
-    // out:
-    // 0 - Vector3 normal
-    fn vertex_normal_color(&mut self) -> Vector4<f32> {
-        let pos = self.matrix_arr[MATRIX_PROJ_VIEW_WORLD].mul(self.read_vec4(IN_VS_VEC_POS));
-        let norm = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_NORM)).normalize();
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f32>, Vector3<f32>) {
+        let pos = uniforms.mat_proj_view_world.mul(vertex.position.extend(1.0_f32));
+        let norm = uniforms.mat_world.mul(&vertex.normal.extend(0.0_f32)).normalize();
 
-        self.out_vec3_from4(&norm);
-        pos
+        (pos, norm.truncate())
     }
+}
 
-    // in:
-    // 0 - Vector3 normal
-    fn pixel_normal_color(&self) -> Vector3<f32> {
-        let color = Vector3::new(self.in_pixel_data[0],
-                                 self.in_pixel_data[1],
-                                 self.in_pixel_data[2]).add(1.0_f32).mul(128.0_f32);
+impl PixelShader for NormalShader {
+    type Varyings = Vector3<f32>;
 
-        color
+    fn pixel(&self, _: &Uniforms, norm: Vector3<f32>) -> Vector4<f32> {
+        norm.add(1.0_f32).mul(128.0_f32).extend(1.0_f32)
     }
 }
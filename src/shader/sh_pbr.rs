@@ -0,0 +1,135 @@
+use cgmath::{Vector, EuclideanVector, Vector2, Vector3, Vector4};
+use shader::base::*;
+use light::Light;
+use mesh::Vertex;
+use std::f32::consts::PI;
+use std::ops::{Add, Sub, Mul};
+
+// metallic-roughness material per pixel: GGX distribution, Smith geometry, Schlick fresnel,
+// image based lighting from material.texture_cube or the ambient light without it
+pub struct PbrShader;
+
+// smaller values give highlights thinner than a pixel
+const MIN_ROUGHNESS: f32 = 0.045_f32;
+// reflectance of dielectrics at normal incidence
+const DIELECTRIC_F0: f32 = 0.04_f32;
+
+fn fresnel_schlick(f0: Vector3<f32>, cos: f32) -> Vector3<f32> {
+    let k = (1.0_f32 - cos).max(0.0_f32).powi(5);
+    f0.add(&Vector3::from_value(1.0_f32).sub(&f0).mul(k))
+}
+
+// GGX (Trowbridge-Reitz) normal distribution, alpha = roughness^2
+fn distribution_ggx(cos_nh: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a_sq = a * a;
+    let d = cos_nh * cos_nh * (a_sq - 1.0_f32) + 1.0_f32;
+    a_sq / (PI * d * d)
+}
+
+// Smith geometry with Schlick-GGX approximation for direct light
+fn geometry_smith(cos_nv: f32, cos_nl: f32, roughness: f32) -> f32 {
+    let k = (roughness + 1.0_f32) * (roughness + 1.0_f32) / 8.0_f32;
+    let g1 = |cos: f32| cos / (cos * (1.0_f32 - k) + k);
+    g1(cos_nv) * g1(cos_nl)
+}
+
+// integral of the specular BRDF over the hemisphere for the split sum approximation,
+// analytic fit of Karis, "Physically Based Shading on Mobile"
+fn env_brdf(f0: Vector3<f32>, cos_nv: f32, roughness: f32) -> Vector3<f32> {
+    let r = Vector4::new(1.0_f32 - roughness, 0.0425_f32 - 0.0275_f32 * roughness,
+                         1.04_f32 - 0.572_f32 * roughness, 0.022_f32 * roughness - 0.04_f32);
+    let a004 = (r.x * r.x).min((-9.28_f32 * cos_nv).exp2()) * r.x + r.y;
+    let scale = r.z - 1.04_f32 * a004;
+    let bias = r.w + 1.04_f32 * a004;
+
+    f0.mul(scale).add(&Vector3::from_value(bias))
+}
+
+// sum of diffuse and specular light from all lights, base color {r, g, b}, [0; 255], f0 in [0; 1].
+// Intensity of lights is the same as in other shaders: lambert surface gets base * cos_nl,
+// so both diffuse and specular BRDF are multiplied by PI
+fn pbr_light(lights: &[Light], pos: Vector3<f32>, view: Vector3<f32>, norm: Vector3<f32>,
+             base: Vector3<f32>, f0: Vector3<f32>, metallic: f32, roughness: f32) -> Vector3<f32> {
+    let cos_nv = norm.dot(view).max(0.0001_f32);
+    let diffuse = base.mul(1.0_f32 - metallic);
+    let mut result = Vector3::new(0.0_f32, 0.0_f32, 0.0_f32);
+    for light in lights {
+        let (light_dir, light_color) = light.illuminate(pos);
+        let cos_nl = norm.dot(light_dir);
+        let half = view.add(&light_dir);
+        if cos_nl <= 0.0_f32 || half.length() == 0.0_f32 {
+            continue;
+        }
+        let half = half.normalize();
+        let cos_nh = norm.dot(half).max(0.0_f32);
+        let cos_vh = view.dot(half).max(0.0_f32);
+
+        let fresnel = fresnel_schlick(f0, cos_vh);
+        let k = distribution_ggx(cos_nh, roughness) * geometry_smith(cos_nv, cos_nl, roughness) / (4.0_f32 * cos_nv * cos_nl);
+        let specular = fresnel.mul(k * PI * 255.0_f32);
+        let diffuse = diffuse.mul(&Vector3::from_value(1.0_f32).sub(&fresnel));
+
+        result = result.add(&diffuse.add(&specular).mul(cos_nl).mul(&light_color));
+    }
+
+    result
+}
+
+impl VertexShader for PbrShader {
+    // tex, pos, norm, color, tangent
+    type Varyings = (Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>);
+
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f32>, Self::Varyings) {
+        let pos = uniforms.mat_proj_view_world.mul(&vertex.position.extend(1.0_f32));
+        let pos_w = uniforms.mat_world.mul(&vertex.position.extend(1.0_f32));
+        let norm = uniforms.mat_world.mul(&vertex.normal.extend(0.0_f32)).normalize();
+        let tangent = uniforms.world_tangent(vertex.tangent);
+
+        (pos, (vertex.tex, pos_w.truncate(), norm.truncate(), vertex.color, tangent))
+    }
+}
+
+impl PixelShader for PbrShader {
+    type Varyings = (Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>);
+
+    fn pixel(&self, uniforms: &Uniforms, (tex, pos, norm, color, tangent): Self::Varyings) -> Vector4<f32> {
+        let material = &uniforms.material;
+        let alpha = uniforms.alpha(tex);
+        let (ambient_color, base_color) = uniforms.base_colors(tex, color);
+        let emissive = uniforms.emissive(tex);
+        if material.illumination == 0 {
+            return base_color.add(&emissive).extend(alpha);
+        }
+
+        let view = uniforms.eye_pos.sub(&pos).normalize();
+        let norm = uniforms.normal(tex, norm, tangent);
+        let (metallic, roughness) = uniforms.metallic_roughness(tex);
+        let roughness = roughness.max(MIN_ROUGHNESS);
+        // metals reflect the base color
+        let f0 = Vector3::from_value(DIELECTRIC_F0 * 255.0_f32).mul(1.0_f32 - metallic)
+            .add(&base_color.mul(metallic))
+            .mul(1.0_f32 / 255.0_f32);
+        let direct = pbr_light(&uniforms.lights, pos, view, norm, base_color, f0, metallic, roughness);
+
+        let cos_nv = norm.dot(view).max(0.0001_f32);
+        let specular_k = env_brdf(f0, cos_nv, roughness);
+        let diffuse_k = Vector3::from_value(1.0_f32).sub(&specular_k).mul(1.0_f32 - metallic);
+        let (diffuse_env, specular_env) = match material.texture_cube {
+            Some(ref cube) => {
+                let reflection = norm.mul(2.0_f32 * norm.dot(view)).sub(&view);
+                let lod = roughness * (cube.mip_count() - 1) as f32;
+                (base_color.mul(&cube.irradiance(norm)).mul(1.0_f32 / 255.0_f32), cube.sample(reflection, lod))
+            },
+            None => (ambient_color.mul(uniforms.ambient_intensity),
+                     Vector3::from_value(255.0_f32 * uniforms.ambient_intensity)),
+        };
+        let ambient = diffuse_env.mul(&diffuse_k).add(&specular_env.mul(&specular_k)).mul(uniforms.occlusion(tex));
+
+        ambient.add(&direct).add(&emissive).extend(alpha)
+    }
+
+    fn uses_cubemap(&self) -> bool {
+        true
+    }
+}
@@ -1,90 +1,71 @@
 use cgmath::{Vector, EuclideanVector, Vector2, Vector3, Vector4};
 use shader::base::*;
+use light::Light;
+use mesh::Vertex;
 use std::ops::{Add, Sub, Mul};
 
-impl Shader {
-    pub fn shader_phong_blinn() -> ShadersType {
-        ([Shader::vertex_phong_blinn_color, Shader::vertex_phong_blinn_texture],
-         [Shader::pixel_phong_blinn_color, Shader::pixel_phong_blinn_texture])
-    }
-
-    // out:
-    // 0 - Vector3 view
-    // 3 - Vector3 norm
-    fn vertex_phong_blinn_color(&mut self) -> Vector4<f32> {
-        let pos_pvw = self.matrix_arr[MATRIX_PROJ_VIEW_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS));
-        let pos_w = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS));
-        let norm = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_NORM)).normalize();
-        let view = self.read_vec4(IN_VS_VEC_EYE_POS).sub(&pos_w).normalize();
-
-        self.out_vec3_from4(&view);
-        self.out_vec3_from4(&norm);
-        pos_pvw
-    }
+// diffuse and specular lighting per pixel
+pub struct PhongBlinnShader;
 
-    // in:
-    // 0 - Vector3 view
-    // 3 - Vector3 norm
-    #[allow(dead_code)]
-    fn pixel_phong_blinn_color(&self) -> Vector3<f32> {
-        let view = Vector3::new(self.in_pixel_data[0], self.in_pixel_data[1], self.in_pixel_data[2]).normalize();
-        let norm = Vector3::new(self.in_pixel_data[3], self.in_pixel_data[4], self.in_pixel_data[5]).normalize();
-        let light = self.read_vec3(IN_VS_VEC_NEG_LIGHT);
-        let half = view.add(&light).normalize();
+// sum of diffuse and specular light from all lights
+fn phong_blinn_light(lights: &[Light], pos: Vector3<f32>, view: Vector3<f32>, norm: Vector3<f32>,
+                     shininess: f32) -> (Vector3<f32>, Vector3<f32>) {
+    let mut diffuse = Vector3::new(0.0_f32, 0.0_f32, 0.0_f32);
+    let mut specular = Vector3::new(0.0_f32, 0.0_f32, 0.0_f32);
+    for light in lights {
+        let (light_dir, light_color) = light.illuminate(pos);
+        let half = view.add(&light_dir).normalize();
         let cos_nh = norm.dot(half).max(0.0_f32);
-        let cos_nl = norm.dot(light).max(0.0_f32);
-
-        const POWER: i32 = 5;
+        let cos_nl = norm.dot(light_dir).max(0.0_f32);
+        diffuse = diffuse.add(&light_color.mul(cos_nl));
+        specular = specular.add(&light_color.mul(cos_nh.powf(shininess)));
+    }
 
-        let ambient = self.ambient.mul(self.ambient_intensity);
-        let diffuse = self.diffuse.mul(cos_nl);
-        let specular = self.specular.mul(cos_nh.powi(POWER));
+    (diffuse, specular)
+}
 
-        ambient.add(&diffuse).add(&specular)
-    }
+impl VertexShader for PhongBlinnShader {
+    // tex, view, norm, pos, color, tangent
+    type Varyings = (Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>);
 
-    // out:
-    // 0 - Vector2 tex
-    // 2 - Vector3 view
-    // 5 - Vector3 norm
-    fn vertex_phong_blinn_texture(&mut self) -> Vector4<f32> {
-        let pos_pvw = self.matrix_arr[MATRIX_PROJ_VIEW_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS));
-        let pos_w = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_POS));
-        let mut norm = self.matrix_arr[MATRIX_WORLD].mul(&self.read_vec4(IN_VS_VEC_NORM)).normalize();
-        norm.neg_self();
-        let view = self.read_vec4(IN_VS_VEC_EYE_POS).sub(&pos_w).normalize();
-        let tex = self.read_vec2(IN_VS_VEC_TEX);
+    fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f32>, Self::Varyings) {
+        let pos_pvw = uniforms.mat_proj_view_world.mul(&vertex.position.extend(1.0_f32));
+        let pos_w = uniforms.mat_world.mul(&vertex.position.extend(1.0_f32));
+        let mut norm = uniforms.mat_world.mul(&vertex.normal.extend(0.0_f32)).normalize();
+        let mut tangent = uniforms.world_tangent(vertex.tangent);
+        if uniforms.material.texture.is_some() {
+            // the whole tangent space is flipped with the normal
+            norm.neg_self();
+            tangent.neg_self();
+        }
+        let view = uniforms.eye_pos.extend(1.0_f32).sub(&pos_w).normalize();
 
-        self.out_vec2(&tex);
-        self.out_vec3_from4(&view);
-        self.out_vec3_from4(&norm);
-        pos_pvw
+        (pos_pvw, (vertex.tex, view.truncate(), norm.truncate(), pos_w.truncate(), vertex.color, tangent))
     }
+}
 
-    // in:
-    // 0 - Vector2 tex
-    // 2 - Vector3 view
-    // 5 - Vector3 norm
-    fn pixel_phong_blinn_texture(&self) -> Vector3<f32> {
-        let tex = Vector2::new(self.in_pixel_data[0], self.in_pixel_data[1]);
-        let view = Vector3::new(self.in_pixel_data[2], self.in_pixel_data[3], self.in_pixel_data[4]).normalize();
-        let norm = Vector3::new(self.in_pixel_data[5], self.in_pixel_data[6], self.in_pixel_data[7]).normalize();
-        let light = self.read_vec3(IN_VS_VEC_NEG_LIGHT);
-        let half = view.add(&light).normalize();
-        let cos_nh = norm.dot(half).max(0.0_f32);
-        let cos_nl = norm.dot(light).max(0.0_f32);
-
-        const POWER: i32 = 5;
+impl PixelShader for PhongBlinnShader {
+    type Varyings = (Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>);
 
-        let color = match self.texture {
-            Some(ref t) => t.tex_2d_bilinear(tex),
-            None => panic!("texture is none"),
-        };
+    fn pixel(&self, uniforms: &Uniforms, (tex, view, norm, pos, color, tangent): Self::Varyings) -> Vector4<f32> {
+        let material = &uniforms.material;
+        let alpha = uniforms.alpha(tex);
+        let (ambient_color, diffuse_color) = uniforms.base_colors(tex, color);
+        let emissive = uniforms.emissive(tex);
+        if material.illumination == 0 {
+            return diffuse_color.add(&emissive).extend(alpha);
+        }
 
-        let ambient = color.mul(self.ambient_intensity);
-        let diffuse = color.mul(cos_nl);
-        let specular = self.specular.mul(cos_nh.powi(POWER));
+        let view = view.normalize();
+        let norm = uniforms.normal(tex, norm, tangent);
+        let (diffuse_light, specular_light) = phong_blinn_light(&uniforms.lights, pos, view, norm, material.shininess);
+        let ambient = ambient_color.mul(uniforms.ambient_intensity);
+        let diffuse = diffuse_color.mul(&diffuse_light);
+        let color = ambient.add(&diffuse).add(&emissive);
+        if material.illumination == 1 {
+            return color.extend(alpha);
+        }
 
-        ambient.add(&diffuse).add(&specular)
+        color.add(&uniforms.specular(tex).mul(&specular_light)).extend(alpha)
     }
 }
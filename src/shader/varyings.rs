@@ -0,0 +1,124 @@
+use cgmath::{Vector2, Vector3, Vector4};
+
+// Values which are written by vertex shader and interpolated by the rasterizer for every pixel.
+// Implemented for f32, vectors and tuples of them,
+// own structures can implement it through a tuple.
+pub trait Varyings: Sized {
+    // count of f32 values
+    fn len() -> usize;
+
+    fn write(&self, out: &mut [f32]);
+
+    fn read(data: &[f32]) -> Self;
+}
+
+impl Varyings for () {
+    fn len() -> usize {
+        0
+    }
+
+    fn write(&self, _: &mut [f32]) {
+    }
+
+    fn read(_: &[f32]) -> () {
+        ()
+    }
+}
+
+impl Varyings for f32 {
+    fn len() -> usize {
+        1
+    }
+
+    fn write(&self, out: &mut [f32]) {
+        out[0] = *self;
+    }
+
+    fn read(data: &[f32]) -> f32 {
+        data[0]
+    }
+}
+
+impl Varyings for Vector2<f32> {
+    fn len() -> usize {
+        2
+    }
+
+    fn write(&self, out: &mut [f32]) {
+        out[0] = self.x;
+        out[1] = self.y;
+    }
+
+    fn read(data: &[f32]) -> Vector2<f32> {
+        Vector2::new(data[0], data[1])
+    }
+}
+
+impl Varyings for Vector3<f32> {
+    fn len() -> usize {
+        3
+    }
+
+    fn write(&self, out: &mut [f32]) {
+        out[0] = self.x;
+        out[1] = self.y;
+        out[2] = self.z;
+    }
+
+    fn read(data: &[f32]) -> Vector3<f32> {
+        Vector3::new(data[0], data[1], data[2])
+    }
+}
+
+impl Varyings for Vector4<f32> {
+    fn len() -> usize {
+        4
+    }
+
+    fn write(&self, out: &mut [f32]) {
+        out[0] = self.x;
+        out[1] = self.y;
+        out[2] = self.z;
+        out[3] = self.w;
+    }
+
+    fn read(data: &[f32]) -> Vector4<f32> {
+        Vector4::new(data[0], data[1], data[2], data[3])
+    }
+}
+
+macro_rules! varyings_tuple {
+    ($($name:ident : $ind:tt),+) => {
+        impl<$($name: Varyings),+> Varyings for ($($name,)+) {
+            fn len() -> usize {
+                0 $(+ $name::len())+
+            }
+
+            #[allow(unused_assignments)]
+            fn write(&self, out: &mut [f32]) {
+                let mut offset = 0;
+                $(
+                    self.$ind.write(&mut out[offset..]);
+                    offset += $name::len();
+                )+
+            }
+
+            #[allow(unused_assignments)]
+            fn read(data: &[f32]) -> ($($name,)+) {
+                let mut offset = 0;
+                ($({
+                    let val = $name::read(&data[offset..]);
+                    offset += $name::len();
+                    val
+                },)+)
+            }
+        }
+    }
+}
+
+varyings_tuple!(A: 0);
+varyings_tuple!(A: 0, B: 1);
+varyings_tuple!(A: 0, B: 1, C: 2);
+varyings_tuple!(A: 0, B: 1, C: 2, D: 3);
+varyings_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4);
+varyings_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
@@ -0,0 +1,114 @@
+use cgmath::*;
+use std::ops::{Add, Sub, Mul};
+use framebuffer::FrameBuffer;
+use light::{Light, LightType};
+
+// Depth of the scene from the light's point of view.
+// It is filled by Scene::draw_shadow with the usual Model::draw and sampled by Light::illuminate.
+#[derive(Clone)]
+pub struct ShadowMap {
+    pub framebuffer: FrameBuffer,  // only zbuffer is used
+    pub mat_light: Matrix4<f32>,   // world space -> clip space of the light
+    pub bias: f32,                 // offset to the light in world units, against self-shadowing
+    pub pcf_radius: i32,           // (2 * pcf_radius + 1)^2 samples
+    pub center: Vector3<f32>,      // directional light: shadow is cast
+    pub radius: f32,               // only inside the sphere (center, radius)
+    pub near: f32,                 // spot light: shadow is cast
+    pub far: f32,                  // only between near and far planes
+}
+
+impl ShadowMap {
+    pub fn new(size: u32) -> ShadowMap {
+        ShadowMap {
+            framebuffer: FrameBuffer::new(size, size),
+            mat_light: Matrix4::<f32>::zero(),
+            bias: 0.05_f32,
+            pcf_radius: 1,
+            center: Vector3::<f32>::zero(),
+            radius: 1.0_f32,
+            near: 0.1_f32,
+            far: 10.0_f32,
+        }
+    }
+
+    pub fn with_bias(mut self, bias: f32) -> ShadowMap {
+        self.bias = bias;
+
+        self
+    }
+
+    pub fn with_pcf(mut self, radius: i32) -> ShadowMap {
+        self.pcf_radius = radius.max(0);
+
+        self
+    }
+
+    // for directional light
+    pub fn with_bounds(mut self, center: Vector3<f32>, radius: f32) -> ShadowMap {
+        self.center = center;
+        self.radius = radius;
+
+        self
+    }
+
+    // for spot light
+    pub fn with_range(mut self, near: f32, far: f32) -> ShadowMap {
+        self.near = near;
+        self.far = far;
+
+        self
+    }
+
+    pub fn update_matrix(&mut self, light: &Light) {
+        let dir = light.direction;
+        let up = if dir.x.abs() < 0.9_f32 && dir.z.abs() < 0.9_f32 {
+            Vector3::new(0.0_f32, 0.0_f32, 1.0_f32)
+        } else {
+            Vector3::new(0.0_f32, 1.0_f32, 0.0_f32)
+        };
+        self.mat_light = match light.light_type {
+            LightType::Directional => {
+                let eye = self.center.sub(&dir.mul(self.radius));
+                let r = self.radius;
+                ortho(-r, r, -r, r, 0.0_f32, 2.0_f32 * r).mul(&Matrix4::look_at(
+                    Point3::from_vec(eye), Point3::from_vec(self.center), up))
+            },
+            LightType::Spot => {
+                let fovy = rad((2.0_f32 * light.cos_outer.acos()).min(3.0_f32));
+                perspective(fovy, 1.0_f32, self.near, self.far).mul(&Matrix4::look_at(
+                    Point3::from_vec(light.position), Point3::from_vec(light.position.add(&dir)), up))
+            },
+            LightType::Point => panic!("shadow is not supported for point light"),
+        };
+    }
+
+    // part of the light which reaches the point, [0; 1], to_light - normalized direction to the light
+    pub fn visibility(&self, pos: Vector3<f32>, to_light: Vector3<f32>) -> f32 {
+        let pos = pos.add(&to_light.mul(self.bias));
+        let p = self.mat_light.mul(Vector4::new(pos.x, pos.y, pos.z, 1.0_f32));
+        if p.w <= 0.0_f32 {
+            return 1.0_f32;
+        }
+        let inverse_w = 1.0_f32 / p.w;
+        let x_size = self.framebuffer.x_size as i32;
+        let y_size = self.framebuffer.y_size as i32;
+        // the same transformation as in Mesh::draw_triangle
+        let x = ((p.x * inverse_w + 1.0_f32) * x_size as f32 * 0.5_f32).floor() as i32;
+        let y = ((p.y * inverse_w + 1.0_f32) * y_size as f32 * 0.5_f32).floor() as i32;
+        let depth = 0.5_f32 - p.z * inverse_w * 0.5_f32;
+
+        let mut cnt_lit = 0;
+        let mut cnt = 0;
+        for sy in (y - self.pcf_radius)..(y + self.pcf_radius + 1) {
+            for sx in (x - self.pcf_radius)..(x + self.pcf_radius + 1) {
+                cnt += 1;
+                if sx < 0 || sy < 0 || sx >= x_size || sy >= y_size ||
+                    self.framebuffer.zbuffer[(sy * x_size + sx) as usize] <= depth {
+                    cnt_lit += 1;
+                }
+            }
+        }
+
+        cnt_lit as f32 / cnt as f32
+    }
+}
@@ -1,21 +1,40 @@
+#[cfg(test)]
+mod common {
+    use cgmath::{Vector3, EuclideanVector};
+    use std::ops::Sub;
+
+    pub fn assert_near(a: Vector3<f32>, b: Vector3<f32>, epsilon: f32) {
+        assert!(a.sub(b).length() < epsilon, "{:?} != {:?}", a, b);
+    }
+
+    pub fn white() -> Vector3<f32> {
+        Vector3::new(1.0_f32, 1.0_f32, 1.0_f32)
+    }
+}
+
 
 #[cfg(test)]
 mod rasterization {
-    use cgmath::{Point2, Point3};
+    use cgmath::{Point2, Vector4, Matrix4};
     use rasterization::triangle;
+    use framebuffer::FrameBuffer;
+    use generator;
+    use material::Material;
+    use tiler::Tiler;
+    use clipping::ClipMode;
     use shader::{Shader, ShaderType, MAX_OUT_VALUES};
 
     fn triangle_test(a_screen: Point2<f32>, b_screen: Point2<f32>, c_screen: Point2<f32>, buffer_except: Vec<u32>) {
-        let a = Point3::new(a_screen.x, a_screen.y, 0.5_f32);
-        let b = Point3::new(b_screen.x, b_screen.y, 0.5_f32);
-        let c = Point3::new(c_screen.x, c_screen.y, 0.5_f32);
+        let a = Vector4::new(a_screen.x, a_screen.y, 0.5_f32, 0.5_f32);
+        let b = Vector4::new(b_screen.x, b_screen.y, 0.5_f32, 0.5_f32);
+        let c = Vector4::new(c_screen.x, c_screen.y, 0.5_f32, 0.5_f32);
         let x_size: usize = 7;
         let y_size: usize = 5;
-        let mut cbuffer: Vec<u32> = vec![0; x_size * y_size];
-        let mut zbuffer: Vec<f32> = vec![0.0_f32; x_size * y_size];
+        let mut framebuffer = FrameBuffer::new(x_size as u32, y_size as u32);
         let mut shader = Shader::new(ShaderType::Default);
         shader.vertex_out_len = 3;
-        triangle(&mut cbuffer, &mut zbuffer, x_size, y_size, [a, b, c], [[0.0_f32;MAX_OUT_VALUES]; 3], &mut shader);
+        triangle(&mut framebuffer.as_tile(), [a, b, c], [[0.0_f32;MAX_OUT_VALUES]; 3], &mut shader);
+        let cbuffer = framebuffer.cbuffer;
 
         println!("");
         println!("real: ");
@@ -45,6 +64,25 @@ mod rasterization {
         }
     }
 
+    // every row is computed from the top of the triangle, so tiles give the same bits as the whole framebuffer
+    #[test]
+    fn tiles_start_from_own_rows() {
+        let points = [Vector4::new(0.7_f32, 0.2_f32, 0.9_f32, 1.0_f32),
+                      Vector4::new(6.6_f32, 2.3_f32, 0.1_f32, 0.5_f32),
+                      Vector4::new(2.3_f32, 4.8_f32, 0.5_f32, 0.25_f32)];
+        let mut shader = Shader::new(ShaderType::Default);
+        let mut whole = FrameBuffer::new(7, 5);
+        let mut tiled = whole.clone();
+        triangle(&mut whole.as_tile(), points, [[0.0_f32; MAX_OUT_VALUES]; 3], &mut shader);
+        for mut tile in tiled.tiles(1) {
+            triangle(&mut tile, points, [[0.0_f32; MAX_OUT_VALUES]; 3], &mut shader);
+        }
+
+        assert_eq!(whole.cbuffer, tiled.cbuffer);
+        assert!(whole.cbuffer.iter().filter(|c| **c != 0).count() > 10);
+        assert!(whole.zbuffer == tiled.zbuffer);
+    }
+
     #[test]
     fn triangle_00() {
         triangle_test(Point2::new(1.0,4.0), Point2::new(2.0,1.0), Point2::new(6.0,3.0),
@@ -234,4 +272,2198 @@ mod rasterization {
                            0,0,0,0,0,0,0,
                            0,0,0,0,0,0,0]);
     }
+
+    #[test]
+    fn tiler_empty_framebuffer() {
+        // a framebuffer without rows or columns has no tiles, primitives are dropped
+        let model = generator::generate_plane(Material::new()).unwrap();
+        let mut shader = Shader::new(ShaderType::Default);
+        shader.uniforms.mat_proj_view_world = Matrix4::from_scale(1.0_f32);
+        for &(x_size, y_size) in &[(0, 0), (4, 0), (0, 4)] {
+            let mut framebuffer = FrameBuffer::new(x_size, y_size);
+            let mut tiler = Tiler::new(2);
+            model.draw(&mut shader, &mut framebuffer, &mut tiler, ClipMode::Frustum);
+            tiler.add_line([Vector4::new(0.0_f32, 0.0_f32, 0.5_f32, 1.0_f32),
+                            Vector4::new(3.0_f32, 3.0_f32, 0.5_f32, 1.0_f32)], 0xFFFFFF);
+            tiler.add_point(Vector4::new(1.0_f32, 1.0_f32, 0.5_f32, 1.0_f32), 0xFFFFFF);
+            tiler.flush(&mut framebuffer);
+            assert!(framebuffer.cbuffer.is_empty());
+        }
+    }
+}
+
+#[cfg(test)]
+mod imagefile {
+    use std;
+    use std::path::Path;
+    use imagefile::{ImageFormat, save, encode_png, encode_ppm, encode_tga};
+
+    // 2x2, rows from bottom to top
+    const PIXELS: [u32; 4] = [0xFF0000, 0x00FF00, 0x0000FF, 0xFFFFFF];
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(ImageFormat::from_path(Path::new("a/b.PNG")), Ok(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path(Path::new("b.ppm")), Ok(ImageFormat::Ppm));
+        assert_eq!(ImageFormat::from_path(Path::new("b.tga")), Ok(ImageFormat::Tga));
+        assert!(ImageFormat::from_path(Path::new("b.bmp")).is_err());
+        assert!(ImageFormat::from_path(Path::new("b")).is_err());
+    }
+
+    #[test]
+    fn ppm_is_flipped() {
+        let data = encode_ppm(2, 2, &PIXELS);
+        let header = b"P6\n2 2\n255\n";
+        assert_eq!(&data[..header.len()], &header[..]);
+        assert_eq!(&data[header.len()..], &[0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 255, 0][..]);
+    }
+
+    #[test]
+    fn tga_size_limit() {
+        let path = std::env::temp_dir().join("software_render_wide.tga");
+        let _ = std::fs::remove_file(&path);
+        let pixels = vec![0_u32; 0x10000];
+        assert!(save(&path, 0x10000, 1, &pixels).is_err());
+        assert!(save(&path, 1, 0x10000, &pixels).is_err());
+        assert!(!path.exists());
+        assert!(save(&path, 0xFFFF, 1, &pixels[1..]).is_ok());
+    }
+
+    #[test]
+    fn tga_is_bottom_up_bgr() {
+        let data = encode_tga(2, 2, &PIXELS);
+        assert_eq!(data.len(), 18 + 12);
+        assert_eq!(&data[12..18], &[2, 0, 2, 0, 24, 0][..]);
+        assert_eq!(&data[18..], &[0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255][..]);
+    }
+
+    #[test]
+    fn png_chunks() {
+        let data = encode_png(2, 2, &PIXELS);
+        assert_eq!(&data[..8], &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A][..]);
+        assert_eq!(&data[12..16], b"IHDR");
+        // IEND chunk with well-known crc
+        assert_eq!(&data[data.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82][..]);
+    }
+}
+
+#[cfg(test)]
+mod clipping {
+    use cgmath::Vector4;
+    use clipping::{ClipMode, ClipVertex, clip_triangle};
+
+    fn vertex(x: f32, y: f32, z: f32, w: f32) -> ClipVertex {
+        let mut v = ClipVertex::new();
+        v.position = Vector4::new(x, y, z, w);
+        v.data[0] = z;
+        v
+    }
+
+    fn clip(triangle: [ClipVertex; 3], mode: ClipMode) -> Vec<ClipVertex> {
+        let mut polygon = Vec::new();
+        let mut tmp = Vec::new();
+        clip_triangle(&triangle, 1, mode, &mut polygon, &mut tmp);
+        polygon
+    }
+
+    #[test]
+    fn inside() {
+        let polygon = clip([vertex(0.0, 0.0, 0.0, 1.0), vertex(1.0, 0.0, 0.0, 1.0), vertex(0.0, 1.0, 0.0, 1.0)], ClipMode::Frustum);
+        assert_eq!(polygon.len(), 3);
+        assert_eq!(polygon[1].position, Vector4::new(1.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn behind_camera() {
+        let polygon = clip([vertex(0.0, 0.0, -2.0, -1.0), vertex(1.0, 0.0, -2.0, -1.0), vertex(0.0, 1.0, -3.0, -2.0)], ClipMode::Near);
+        assert!(polygon.is_empty());
+    }
+
+    #[test]
+    fn cross_near() {
+        // one vertex before the near plane: triangle becomes quad
+        let polygon = clip([vertex(0.0, 0.0, -3.0, 1.0), vertex(1.0, 0.0, 0.0, 1.0), vertex(0.0, 1.0, 0.0, 1.0)], ClipMode::Near);
+        assert_eq!(polygon.len(), 4);
+        for v in &polygon {
+            assert!(v.position.z + v.position.w >= -0.00001);
+            assert_eq!(v.data[0], v.position.z);
+        }
+    }
+
+    #[test]
+    fn side_planes_only_in_frustum_mode() {
+        let triangle = [vertex(-3.0, 0.0, 0.0, 1.0), vertex(0.5, 0.0, 0.0, 1.0), vertex(0.5, 0.5, 0.0, 1.0)];
+        assert_eq!(clip(triangle, ClipMode::Near).len(), 3);
+        let polygon = clip(triangle, ClipMode::Frustum);
+        assert_eq!(polygon.len(), 4);
+        for v in &polygon {
+            assert!(v.position.x >= -1.00001);
+        }
+    }
+}
+
+#[cfg(test)]
+mod halfspace {
+    use cgmath::Vector4;
+    use halfspace::triangle;
+    use framebuffer::FrameBuffer;
+    use shader::{Shader, ShaderType, MAX_OUT_VALUES};
+
+    const X_SIZE: usize = 16;
+    const Y_SIZE: usize = 12;
+
+    // number of triangles covering every pixel, coverage is taken from zbuffer
+    fn coverage(triangles: &[[(f32, f32); 3]]) -> Vec<u32> {
+        let mut result = vec![0_u32; X_SIZE * Y_SIZE];
+        let mut shader = Shader::new(ShaderType::Default);
+        shader.vertex_out_len = 3;
+        for t in triangles {
+            let points = [Vector4::new(t[0].0, t[0].1, 0.5_f32, 0.5_f32),
+                          Vector4::new(t[1].0, t[1].1, 0.5_f32, 0.5_f32),
+                          Vector4::new(t[2].0, t[2].1, 0.5_f32, 0.5_f32)];
+            let mut framebuffer = FrameBuffer::new(X_SIZE as u32, Y_SIZE as u32);
+            triangle(&mut framebuffer.as_tile(), points, [[0.0_f32; MAX_OUT_VALUES]; 3], &mut shader);
+            for (cnt, z) in result.iter_mut().zip(framebuffer.zbuffer.iter()) {
+                if *z != 0.0_f32 {
+                    *cnt += 1;
+                }
+            }
+        }
+
+        result
+    }
+
+    fn check_exactly_once(triangles: &[[(f32, f32); 3]], cnt_expected: usize) {
+        let result = coverage(triangles);
+        for (ind, cnt) in result.iter().enumerate() {
+            assert!(*cnt <= 1, "pixel x = {}, y = {} is covered {} times", ind % X_SIZE, ind / X_SIZE, cnt);
+        }
+        assert_eq!(result.iter().filter(|cnt| **cnt == 1).count(), cnt_expected);
+    }
+
+    #[test]
+    fn mask() {
+        // the same triangle as rasterization::triangle_00, y-axis is directed up
+        let result = coverage(&[[(1.0, 4.0), (2.0, 1.0), (6.0, 3.0)]]);
+        let expected = [(1, 3), (2, 3), (1, 2), (2, 2), (3, 2), (4, 2), (2, 1)];
+        for y in 0..Y_SIZE {
+            for x in 0..X_SIZE {
+                let is_expected = expected.contains(&(x, y));
+                assert!((result[y * X_SIZE + x] == 1) == is_expected, "x = {}, y = {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn winding_does_not_matter() {
+        let ccw = coverage(&[[(1.3, 0.7), (9.1, 2.2), (4.6, 8.9)]]);
+        let cw = coverage(&[[(1.3, 0.7), (4.6, 8.9), (9.1, 2.2)]]);
+        assert!(ccw == cw);
+        assert!(ccw.iter().any(|cnt| *cnt != 0));
+    }
+
+    #[test]
+    fn degenerate() {
+        check_exactly_once(&[[(1.0, 1.0), (5.0, 5.0), (9.0, 9.0)]], 0);
+    }
+
+    #[test]
+    fn quad_edges_through_pixel_centers() {
+        // all edges go through pixel centers: 8x6 pixels
+        let (a, b, c, d) = ((2.5, 2.5), (10.5, 2.5), (10.5, 8.5), (2.5, 8.5));
+        check_exactly_once(&[[a, b, c], [a, c, d]], 8 * 6);
+        check_exactly_once(&[[a, b, d], [b, c, d]], 8 * 6);
+    }
+
+    #[test]
+    fn fan_around_pixel_center() {
+        // shared vertex at pixel center and shared edges at odd angles
+        let center = (7.5, 5.5);
+        let ring = [(1.5, 0.5), (7.5, 0.25), (14.75, 1.5), (15.0, 5.5), (13.5, 11.5),
+                    (7.5, 11.0), (0.5, 11.5), (0.25, 5.5)];
+        let mut triangles = Vec::new();
+        for i in 0..ring.len() {
+            triangles.push([center, ring[i], ring[(i + 1) % ring.len()]]);
+        }
+        let whole = coverage(&triangles);
+        let cnt_covered = whole.iter().filter(|cnt| **cnt != 0).count();
+        assert!(cnt_covered > 100);
+        check_exactly_once(&triangles, cnt_covered);
+    }
+
+    #[test]
+    fn subpixel_triangle_between_centers() {
+        // does not contain any pixel center
+        check_exactly_once(&[[(3.6, 3.6), (4.4, 3.6), (4.4, 4.4)]], 0);
+    }
+}
+
+#[cfg(test)]
+mod line {
+    use cgmath::*;
+    use line;
+    use framebuffer::FrameBuffer;
+    use generator;
+    use material::Material;
+    use scene::Scene;
+    use shader::{Shader, ShaderType};
+    use tiler::PolygonMode;
+
+    const X_SIZE: usize = 8;
+    const Y_SIZE: usize = 6;
+
+    fn p(x: f32, y: f32) -> Vector4<f32> {
+        Vector4::new(x, y, 0.5_f32, 1.0_f32)
+    }
+
+    // rows from the top, 1 - the pixel is drawn
+    fn pixels(framebuffer: &FrameBuffer) -> Vec<String> {
+        (0..Y_SIZE).rev().map(|y| {
+            (0..X_SIZE).map(|x| if framebuffer.cbuffer[y * X_SIZE + x] == 0 { '0' } else { '1' }).collect()
+        }).collect()
+    }
+
+    fn bresenham(a: Vector4<f32>, b: Vector4<f32>) -> Vec<String> {
+        let mut framebuffer = FrameBuffer::new(X_SIZE as u32, Y_SIZE as u32);
+        line::bresenham(&mut framebuffer.as_tile(), a, b, 0xFFFFFF);
+        pixels(&framebuffer)
+    }
+
+    #[test]
+    fn bresenham_octants() {
+        let expected = vec!["00000000",
+                            "00000000",
+                            "00000011",
+                            "00011100",
+                            "01100000",
+                            "00000000"];
+        assert_eq!(bresenham(p(1.5_f32, 1.5_f32), p(7.5_f32, 3.5_f32)), expected);
+        assert_eq!(bresenham(p(7.5_f32, 3.5_f32), p(1.5_f32, 1.5_f32)), expected);
+        let expected = vec!["00001000",
+                            "00001000",
+                            "00010000",
+                            "00010000",
+                            "00100000",
+                            "00100000"];
+        assert_eq!(bresenham(p(2.5_f32, 0.5_f32), p(4.5_f32, 5.5_f32)), expected);
+        // the part out of the framebuffer is clipped
+        let expected = vec!["00000000",
+                            "00000000",
+                            "00000000",
+                            "11111111",
+                            "00000000",
+                            "00000000"];
+        assert_eq!(bresenham(p(-100.0_f32, 2.5_f32), p(100.0_f32, 2.5_f32)), expected);
+    }
+
+    #[test]
+    fn tiles_do_not_change_lines() {
+        let lines = [(p(0.2_f32, 0.3_f32), p(7.9_f32, 5.7_f32)), (p(6.1_f32, 0.0_f32), p(1.3_f32, 6.0_f32)),
+                     (p(-3.0_f32, -1.0_f32), p(11.0_f32, 4.0_f32))];
+        for &(a, b) in &lines {
+            let mut whole = FrameBuffer::new(X_SIZE as u32, Y_SIZE as u32);
+            let mut tiled = whole.clone();
+            line::bresenham(&mut whole.as_tile(), a, b, 0xFFFFFF);
+            line::wu(&mut whole.as_tile(), b, a, 0x00FF00);
+            for mut tile in tiled.tiles(1) {
+                line::bresenham(&mut tile, a, b, 0xFFFFFF);
+                line::wu(&mut tile, b, a, 0x00FF00);
+            }
+            assert_eq!(whole.cbuffer, tiled.cbuffer);
+        }
+    }
+
+    #[test]
+    fn wu_coverage() {
+        // the line between two rows of pixel centers covers both rows by half
+        let mut framebuffer = FrameBuffer::new(X_SIZE as u32, Y_SIZE as u32);
+        line::wu(&mut framebuffer.as_tile(), p(0.5_f32, 3.0_f32), p(7.5_f32, 3.0_f32), 0xFEFEFE);
+        for x in 0..X_SIZE {
+            assert_eq!(framebuffer.cbuffer[2 * X_SIZE + x], 0x7F7F7F);
+            assert_eq!(framebuffer.cbuffer[3 * X_SIZE + x], 0x7F7F7F);
+        }
+        assert_eq!(framebuffer.cbuffer.iter().filter(|c| **c != 0).count(), 2 * X_SIZE);
+    }
+
+    #[test]
+    fn depth_test() {
+        let mut framebuffer = FrameBuffer::new(X_SIZE as u32, Y_SIZE as u32);
+        for ind in 0..X_SIZE {
+            framebuffer.zbuffer[Y_SIZE / 2 * X_SIZE + ind] = if ind < X_SIZE / 2 { 0.25_f32 } else { 0.75_f32 };
+        }
+        line::bresenham(&mut framebuffer.as_tile(), p(0.5_f32, 3.5_f32), p(7.5_f32, 3.5_f32), 0xFFFFFF);
+        line::point(&mut framebuffer.as_tile(), p(1.5_f32, 1.5_f32), 3, 0xFFFFFF);
+
+        assert_eq!(pixels(&framebuffer), vec!["00000000",
+                                              "00000000",
+                                              "11110000",
+                                              "11100000",
+                                              "11100000",
+                                              "11100000"]);
+        // the depth is not written
+        assert_eq!(framebuffer.zbuffer[0], 0.0_f32);
+    }
+
+    fn new_scene() -> Scene {
+        let mut scene = Scene::headless(32, 32);
+        scene.proj(deg(60.0_f32), 0.1_f32, 10.0_f32)
+            .view(Point3::new(0.0_f32, 0.0_f32, -2.0_f32), Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                  Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+            .wire_color(0xFF0000)
+            .threads(2);
+        scene.start(0);
+        scene
+    }
+
+    fn count(scene: &Scene, color: u32) -> usize {
+        scene.framebuffer().cbuffer.iter().filter(|c| **c == color).count()
+    }
+
+    #[test]
+    fn polygon_modes() {
+        let model = generator::generate_plane(Material::new()).unwrap();
+        let mut shader = Shader::new(ShaderType::Default);
+        let mut counts = Vec::<(usize, usize)>::new();
+        for mode in &[PolygonMode::Fill, PolygonMode::Line, PolygonMode::Point, PolygonMode::FillLine] {
+            let mut scene = new_scene();
+            scene.polygon_mode(*mode).draw(&model, Matrix4::from_scale(1.0_f32), &mut shader);
+            counts.push((count(&scene, 0xFFFFFF), count(&scene, 0xFF0000)));
+            if *mode != PolygonMode::Fill && *mode != PolygonMode::FillLine {
+                assert!(scene.framebuffer().zbuffer.iter().all(|d| *d == 0.0_f32));
+            }
+        }
+
+        let (fill, _) = counts[0];
+        assert!(fill > 0 && counts[0].1 == 0);
+        // the outline and the diagonal
+        assert!(counts[1].0 == 0 && counts[1].1 > 0 && counts[1].1 < fill);
+        // 3x3 at 4 corners
+        assert_eq!(counts[2], (0, 4 * 9));
+        assert_eq!(counts[3].0 + counts[3].1, fill);
+        assert!(counts[3].1 > 0);
+    }
+
+    #[test]
+    fn debug_lines() {
+        let model = generator::generate_plane(Material::new()).unwrap();
+        let mut shader = Shader::new(ShaderType::Default);
+        let mut scene = new_scene();
+        scene.draw(&model, Matrix4::from_scale(1.0_f32), &mut shader);
+        let fill = count(&scene, 0xFFFFFF);
+
+        // the line behind the plane is visible only out of it
+        scene.draw_line(Point3::new(-2.0_f32, 0.0_f32, 1.0_f32), Point3::new(2.0_f32, 0.0_f32, 1.0_f32), 0x00FF00);
+        let behind = count(&scene, 0x00FF00);
+        assert!(behind > 0 && behind < 32);
+        assert_eq!(count(&scene, 0xFFFFFF), fill);
+        scene.draw_line(Point3::new(-2.0_f32, 0.1_f32, -1.0_f32), Point3::new(2.0_f32, 0.1_f32, -1.0_f32), 0x0000FF);
+        assert_eq!(count(&scene, 0x0000FF), 32);
+
+        // the bounding box of the flat plane is its outline, the normal goes to z
+        let mut scene = new_scene();
+        scene.draw_bounds(&model, Matrix4::from_scale(1.0_f32), 0x00FF00);
+        assert!(count(&scene, 0x00FF00) > 0);
+        scene.draw_normals(&model, Matrix4::from_scale(1.0_f32), 0.0_f32, 0x0000FF);
+        assert_eq!(count(&scene, 0x0000FF), 4);
+    }
+}
+
+#[cfg(test)]
+mod antialiasing {
+    use std;
+    use cgmath::*;
+    use std::ops::Mul;
+    use framebuffer::{FrameBuffer, Antialiasing};
+    use generator;
+    use imagefile;
+    use material::Material;
+    use mesh::Model;
+    use scene::Scene;
+    use shader::{Shader, ShaderType};
+
+    #[test]
+    fn resolve() {
+        // 2x1 pixels with 2 samples
+        let mut framebuffer = FrameBuffer::multisample(2, 1, 2);
+        framebuffer.cbuffer.copy_from_slice(&[0xFF0000, 0x0000FF, 0x102030, 0x102030]);
+        framebuffer.zbuffer.copy_from_slice(&[0.25_f32, 0.5_f32, 0.0_f32, 0.0_f32]);
+        let mut frame = FrameBuffer::new(2, 1);
+        framebuffer.resolve(&mut frame);
+        assert_eq!(frame.cbuffer, vec![0x800080, 0x102030]);
+        assert_eq!(frame.zbuffer, vec![0.5_f32, 0.0_f32]);
+
+        // supersampling 2x2 -> 1x1
+        let mut framebuffer = FrameBuffer::new(2, 2);
+        framebuffer.cbuffer.copy_from_slice(&[0xFFFFFF, 0x000000, 0x000000, 0x000000]);
+        let mut frame = FrameBuffer::new(1, 1);
+        framebuffer.resolve(&mut frame);
+        assert_eq!(frame.cbuffer, vec![0x404040]);
+    }
+
+    #[test]
+    fn save_multisample() {
+        // samples are resolved before saving
+        let mut framebuffer = FrameBuffer::multisample(2, 1, 4);
+        framebuffer.cbuffer.copy_from_slice(&[0xFFFFFF, 0xFFFFFF, 0, 0, 0x102030, 0x102030, 0x102030, 0x102030]);
+        framebuffer.zbuffer.copy_from_slice(&[0.5_f32, 0.0_f32, 0.0_f32, 0.0_f32, 0.25_f32, 0.25_f32, 0.25_f32, 0.25_f32]);
+        let path = std::env::temp_dir().join("software_render_multisample.ppm");
+        framebuffer.save_color(&path).unwrap();
+        assert_eq!(imagefile::load_ppm(&path).unwrap(), (2, 1, vec![0x808080, 0x102030]));
+
+        let path = std::env::temp_dir().join("software_render_multisample_depth.ppm");
+        framebuffer.save_depth(&path).unwrap();
+        assert_eq!(imagefile::load_ppm(&path).unwrap(), (2, 1, vec![0xFFFFFF, 0x202020]));
+    }
+
+    fn render(mode: Antialiasing, model: &Model, scale: f32) -> FrameBuffer {
+        let mut shader = Shader::new(ShaderType::Default);
+        let mut scene = Scene::headless(40, 40);
+        scene.proj(deg(60.0_f32), 0.1_f32, 10.0_f32)
+            .view(Point3::new(0.0_f32, 0.0_f32, -2.0_f32), Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                  Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+            .antialiasing(mode)
+            .threads(3);
+        scene.start(0);
+        let mat_world = Matrix4::from(Matrix3::from_angle_z(rad(0.2_f32))).mul(&Matrix4::from_scale(scale));
+        scene.draw(model, mat_world, &mut shader)
+            .resolve();
+
+        scene.framebuffer().clone()
+    }
+
+    #[test]
+    fn edges() {
+        let model = generator::generate_plane(Material::new()).unwrap();
+        let aliased = render(Antialiasing::None, &model, 1.0_f32);
+        let area = aliased.cbuffer.iter().filter(|c| **c == 0xFFFFFF).count() as f32;
+        for mode in &[Antialiasing::Msaa(2), Antialiasing::Msaa(4), Antialiasing::Msaa(8), Antialiasing::Ssaa(3)] {
+            let frame = render(*mode, &model, 1.0_f32);
+            assert_eq!((frame.x_size, frame.y_size), (40, 40));
+            // edges are grey, the area is about the same
+            let mut partial = 0;
+            let mut coverage = 0.0_f32;
+            for color in &frame.cbuffer {
+                let grey = color & 0xFF;
+                assert_eq!(*color, grey * 0x010101);
+                if grey != 0 && grey != 0xFF {
+                    partial += 1;
+                }
+                coverage += grey as f32 / 255.0_f32;
+            }
+            assert!(partial > 20, "{:?}", mode);
+            assert!((coverage - area).abs() < area * 0.05_f32, "{:?}: {} {}", mode, coverage, area);
+        }
+    }
+
+    #[test]
+    fn watertight() {
+        // shared edges of triangles and borders of tiles do not leave uncovered samples
+        let plane = generator::generate_plane(Material::new()).unwrap();
+        let sphere = generator::generate_sphere(32, Material::new()).unwrap();
+        for mode in &[Antialiasing::Msaa(2), Antialiasing::Msaa(4), Antialiasing::Msaa(8), Antialiasing::Ssaa(2)] {
+            let frame = render(*mode, &plane, 4.0_f32);
+            assert!(frame.cbuffer.iter().all(|c| *c == 0xFFFFFF), "{:?}", mode);
+
+            // pixels inside the silhouette of the sphere are fully covered
+            let frame = render(*mode, &sphere, 0.8_f32);
+            let x_size = frame.x_size;
+            let is_inside = |x: usize, y: usize| (y - 1..y + 2)
+                .all(|ny| (x - 1..x + 2).all(|nx| frame.cbuffer[ny * x_size + nx] != 0));
+            for y in 1..frame.y_size - 1 {
+                for x in 1..x_size - 1 {
+                    if is_inside(x, y) {
+                        assert_eq!(frame.cbuffer[y * x_size + x], 0xFFFFFF, "{:?} at {} {}", mode, x, y);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod texture {
+    use cgmath::*;
+    use imagefile;
+    use std::ops::Sub;
+    use texture::{Texture, TextureCube};
+    use tests::common::assert_near;
+
+    // faces of one color, 8x8 pixels
+    fn solid_cube(colors: [u32; 6]) -> TextureCube {
+        let face = |color: u32| {
+            let png = imagefile::encode_png(8, 8, &[color; 64]);
+            Texture::from_memory(&png, "face").unwrap()
+        };
+        TextureCube::from_textures([face(colors[0]), face(colors[1]), face(colors[2]),
+                                    face(colors[3]), face(colors[4]), face(colors[5])])
+    }
+
+    #[test]
+    fn cube_faces() {
+        let cube = solid_cube([0x010000, 0x020000, 0x030000, 0x040000, 0x050000, 0x060000]);
+        let dirs = [(1.0_f32, 0.1_f32, 0.2_f32), (-1.0_f32, 0.3_f32, -0.2_f32), (0.2_f32, 1.0_f32, 0.1_f32),
+                    (-0.2_f32, -1.0_f32, 0.5_f32), (0.3_f32, 0.4_f32, 1.0_f32), (0.1_f32, -0.1_f32, -1.0_f32)];
+        for (ind, d) in dirs.iter().enumerate() {
+            let dir = Vector3::new(d.0, d.1, d.2);
+            assert_eq!(TextureCube::face_coord(dir).0, ind);
+            assert_eq!(cube.sample(dir, 0.0_f32), Vector3::new((ind + 1) as f32, 0.0_f32, 0.0_f32));
+            assert_eq!(cube.sample(dir, 1.5_f32), Vector3::new((ind + 1) as f32, 0.0_f32, 0.0_f32));
+        }
+        assert_eq!(cube.mip_count(), 4);
+    }
+
+    #[test]
+    fn irradiance() {
+        let cube = solid_cube([0x6496C8; 6]);
+        for n in &[(1.0_f32, 0.0_f32, 0.0_f32), (0.0_f32, -1.0_f32, 0.0_f32), (0.5_f32, 0.5_f32, -0.7_f32)] {
+            assert_near(cube.irradiance(Vector3::new(n.0, n.1, n.2)), Vector3::new(100.0_f32, 150.0_f32, 200.0_f32), 0.5_f32);
+        }
+
+        // the light from the upper face only
+        let cube = solid_cube([0x000000, 0x000000, 0xC8C8C8, 0x000000, 0x000000, 0x000000]);
+        let up = cube.irradiance(Vector3::new(0.0_f32, 1.0_f32, 0.0_f32));
+        let down = cube.irradiance(Vector3::new(0.0_f32, -1.0_f32, 0.0_f32));
+        assert!(up.x > 100.0_f32 && down.x < 10.0_f32, "up = {:?}, down = {:?}", up, down);
+    }
+}
+
+#[cfg(test)]
+mod light {
+    use cgmath::*;
+    use light::Light;
+    use tests::common::white;
+
+    #[test]
+    fn directional() {
+        let light = Light::directional(Vector3::new(0.0_f32, -2.0_f32, 0.0_f32), white(), 0.5_f32);
+        let (dir, color) = light.illuminate(Vector3::new(10.0_f32, 3.0_f32, -7.0_f32));
+        assert_eq!(dir, Vector3::new(0.0_f32, 1.0_f32, 0.0_f32));
+        assert_eq!(color, Vector3::new(0.5_f32, 0.5_f32, 0.5_f32));
+    }
+
+    #[test]
+    fn point_attenuation() {
+        let light = Light::point(Vector3::new(0.0_f32, 0.0_f32, 2.0_f32), white(), 2.0_f32)
+            .with_attenuation(1.0_f32, 0.5_f32, 0.25_f32);
+        let (dir, color) = light.illuminate(Vector3::new(0.0_f32, 0.0_f32, 0.0_f32));
+        assert_eq!(dir, Vector3::new(0.0_f32, 0.0_f32, 1.0_f32));
+        // 2 / (1 + 0.5 * 2 + 0.25 * 4)
+        assert!((color.x - 2.0_f32 / 3.0_f32).abs() < 0.00001_f32);
+    }
+
+    #[test]
+    fn spot_cone() {
+        let light = Light::spot(Vector3::new(0.0_f32, 0.0_f32, 0.0_f32), Vector3::new(0.0_f32, 0.0_f32, -1.0_f32),
+                                deg(10.0_f32), deg(20.0_f32), white(), 1.0_f32);
+        let at_angle = |angle: f32| {
+            let angle = rad(angle.to_radians());
+            light.illuminate(Vector3::new(angle.sin(), 0.0_f32, -angle.cos())).1.x
+        };
+        assert_eq!(at_angle(0.0_f32), 1.0_f32);
+        assert_eq!(at_angle(9.0_f32), 1.0_f32);
+        let middle = at_angle(15.0_f32);
+        assert!(middle > 0.0_f32 && middle < 1.0_f32);
+        assert_eq!(at_angle(21.0_f32), 0.0_f32);
+        assert_eq!(at_angle(90.0_f32), 0.0_f32);
+    }
+}
+
+#[cfg(test)]
+mod shadow {
+    use cgmath::*;
+    use generator;
+    use light::Light;
+    use tiler::Tiler;
+    use shadow::ShadowMap;
+    use material::Material;
+    use clipping::ClipMode;
+    use shader::{Shader, ShaderType};
+    use tests::common::white;
+
+    // unit sphere in the center
+    fn draw_sphere(light: &Light, map: &mut ShadowMap) {
+        map.update_matrix(light);
+        let model = generator::generate_sphere(20, Material::new()).unwrap();
+        let mut shader = Shader::new(ShaderType::Default);
+        shader.uniforms.mat_proj_view_world = map.mat_light;
+        model.draw(&mut shader, &mut map.framebuffer, &mut Tiler::new(1), ClipMode::Frustum);
+    }
+
+    fn up() -> Vector3<f32> {
+        Vector3::new(0.0_f32, 1.0_f32, 0.0_f32)
+    }
+
+    #[test]
+    fn directional() {
+        let light = Light::directional(Vector3::new(0.0_f32, -1.0_f32, 0.0_f32), white(), 1.0_f32);
+        let mut map = ShadowMap::new(64).with_bounds(Vector3::new(0.0_f32, 0.0_f32, 0.0_f32), 2.0_f32).with_pcf(0);
+        draw_sphere(&light, &mut map);
+
+        assert_eq!(map.visibility(Vector3::new(0.0_f32, -1.5_f32, 0.0_f32), up()), 0.0_f32);
+        assert_eq!(map.visibility(Vector3::new(0.3_f32, -1.5_f32, -0.4_f32), up()), 0.0_f32);
+        assert_eq!(map.visibility(Vector3::new(1.5_f32, -1.5_f32, 0.0_f32), up()), 1.0_f32);
+        // lit side of the sphere does not shadow itself
+        assert_eq!(map.visibility(Vector3::new(0.0_f32, 1.0_f32, 0.0_f32), up()), 1.0_f32);
+        assert_eq!(map.visibility(Vector3::new(0.6_f32, 0.8_f32, 0.0_f32), up()), 1.0_f32);
+    }
+
+    // the alpha test of the material discards the occluder in the depth pass as in the color pass
+    #[test]
+    fn alpha_cutout() {
+        // the light looks at the plane from -z, the side which is not culled
+        let light = Light::directional(Vector3::new(0.0_f32, 0.0_f32, 1.0_f32), white(), 1.0_f32);
+        let back = Vector3::new(0.0_f32, 0.0_f32, -1.0_f32);
+        for &(alpha, expected) in &[(0.25_f32, 1.0_f32), (0.75_f32, 0.0_f32)] {
+            let mut material = Material::new();
+            material.alpha = alpha;
+            material.alpha_cutoff = Some(0.5_f32);
+            let model = generator::generate_plane(material).unwrap();
+
+            let mut map = ShadowMap::new(64).with_bounds(Vector3::new(0.0_f32, 0.0_f32, 0.0_f32), 2.0_f32).with_pcf(0);
+            map.update_matrix(&light);
+            let mut shader = Shader::new(ShaderType::Depth);
+            shader.uniforms.mat_proj_view_world = map.mat_light;
+            model.draw_opaque(&mut shader, &mut map.framebuffer, &mut Tiler::new(1), ClipMode::Frustum);
+
+            assert_eq!(map.visibility(Vector3::new(0.1_f32, 0.2_f32, 1.0_f32), back), expected, "alpha = {}", alpha);
+        }
+    }
+
+    #[test]
+    fn pcf_soft_edge() {
+        let light = Light::directional(Vector3::new(0.0_f32, -1.0_f32, 0.0_f32), white(), 1.0_f32);
+        let mut map = ShadowMap::new(64).with_bounds(Vector3::new(0.0_f32, 0.0_f32, 0.0_f32), 2.0_f32).with_pcf(2);
+        draw_sphere(&light, &mut map);
+
+        let edge = map.visibility(Vector3::new(1.0_f32, -1.5_f32, 0.0_f32), up());
+        assert!(edge > 0.0_f32 && edge < 1.0_f32, "visibility = {}", edge);
+    }
+
+    #[test]
+    fn spot() {
+        let light = Light::spot(Vector3::new(0.0_f32, 3.0_f32, 0.0_f32), Vector3::new(0.0_f32, -1.0_f32, 0.0_f32),
+                                deg(30.0_f32), deg(40.0_f32), white(), 1.0_f32)
+            .with_attenuation(1.0_f32, 0.0_f32, 0.0_f32);
+        let mut map = ShadowMap::new(64).with_range(0.5_f32, 10.0_f32).with_pcf(0);
+        draw_sphere(&light, &mut map);
+
+        assert_eq!(map.visibility(Vector3::new(0.0_f32, -2.0_f32, 0.0_f32), up()), 0.0_f32);
+        assert_eq!(map.visibility(Vector3::new(2.0_f32, -2.0_f32, 0.0_f32), up()), 1.0_f32);
+        assert_eq!(map.visibility(Vector3::new(0.0_f32, 1.0_f32, 0.0_f32), up()), 1.0_f32);
+    }
+
+    #[test]
+    fn illuminate() {
+        let light = Light::directional(Vector3::new(0.0_f32, -1.0_f32, 0.0_f32), white(), 1.0_f32);
+        let mut map = ShadowMap::new(64).with_bounds(Vector3::new(0.0_f32, 0.0_f32, 0.0_f32), 2.0_f32);
+        draw_sphere(&light, &mut map);
+        let light = light.with_shadow(map);
+
+        assert_eq!(light.illuminate(Vector3::new(0.0_f32, -1.5_f32, 0.0_f32)).1, Vector3::new(0.0_f32, 0.0_f32, 0.0_f32));
+        assert_eq!(light.illuminate(Vector3::new(1.8_f32, -1.5_f32, 0.0_f32)).1, white());
+    }
+}
+
+#[cfg(test)]
+mod shader {
+    use std;
+    use cgmath::*;
+    use texture;
+    use imagefile;
+    use generator;
+    use light::Light;
+    use scene::Scene;
+    use material::Material;
+    use mesh::Vertex;
+    use std::ops::{Sub, Mul};
+    use shader::*;
+    use tests::common::assert_near;
+
+    // own varyings are stored through a tuple
+    #[derive(Debug, PartialEq)]
+    struct Surface {
+        height: f32,
+        norm: Vector3<f32>,
+    }
+
+    impl Varyings for Surface {
+        fn len() -> usize {
+            <(f32, Vector3<f32>)>::len()
+        }
+
+        fn write(&self, out: &mut [f32]) {
+            (self.height, self.norm).write(out)
+        }
+
+        fn read(data: &[f32]) -> Surface {
+            let (height, norm) = <(f32, Vector3<f32>)>::read(data);
+            Surface { height: height, norm: norm }
+        }
+    }
+
+    // red at the top, blue at the bottom of the object
+    struct HeightShader {
+        scale: f32,
+    }
+
+    impl VertexShader for HeightShader {
+        type Varyings = Surface;
+
+        fn vertex(&self, uniforms: &Uniforms, vertex: &Vertex) -> (Vector4<f32>, Surface) {
+            let pos = uniforms.mat_proj_view_world.mul(vertex.position.extend(1.0_f32));
+            let pos_w = uniforms.mat_world.mul(vertex.position.extend(1.0_f32));
+
+            (pos, Surface { height: pos_w.y * self.scale, norm: vertex.normal })
+        }
+    }
+
+    impl PixelShader for HeightShader {
+        type Varyings = Surface;
+
+        fn pixel(&self, _: &Uniforms, varyings: Surface) -> Vector4<f32> {
+            let t = (varyings.height + 0.5_f32).max(0.0_f32).min(1.0_f32);
+            Vector4::new(255.0_f32 * t, 0.0_f32, 255.0_f32 * (1.0_f32 - t), 1.0_f32)
+        }
+    }
+
+    #[test]
+    fn varyings_round_trip() {
+        let val = (1.0_f32, Vector2::new(2.0_f32, 3.0_f32), Vector3::new(4.0_f32, 5.0_f32, 6.0_f32),
+                   Vector4::new(7.0_f32, 8.0_f32, 9.0_f32, 10.0_f32));
+        let mut data = [0.0_f32; MAX_OUT_VALUES];
+        val.write(&mut data);
+
+        assert_eq!(<(f32, Vector2<f32>, Vector3<f32>, Vector4<f32>)>::len(), 10);
+        assert_eq!(&data[..11], &[1.0_f32, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 0.0]);
+        assert!(<(f32, Vector2<f32>, Vector3<f32>, Vector4<f32>)>::read(&data) == val);
+    }
+
+    #[test]
+    fn struct_varyings() {
+        let val = Surface { height: 0.5_f32, norm: Vector3::new(0.0_f32, 1.0_f32, 0.0_f32) };
+        let mut data = [0.0_f32; 4];
+        val.write(&mut data);
+
+        assert_eq!(Surface::len(), 4);
+        assert_eq!(Surface::read(&data), val);
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_varyings() {
+        struct Big;
+        impl VertexShader for Big {
+            type Varyings = (Vector4<f32>, Vector4<f32>, Vector4<f32>, Vector4<f32>, Vector4<f32>, Vector4<f32>);
+            fn vertex(&self, _: &Uniforms, _: &Vertex) -> (Vector4<f32>, Self::Varyings) {
+                unimplemented!()
+            }
+        }
+        impl PixelShader for Big {
+            type Varyings = (Vector4<f32>, Vector4<f32>, Vector4<f32>, Vector4<f32>, Vector4<f32>, Vector4<f32>);
+            fn pixel(&self, _: &Uniforms, _: Self::Varyings) -> Vector4<f32> {
+                unimplemented!()
+            }
+        }
+
+        Shader::with_shaders(Big, Big);
+    }
+
+    #[test]
+    fn illumination_model() {
+        let mut shader = Shader::new(ShaderType::PhongBlinn);
+        shader.uniforms.lights.push(Light::directional(Vector3::new(0.0_f32, 0.0_f32, -1.0_f32),
+                                                       Vector3::new(1.0_f32, 1.0_f32, 1.0_f32), 1.0_f32));
+        shader.uniforms.material.diffuse = Vector3::new(100.0_f32, 0.0_f32, 0.0_f32);
+        shader.uniforms.material.specular = Vector3::new(0.0_f32, 50.0_f32, 0.0_f32);
+        shader.uniforms.material.emissive = Vector3::new(0.0_f32, 0.0_f32, 10.0_f32);
+        // tex, view, norm, pos, color, tangent: the light, the normal and the eye are on one line
+        let varyings = (Vector2::new(0.0_f32, 0.0_f32), Vector3::new(0.0_f32, 0.0_f32, 1.0_f32),
+                        Vector3::new(0.0_f32, 0.0_f32, 1.0_f32), Vector3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                        Vector3::new(51.0_f32, 255.0_f32, 255.0_f32), Vector4::new(1.0_f32, 0.0_f32, 0.0_f32, 1.0_f32));
+        varyings.write(&mut shader.in_pixel_data);
+        shader.vertex_out2_base = <(Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>)>::len();
+
+        shader.uniforms.material.illumination = 0;
+        assert_eq!(shader.pixel(), Vector4::new(100.0_f32, 0.0_f32, 10.0_f32, 1.0_f32));
+        shader.uniforms.material.illumination = 1;
+        assert_eq!(shader.pixel(), Vector4::new(100.0_f32, 0.0_f32, 10.0_f32, 1.0_f32));
+        shader.uniforms.material.illumination = 2;
+        assert_eq!(shader.pixel(), Vector4::new(100.0_f32, 50.0_f32, 10.0_f32, 1.0_f32));
+        // the vertex color multiplies the diffuse color only
+        shader.uniforms.material.vertex_color = true;
+        assert!(shader.pixel().truncate().sub(Vector3::new(20.0_f32, 50.0_f32, 10.0_f32)).length() < 0.001_f32);
+        // alpha of the material is passed to blending
+        shader.uniforms.material.alpha = 0.25_f32;
+        assert_eq!(shader.pixel().w, 0.25_f32);
+    }
+
+    #[test]
+    fn cook_torrance_shininess() {
+        // the highlight is brighter and narrower with a bigger specular exponent,
+        // without the exponent the roughness is 0.3 as for Ns = 2 / 0.09 - 2
+        fn specular(shininess: Option<f32>, view: Vector3<f32>) -> f32 {
+            let mut shader = Shader::new(ShaderType::CookTorrance);
+            shader.uniforms.lights.push(Light::directional(Vector3::new(0.0_f32, 0.0_f32, -1.0_f32),
+                                                           Vector3::new(1.0_f32, 1.0_f32, 1.0_f32), 1.0_f32));
+            shader.uniforms.material.diffuse = Vector3::new(0.0_f32, 0.0_f32, 0.0_f32);
+            shader.uniforms.material.specular = Vector3::new(100.0_f32, 100.0_f32, 100.0_f32);
+            if let Some(shininess) = shininess {
+                shader.uniforms.material.shininess = shininess;
+                shader.uniforms.material.shininess_given = true;
+            }
+            shader.uniforms.material.illumination = 2;
+            shader.uniforms.ambient_intensity = 0.0_f32;
+            // tex, view, norm, pos, color, tangent
+            let varyings = (Vector2::new(0.0_f32, 0.0_f32), view,
+                            Vector3::new(0.0_f32, 0.0_f32, 1.0_f32), Vector3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                            Vector3::new(255.0_f32, 255.0_f32, 255.0_f32), Vector4::new(1.0_f32, 0.0_f32, 0.0_f32, 1.0_f32));
+            varyings.write(&mut shader.in_pixel_data);
+            shader.vertex_out2_base = <(Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>)>::len();
+
+            shader.pixel().x
+        }
+
+        let mirror = Vector3::new(0.0_f32, 0.0_f32, 1.0_f32);
+        let aside = Vector3::new(0.5_f32, 0.0_f32, 0.866_f32);
+        assert!(specular(Some(200.0_f32), mirror) > specular(Some(5.0_f32), mirror));
+        assert!(specular(Some(200.0_f32), aside) < specular(Some(5.0_f32), aside));
+        let ns = 2.0_f32 / 0.09_f32 - 2.0_f32;
+        assert!((specular(None, mirror) - specular(Some(ns), mirror)).abs() < 0.01_f32);
+        assert!((specular(None, aside) - specular(Some(ns), aside)).abs() < 0.01_f32);
+    }
+
+    #[test]
+    fn emissive_map() {
+        // the emissive map multiplies the emissive color in every lit shader
+        let lambert_len = <(Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>)>::len();
+        let phong_len = <(Vector2<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector3<f32>, Vector4<f32>)>::len();
+        let png = imagefile::encode_png(1, 1, &[0x000033]);
+        let texture = texture::Texture::from_memory(&png, "emissive").unwrap().get_surface(0);
+        for (name, shader_type, len) in vec![("lambert", ShaderType::Lambert, lambert_len),
+                                             ("phong_blinn", ShaderType::PhongBlinn, phong_len),
+                                             ("cook_torrance", ShaderType::CookTorrance, phong_len),
+                                             ("pbr", ShaderType::Pbr, lambert_len)] {
+            let mut shader = Shader::new(shader_type);
+            shader.uniforms.material.diffuse = Vector3::new(100.0_f32, 0.0_f32, 0.0_f32);
+            shader.uniforms.material.emissive = Vector3::new(0.0_f32, 0.0_f32, 100.0_f32);
+            shader.uniforms.material.illumination = 0;
+            shader.uniforms.texture_emissive = Some(texture.clone());
+            shader.vertex_out2_base = len;
+
+            let color = shader.pixel();
+            assert!(color.sub(Vector4::new(100.0_f32, 0.0_f32, 20.0_f32, 1.0_f32)).length() < 0.001_f32, "{}: {:?}", name, color);
+        }
+    }
+
+    #[test]
+    fn normal_map() {
+        // one texel of the normal map: {r, g, b} -> {x, y, z} in [-1; 1]
+        fn bump(color: u32) -> Option<std::sync::Arc<texture::Surface>> {
+            let png = imagefile::encode_png(1, 1, &[color]);
+            Some(texture::Texture::from_memory(&png, "bump").unwrap().get_surface(0))
+        }
+
+        let tex = Vector2::new(0.5_f32, 0.5_f32);
+        let norm = Vector3::new(0.0_f32, 0.0_f32, 2.0_f32);
+        let tangent = Vector4::new(1.0_f32, 0.0_f32, 0.0_f32, 1.0_f32);
+        let mut uniforms = Uniforms::new();
+        assert_eq!(uniforms.normal(tex, norm, tangent), Vector3::unit_z());
+        // back faces of double-sided meshes are lit from the other side
+        uniforms.front_facing = false;
+        assert_eq!(uniforms.normal(tex, norm, tangent), Vector3::unit_z().mul(-1.0_f32));
+        uniforms.front_facing = true;
+
+        uniforms.texture_bump = bump(0x8080FF);
+        assert_near(uniforms.normal(tex, norm, tangent), Vector3::unit_z(), 0.01_f32);
+        uniforms.texture_bump = bump(0xFF8080);
+        assert_near(uniforms.normal(tex, norm, tangent), Vector3::unit_x(), 0.01_f32);
+        // the bitangent goes along cross(normal, tangent) * w
+        uniforms.texture_bump = bump(0x80FF80);
+        assert_near(uniforms.normal(tex, norm, tangent), Vector3::unit_y(), 0.01_f32);
+        let mirrored = Vector4::new(1.0_f32, 0.0_f32, 0.0_f32, -1.0_f32);
+        assert_near(uniforms.normal(tex, norm, mirrored), Vector3::new(0.0_f32, -1.0_f32, 0.0_f32), 0.01_f32);
+        // without the tangent the normal map can't be used
+        assert_eq!(uniforms.normal(tex, norm, Vector4::zero()), Vector3::unit_z());
+
+        uniforms.texture_bump = bump(0xC080C0);
+        uniforms.material.bump_scale = 0.0_f32;
+        assert_near(uniforms.normal(tex, norm, tangent), Vector3::unit_z(), 0.01_f32);
+    }
+
+    #[test]
+    fn custom_shader() {
+        let model = generator::generate_sphere(20, Material::new()).unwrap();
+        let mut shader = Shader::with_shaders(HeightShader { scale: 1.0_f32 }, HeightShader { scale: 1.0_f32 });
+        let mut scene = Scene::headless(32, 32);
+        scene.proj(deg(60.0_f32), 0.1_f32, 10.0_f32)
+            .view(Point3::new(0.0_f32, 0.0_f32, -2.5_f32), Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                  Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+            .threads(1);
+        scene.start(0);
+        scene.draw(&model, Matrix4::from_scale(1.0_f32), &mut shader);
+
+        // y of the framebuffer goes up
+        let framebuffer = scene.framebuffer();
+        let top = framebuffer.cbuffer[26 * 32 + 16];
+        let bottom = framebuffer.cbuffer[5 * 32 + 16];
+        assert_eq!(framebuffer.cbuffer[0], 0);
+        assert!((top >> 16) > 0xC0 && (top & 0xFF) < 0x40, "top = {:x}", top);
+        assert!((bottom >> 16) < 0x40 && (bottom & 0xFF) > 0xC0, "bottom = {:x}", bottom);
+    }
+}
+
+#[cfg(test)]
+mod blend {
+    use cgmath::*;
+    use blend::{blend, BlendMode};
+    use generator;
+    use material::Material;
+    use mesh::Model;
+    use scene::Scene;
+    use shader::{Shader, ShaderType};
+
+    #[test]
+    fn blend_modes() {
+        let src = Vector4::new(200.0_f32, 100.0_f32, 0.0_f32, 0.5_f32);
+        let dst = 0x408020;
+        assert_eq!(blend(BlendMode::Opaque, src, dst), 0xC86400);
+        assert_eq!(blend(BlendMode::Alpha, src, dst), 0x847210);
+        assert_eq!(blend(BlendMode::Additive, src, dst), 0xA4B220);
+        assert_eq!(blend(BlendMode::Multiply, src, dst), 0x395910);
+        assert_eq!(blend(BlendMode::Premultiplied, src, dst), 0xE8A410);
+        // the result is clamped, alpha too
+        assert_eq!(blend(BlendMode::Additive, src.truncate().extend(2.0_f32), 0xFFFFFF), 0xFFFFFF);
+        assert_eq!(blend(BlendMode::Alpha, src.truncate().extend(-1.0_f32), dst), dst);
+    }
+
+    // unlit plane 1x1, facing the camera at z = -2
+    fn plane(color: u32, alpha: f32) -> Model {
+        let mut material = Material::new();
+        material.illumination = 0;
+        material.diffuse = Vector3::new((color >> 16) as f32, ((color >> 8) & 0xFF) as f32, (color & 0xFF) as f32);
+        material.alpha = alpha;
+        if alpha < 1.0_f32 {
+            material.blend = BlendMode::Alpha;
+            material.render_state.depth_write = false;
+        }
+        generator::generate_plane(material).unwrap()
+    }
+
+    // color and depth at the center of the framebuffer, models are drawn in the given order
+    fn render(models: &[(&Model, f32)]) -> (u32, f32) {
+        let mut shader = Shader::new(ShaderType::Lambert);
+        let mut scene = Scene::headless(8, 8);
+        scene.proj(deg(60.0_f32), 0.1_f32, 10.0_f32)
+            .view(Point3::new(0.0_f32, 0.0_f32, -2.0_f32), Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                  Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+            .threads(1);
+        scene.start(0);
+        for &(model, z) in models {
+            scene.draw(model, Matrix4::from_translation(Vector3::new(0.0_f32, 0.0_f32, z)), &mut shader);
+        }
+        scene.flush_transparent();
+        let framebuffer = scene.framebuffer();
+        let ind = 4 * 8 + 4;
+
+        (framebuffer.cbuffer[ind], framebuffer.zbuffer[ind])
+    }
+
+    #[test]
+    fn transparent_pass() {
+        let red = plane(0xFF0000, 1.0_f32);
+        let green = plane(0x00FF00, 0.5_f32);
+        let blue = plane(0x0000FF, 0.5_f32);
+        let (opaque_color, opaque_depth) = render(&[(&red, 0.5_f32)]);
+        assert_eq!(opaque_color, 0xFF0000);
+
+        // transparent planes are drawn after the opaque one and from back to front,
+        // they do not write depth
+        let expected = 0x3F3F7F;
+        assert_eq!(render(&[(&red, 0.5_f32), (&green, 0.0_f32), (&blue, -0.5_f32)]), (expected, opaque_depth));
+        assert_eq!(render(&[(&blue, -0.5_f32), (&green, 0.0_f32), (&red, 0.5_f32)]), (expected, opaque_depth));
+        assert_eq!(render(&[(&green, 0.0_f32), (&red, 0.5_f32), (&blue, -0.5_f32)]), (expected, opaque_depth));
+        // the opaque plane in front hides transparent ones
+        assert_eq!(render(&[(&green, 0.0_f32), (&red, -1.0_f32)]).0, 0xFF0000);
+    }
+
+    #[test]
+    fn transparent_triangles() {
+        // one mesh of two planes by vertex colors: the near blue one is the first in the index buffer,
+        // triangles are sorted as separate meshes would be
+        let mut both = plane(0xFFFFFF, 0.5_f32);
+        both.material_list[0].vertex_color = true;
+        let far = both.vertex_buffer.clone();
+        for v in &mut both.vertex_buffer {
+            v.position.z = -0.5_f32;
+            v.color = Vector3::new(0.0_f32, 0.0_f32, 255.0_f32);
+        }
+        for mut v in far {
+            v.color = Vector3::new(0.0_f32, 255.0_f32, 0.0_f32);
+            both.vertex_buffer.push(v);
+        }
+        let far_indices: Vec<u32> = both.mesh_list[0].index_buffer.iter().map(|ind| ind + 4).collect();
+        both.mesh_list[0].index_buffer.extend(far_indices);
+
+        let green = plane(0x00FF00, 0.5_f32);
+        let blue = plane(0x0000FF, 0.5_f32);
+        let expected = render(&[(&green, 0.0_f32), (&blue, -0.5_f32)]);
+        assert_eq!(expected.0, 0x003F7F);
+        assert_eq!(render(&[(&both, 0.0_f32)]), expected);
+    }
+
+    #[test]
+    fn alpha_test() {
+        let red = plane(0xFF0000, 1.0_f32);
+        let mut cutout = plane(0x00FF00, 1.0_f32);
+        cutout.material_list[0].alpha_cutoff = Some(0.5_f32);
+        let (_, red_depth) = render(&[(&red, 0.5_f32)]);
+
+        // the discarded pixel does not write depth, the order of draws does not matter
+        cutout.material_list[0].alpha = 0.25_f32;
+        assert_eq!(render(&[(&cutout, 0.0_f32), (&red, 0.5_f32)]), (0xFF0000, red_depth));
+        cutout.material_list[0].alpha = 0.75_f32;
+        let (color, depth) = render(&[(&cutout, 0.0_f32), (&red, 0.5_f32)]);
+        assert!(color == 0x00FF00 && depth > red_depth);
+    }
+}
+
+#[cfg(test)]
+mod renderstate {
+    use cgmath::*;
+    use renderstate::*;
+    use generator;
+    use material::Material;
+    use scene::Scene;
+    use shader::{Shader, ShaderType};
+
+    #[test]
+    fn cull_modes() {
+        let mut state = RenderState::new();
+        assert_eq!((state.face(1.0_f32), state.face(-1.0_f32)), (Some(true), None));
+        state.front_face = Winding::Clockwise;
+        assert_eq!((state.face(1.0_f32), state.face(-1.0_f32)), (None, Some(true)));
+        state.cull = CullMode::Front;
+        assert_eq!((state.face(1.0_f32), state.face(-1.0_f32)), (Some(false), None));
+        let state = RenderState::double_sided();
+        assert_eq!((state.face(1.0_f32), state.face(-1.0_f32)), (Some(true), Some(false)));
+    }
+
+    #[test]
+    fn depth_funcs() {
+        // the nearer pixel has the bigger depth
+        let near = 0.75_f32;
+        let far = 0.25_f32;
+        assert!(DepthFunc::Less.test(near, far) && !DepthFunc::Less.test(far, far));
+        assert!(DepthFunc::LessEqual.test(far, far) && !DepthFunc::LessEqual.test(far, near));
+        assert!(DepthFunc::Greater.test(far, near) && !DepthFunc::Greater.test(near, near));
+        assert!(DepthFunc::GreaterEqual.test(near, near) && !DepthFunc::GreaterEqual.test(near, far));
+        assert!(DepthFunc::Equal.test(near, near) && !DepthFunc::Equal.test(near, far));
+        assert!(DepthFunc::Always.test(far, near));
+    }
+
+    #[test]
+    fn color_mask() {
+        let mut state = RenderState::new();
+        assert_eq!(state.mask_color(0x123456, 0xABCDEF), 0x123456);
+        state.color_mask = 0xFF00FF;
+        assert_eq!(state.mask_color(0x123456, 0xABCDEF), 0x12CD56);
+        state.color_mask = 0;
+        assert_eq!(state.mask_color(0x123456, 0xABCDEF), 0xABCDEF);
+    }
+
+    // color and depth at the center of the unlit plane, eye_z = -2 looks at its front face
+    fn render(state: RenderState, eye_z: f32) -> (u32, f32) {
+        let mut material = Material::new();
+        material.illumination = 0;
+        material.diffuse = Vector3::new(255.0_f32, 0.0_f32, 0.0_f32);
+        material.render_state = state;
+        let model = generator::generate_plane(material).unwrap();
+        let mut shader = Shader::new(ShaderType::Lambert);
+        let mut scene = Scene::headless(8, 8);
+        scene.proj(deg(60.0_f32), 0.1_f32, 10.0_f32)
+            .view(Point3::new(0.0_f32, 0.0_f32, eye_z), Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                  Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+            .threads(1);
+        scene.start(0x0000FF);
+        scene.draw(&model, Matrix4::from_scale(1.0_f32), &mut shader);
+        let framebuffer = scene.framebuffer();
+        let ind = 4 * 8 + 4;
+
+        (framebuffer.cbuffer[ind], framebuffer.zbuffer[ind])
+    }
+
+    #[test]
+    fn draw_states() {
+        let (color, depth) = render(RenderState::new(), -2.0_f32);
+        assert!(color == 0xFF0000 && depth > 0.0_f32);
+        assert_eq!(render(RenderState::new(), 2.0_f32), (0x0000FF, 0.0_f32));
+        assert_eq!(render(RenderState::double_sided(), 2.0_f32), (color, depth));
+        let mut state = RenderState::new();
+        state.cull = CullMode::Front;
+        assert_eq!(render(state, 2.0_f32), (color, depth));
+        state.front_face = Winding::Clockwise;
+        assert_eq!(render(state, -2.0_f32), (color, depth));
+
+        let mut state = RenderState::new();
+        state.depth_write = false;
+        state.color_mask = 0x00FF00;
+        assert_eq!(render(state, -2.0_f32), (0x0000FF, 0.0_f32));
+        // nothing is farther than the cleared depth
+        state.depth_func = DepthFunc::Greater;
+        state.color_mask = COLOR_MASK_ALL;
+        assert_eq!(render(state, -2.0_f32).0, 0x0000FF);
+        state.depth_func = DepthFunc::Always;
+        assert_eq!(render(state, -2.0_f32), (color, 0.0_f32));
+    }
+}
+
+#[cfg(test)]
+mod camera {
+    use cgmath::*;
+    use std::ops::Mul;
+    use camera::Camera;
+    use scene::Scene;
+    use tests::common::assert_near;
+
+    const EPSILON: f32 = 0.0001_f32;
+
+    fn camera() -> Camera {
+        Camera::perspective(deg(90.0_f32), 2.0_f32, 0.1_f32, 10.0_f32)
+            .with_look_at(Point3::new(0.0_f32, 0.0_f32, 2.0_f32), Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                          Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+    }
+
+    #[test]
+    fn view_matrix_is_look_at() {
+        let camera = camera();
+        let expected = Matrix4::look_at(Point3::new(0.0_f32, 0.0_f32, 2.0_f32), Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                                        Vector3::new(0.0_f32, 1.0_f32, 0.0_f32));
+        assert!(camera.view_matrix() == expected);
+    }
+
+    #[test]
+    fn unproject() {
+        let camera = camera();
+        let point = Vector4::new(0.3_f32, -0.2_f32, -1.0_f32, 1.0_f32);
+        let p = camera.proj_view_matrix().mul(point);
+        let ndc = p.truncate().mul(1.0_f32 / p.w);
+        assert_near(camera.unproject(ndc).to_vec(), point.truncate(), EPSILON);
+    }
+
+    #[test]
+    fn perspective_ray() {
+        let camera = camera();
+        let (start, dir) = camera.ray(0.0_f32, 0.0_f32);
+        assert_near(start.to_vec(), Vector3::new(0.0_f32, 0.0_f32, 1.9_f32), EPSILON);
+        assert_near(dir, Vector3::new(0.0_f32, 0.0_f32, -1.0_f32), EPSILON);
+
+        // fovy = 90, aspect = 2: the corner is at 45 degrees vertically and atan(2) horizontally
+        let (_, dir) = camera.ray(1.0_f32, 1.0_f32);
+        assert_near(dir, Vector3::new(2.0_f32, 1.0_f32, -1.0_f32).normalize(), EPSILON);
+    }
+
+    #[test]
+    fn orthographic_ray() {
+        let camera = Camera::orthographic(1.5_f32, 2.0_f32, 0.1_f32, 10.0_f32)
+            .with_look_at(Point3::new(0.0_f32, 5.0_f32, 0.0_f32), Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                          Vector3::new(0.0_f32, 0.0_f32, -1.0_f32));
+        let (center, dir_center) = camera.ray(0.0_f32, 0.0_f32);
+        let (corner, dir_corner) = camera.ray(1.0_f32, 1.0_f32);
+        assert_near(dir_center, Vector3::new(0.0_f32, -1.0_f32, 0.0_f32), EPSILON);
+        assert_near(dir_corner, dir_center, EPSILON);
+        assert_near(center.to_vec(), Vector3::new(0.0_f32, 4.9_f32, 0.0_f32), EPSILON);
+        assert_near(corner.to_vec(), Vector3::new(3.0_f32, 4.9_f32, -1.5_f32), EPSILON);
+    }
+
+    #[test]
+    fn empty_framebuffer_aspect() {
+        // the camera of an empty frame gets the aspect 1 instead of NaN
+        for &(x_size, y_size) in &[(0, 0), (4, 0), (0, 4)] {
+            assert_eq!(Scene::headless(x_size, y_size).get_camera().aspect, 1.0_f32);
+        }
+        assert_eq!(Scene::headless(4, 2).get_camera().aspect, 2.0_f32);
+    }
+
+    #[test]
+    fn frustum() {
+        let frustum = camera().frustum();
+        assert!(frustum.contains_point(Point3::new(0.0_f32, 0.0_f32, 0.0_f32)));
+        assert!(frustum.contains_point(Point3::new(2.5_f32, 1.0_f32, 0.5_f32)));
+        assert!(!frustum.contains_point(Point3::new(0.0_f32, 2.5_f32, 0.0_f32)));
+        assert!(!frustum.contains_point(Point3::new(0.0_f32, 0.0_f32, 2.5_f32)));
+        assert!(!frustum.contains_point(Point3::new(0.0_f32, 0.0_f32, -8.5_f32)));
+
+        assert!(frustum.intersects_sphere(Point3::new(0.0_f32, 2.5_f32, 0.0_f32), 1.0_f32));
+        assert!(!frustum.intersects_sphere(Point3::new(0.0_f32, 5.0_f32, 0.0_f32), 1.0_f32));
+    }
+}
+
+#[cfg(test)]
+mod mesh {
+    use cgmath::*;
+    use std::ops::{Sub, Mul};
+    use mesh::{Model, Mesh, Vertex};
+    use tests::common::assert_near;
+
+    // the unit cube with 8 shared vertices, every face is split into 2 triangles
+    fn cube() -> Model {
+        let mut model = Model::new();
+        for ind in 0..8 {
+            let position = Vector3::new((ind & 1) as f32, ((ind >> 1) & 1) as f32, (ind >> 2) as f32);
+            model.vertex_buffer.push(Vertex::new(&position, &Vector2::zero(), &Vector3::zero()));
+        }
+        let mut mesh = Mesh::new();
+        mesh.index_buffer = vec![0, 2, 3, 0, 3, 1, 4, 5, 7, 4, 7, 6, 0, 1, 5, 0, 5, 4,
+                                 2, 6, 7, 2, 7, 3, 0, 4, 6, 0, 6, 2, 1, 3, 7, 1, 7, 5];
+        model.mesh_list.push(mesh);
+        model
+    }
+
+    // a quad in the plane z = 0 from 2 triangles with shared vertices
+    fn quad(tex: [(f32, f32); 4]) -> Model {
+        let mut model = Model::new();
+        for (ind, t) in tex.iter().enumerate() {
+            let position = Vector3::new((ind & 1) as f32, (ind >> 1) as f32, 0.0_f32);
+            model.vertex_buffer.push(Vertex::new(&position, &Vector2::new(t.0, t.1), &Vector3::unit_z()));
+        }
+        let mut mesh = Mesh::new();
+        mesh.index_buffer = vec![0, 1, 3, 0, 3, 2];
+        model.mesh_list.push(mesh);
+        model
+    }
+
+    #[test]
+    fn smooth_normals() {
+        // triangulation of faces does not change normals with the angle weighting
+        let mut model = cube();
+        model.calc_smooth_normals(deg(180.0_f32));
+        assert_eq!(model.vertex_buffer.len(), 8);
+        for v in &model.vertex_buffer {
+            let expected = v.position.mul(2.0_f32).sub(&Vector3::from_value(1.0_f32)).normalize();
+            assert_near(v.normal, expected, 0.00001_f32);
+        }
+    }
+
+    #[test]
+    fn crease_angle() {
+        let mut model = cube();
+        model.calc_smooth_normals(deg(60.0_f32));
+        assert_eq!(model.vertex_buffer.len(), 24);
+        for indexes in model.mesh_list[0].index_buffer.chunks(3) {
+            let v: Vec<&Vertex> = indexes.iter().map(|ind| &model.vertex_buffer[*ind as usize]).collect();
+            let face = v[1].position.sub(&v[0].position).cross(v[2].position.sub(&v[0].position)).normalize();
+            for vertex in v {
+                assert_near(vertex.normal, face, 0.00001_f32);
+            }
+        }
+    }
+
+    #[test]
+    fn flat_normals() {
+        let mut model = cube();
+        model.calc_flat_normals();
+        assert_eq!(model.vertex_buffer.len(), 36);
+        assert_eq!(model.mesh_list[0].index_buffer, (0..36).collect::<Vec<u32>>());
+        assert_near(model.vertex_buffer[0].normal, Vector3::new(0.0_f32, 0.0_f32, -1.0_f32), 0.00001_f32);
+    }
+
+    #[test]
+    fn tangents() {
+        let mut model = quad([(0.0, 0.0), (2.0, 0.0), (0.0, 2.0), (2.0, 2.0)]);
+        model.calc_tangents();
+        assert_eq!(model.vertex_buffer.len(), 4);
+        for v in &model.vertex_buffer {
+            assert_eq!(v.tangent, Vector4::new(1.0_f32, 0.0_f32, 0.0_f32, 1.0_f32));
+            assert_eq!(v.bitangent(), Vector3::unit_y());
+        }
+
+        // v goes down: the bitangent too
+        let mut model = quad([(0.0, 1.0), (1.0, 1.0), (0.0, 0.0), (1.0, 0.0)]);
+        model.calc_tangents();
+        for v in &model.vertex_buffer {
+            assert_eq!(v.tangent, Vector4::new(1.0_f32, 0.0_f32, 0.0_f32, -1.0_f32));
+            assert_eq!(v.bitangent(), Vector3::new(0.0_f32, -1.0_f32, 0.0_f32));
+        }
+    }
+
+    #[test]
+    fn mirrored_tangents() {
+        // u goes along x in the first triangle and against x in the second one, vertices on the diagonal are split
+        let mut model = quad([(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        model.calc_tangents();
+        assert_eq!(model.vertex_buffer.len(), 6);
+        let index_buffer = &model.mesh_list[0].index_buffer;
+        for (ind, handedness) in [(0, 1.0_f32), (3, -1.0_f32)].iter() {
+            for k in 0..3 {
+                let v = &model.vertex_buffer[index_buffer[ind + k] as usize];
+                assert_eq!(v.tangent.w, *handedness);
+                assert_near(v.bitangent(), Vector3::unit_y(), 0.00001_f32);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod importobj {
+    use std;
+    use utils;
+    use std::io::Write;
+    use cgmath::*;
+    use mesh::Model;
+    use blend::BlendMode;
+    use importobj::ModelObj;
+
+    // writes the model and the material into the temp directory and loads it
+    fn load_obj(name: &str, obj: &str, mtl: &str) -> Model {
+        let dir = std::env::temp_dir().join("software_render_importobj");
+        std::fs::create_dir_all(&dir).unwrap();
+        let obj = format!("mtllib {}.mtl\n{}", name, obj);
+        std::fs::File::create(dir.join(format!("{}.obj", name))).unwrap().write_all(obj.as_bytes()).unwrap();
+        std::fs::File::create(dir.join(format!("{}.mtl", name))).unwrap().write_all(mtl.as_bytes()).unwrap();
+
+        ModelObj::load(&dir.join(format!("{}.obj", name))).unwrap()
+    }
+
+    // a triangle with the material
+    fn load(name: &str, mtl: &str) -> Model {
+        load_obj(name, "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nusemtl test\nf 1//1 2//1 3//1\n", mtl)
+    }
+
+    #[test]
+    fn material() {
+        let model = load("material", "newmtl test\nKa 0.1 0.2 0.3\nKd 1 0.5 0\nKs 0.5 0.5 0.5\nKe 0 0 1\n\
+                                      Ns 96\nd 0.25\nillum 1\n");
+        let mat = &model.material_list[model.mesh_list[0].material_id];
+        assert_eq!(mat.diffuse, Vector3::new(255.0_f32, 127.5_f32, 0.0_f32));
+        assert_eq!(mat.specular, Vector3::new(127.5_f32, 127.5_f32, 127.5_f32));
+        assert_eq!(mat.emissive, Vector3::new(0.0_f32, 0.0_f32, 255.0_f32));
+        assert!(mat.shininess == 96.0_f32 && mat.shininess_given);
+        assert_eq!(mat.alpha, 0.25_f32);
+        assert_eq!(mat.illumination, 1);
+        assert!(mat.texture.is_none() && mat.texture_specular.is_none());
+        assert!(mat.texture_bump.is_none() && mat.texture_alpha.is_none());
+    }
+
+    #[test]
+    fn transparency() {
+        let model = load("transparency", "newmtl test\nKd 1 1 1\nTr 0.25\n");
+        let mat = &model.material_list[model.mesh_list[0].material_id];
+        assert_eq!(mat.alpha, 0.75_f32);
+        assert_eq!(mat.illumination, 2);
+        assert!(mat.blend == BlendMode::Alpha && !mat.render_state.depth_write);
+    }
+
+    #[test]
+    fn missing_normals() {
+        let mtl = format!("newmtl test\nKd 1 1 1\nmap_bump {}\n", utils::get_base_dir().unwrap().join("lego.png").display());
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nusemtl test\nf 1/1 2/2 3/3\n";
+        let model = load_obj("missing_normals", obj, &mtl);
+        for v in &model.vertex_buffer {
+            assert_eq!(v.normal, Vector3::new(0.0_f32, 0.0_f32, 1.0_f32));
+            assert_eq!(v.tangent, Vector4::new(1.0_f32, 0.0_f32, 0.0_f32, 1.0_f32));
+        }
+    }
+}
+
+#[cfg(test)]
+mod json {
+    use json::Json;
+
+    #[test]
+    fn parse() {
+        let json = Json::parse(" {\"a\": [1, -2.5e1, true, null], \"b\": {\"c\": \"x\\n\\u0041\\ud83d\\ude00\"}} ").unwrap();
+        let a = json.get("a").and_then(Json::as_array).unwrap();
+        assert_eq!(a.len(), 4);
+        assert_eq!(a[0].as_usize(), Some(1));
+        assert_eq!(a[1].as_f64(), Some(-25.0_f64));
+        assert_eq!(a[1].as_usize(), None);
+        assert_eq!(a[2].as_bool(), Some(true));
+        assert_eq!(a[3], Json::Null);
+        assert_eq!(json.get("b").and_then(|b| b.get("c")).and_then(Json::as_str), Some("x\nA\u{1F600}"));
+        assert!(json.get("d").is_none());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Json::parse("").is_err());
+        assert!(Json::parse("{\"a\" 1}").is_err());
+        assert!(Json::parse("[1, 2").is_err());
+        assert!(Json::parse("[1] 2").is_err());
+        assert!(Json::parse("\"\\x\"").is_err());
+        assert!(Json::parse(&"[".repeat(1000)).is_err());
+    }
+}
+
+#[cfg(test)]
+mod importgltf {
+    use std;
+    use std::io::Write;
+    use std::ops::Sub;
+    use cgmath::*;
+    use mesh::Model;
+    use blend::BlendMode;
+    use imagefile;
+    use importgltf::ModelGltf;
+
+    // the same data as BUFFER_BASE64
+    fn buffer() -> Vec<u8> {
+        let floats = [0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, // positions
+                      0.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, 0.0_f32, 1.0_f32, // normals
+                      0.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, 0.0_f32, 1.0_f32];                           // uv
+        let mut data = Vec::<u8>::new();
+        for v in &floats {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        for ind in &[0_u16, 1_u16, 2_u16, 0_u16] {
+            data.extend_from_slice(&ind.to_le_bytes());
+        }
+        data
+    }
+
+    const BUFFER_BASE64: &'static str = "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/\
+                                         AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAABAAIAAAA=";
+
+    // triangle in the child node, buffer - json of the buffer without byteLength
+    fn document(buffer: &str, nodes: &str) -> String {
+        format!("{{\"asset\": {{\"version\": \"2.0\"}}, \"scene\": 0, \"scenes\": [{{\"nodes\": [0]}}],
+            \"nodes\": {},
+            \"meshes\": [{{\"primitives\": [{{\"attributes\": {{\"POSITION\": 0, \"NORMAL\": 1, \"TEXCOORD_0\": 2}},
+                \"indices\": 3, \"material\": 0}}]}}],
+            \"materials\": [{{\"pbrMetallicRoughness\": {{\"baseColorFactor\": [1, 0.5, 0, 0.5],
+                \"metallicFactor\": 0.25, \"roughnessFactor\": 0.5}},
+                \"emissiveFactor\": [0, 0, 1], \"alphaMode\": \"BLEND\"}}],
+            \"buffers\": [{{{}\"byteLength\": 104}}],
+            \"bufferViews\": [{{\"buffer\": 0, \"byteLength\": 96}}, {{\"buffer\": 0, \"byteOffset\": 96, \"byteLength\": 6}}],
+            \"accessors\": [{{\"bufferView\": 0, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC3\"}},
+                {{\"bufferView\": 0, \"byteOffset\": 36, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC3\"}},
+                {{\"bufferView\": 0, \"byteOffset\": 72, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC2\"}},
+                {{\"bufferView\": 1, \"componentType\": 5123, \"count\": 3, \"type\": \"SCALAR\"}}]}}",
+                nodes, buffer)
+    }
+
+    const NODES_TRS: &'static str = "[{\"translation\": [1, 2, 3], \"children\": [1]}, {\"scale\": [2, 2, 2], \"mesh\": 0}]";
+
+    // writes files into the temp directory and loads the first one
+    fn load(files: &[(&str, &[u8])]) -> Result<Model, String> {
+        let dir = std::env::temp_dir().join("software_render_importgltf");
+        std::fs::create_dir_all(&dir).unwrap();
+        for &(name, data) in files {
+            std::fs::File::create(dir.join(name)).unwrap().write_all(data).unwrap();
+        }
+
+        ModelGltf::load(&dir.join(files[0].0))
+    }
+
+    fn position(model: &Model, ind: usize) -> Vector3<f32> {
+        model.vertex_buffer[model.mesh_list[0].index_buffer[ind] as usize].position
+    }
+
+    #[test]
+    fn embedded_buffer() {
+        let uri = format!("\"uri\": \"data:application/octet-stream;base64,{}\", ", BUFFER_BASE64);
+        let model = load(&[("embedded.gltf", document(&uri, NODES_TRS).as_bytes())]).unwrap();
+
+        assert_eq!(model.mesh_list.len(), 1);
+        assert_eq!(model.mesh_list[0].index_buffer, vec![0, 1, 2]);
+        assert_eq!(position(&model, 0), Vector3::new(1.0_f32, 2.0_f32, 3.0_f32));
+        assert_eq!(position(&model, 1), Vector3::new(3.0_f32, 2.0_f32, 3.0_f32));
+        assert_eq!(position(&model, 2), Vector3::new(1.0_f32, 4.0_f32, 3.0_f32));
+        for v in &model.vertex_buffer {
+            assert_eq!(v.normal, Vector3::new(0.0_f32, 0.0_f32, 1.0_f32));
+        }
+        // v goes up
+        assert_eq!(model.vertex_buffer[0].tex, Vector2::new(0.0_f32, 1.0_f32));
+        assert_eq!(model.vertex_buffer[2].tex, Vector2::new(0.0_f32, 0.0_f32));
+
+        // the first material is the default
+        assert_eq!(model.material_list.len(), 2);
+        let mat = &model.material_list[model.mesh_list[0].material_id];
+        assert_eq!(mat.diffuse, Vector3::new(255.0_f32, 127.5_f32, 0.0_f32));
+        assert_eq!(mat.emissive, Vector3::new(0.0_f32, 0.0_f32, 255.0_f32));
+        assert_eq!(mat.alpha, 0.5_f32);
+        assert!(mat.blend == BlendMode::Alpha && !mat.render_state.depth_write);
+        assert_eq!(mat.metallic, 0.25_f32);
+        assert_eq!(mat.roughness, 0.5_f32);
+        assert!(mat.texture.is_none() && mat.texture_metallic_roughness.is_none() && mat.texture_bump.is_none());
+    }
+
+    #[test]
+    fn alpha_mask() {
+        let uri = format!("\"uri\": \"data:application/octet-stream;base64,{}\", ", BUFFER_BASE64);
+        let gltf = document(&uri, NODES_TRS).replace("\"alphaMode\": \"BLEND\"", "\"alphaMode\": \"MASK\"");
+        let model = load(&[("mask.gltf", gltf.as_bytes())]).unwrap();
+        let mat = &model.material_list[model.mesh_list[0].material_id];
+        assert!(mat.blend == BlendMode::Opaque && mat.render_state.depth_write);
+        assert_eq!(mat.alpha_cutoff, Some(0.5_f32));
+
+        let gltf = gltf.replace("\"alphaMode\": \"MASK\"", "\"alphaMode\": \"MASK\", \"alphaCutoff\": 0.25");
+        let model = load(&[("mask_cutoff.gltf", gltf.as_bytes())]).unwrap();
+        assert_eq!(model.material_list[model.mesh_list[0].material_id].alpha_cutoff, Some(0.25_f32));
+    }
+
+    #[test]
+    fn missing_normals() {
+        let uri = format!("\"uri\": \"data:application/octet-stream;base64,{}\", ", BUFFER_BASE64);
+        let gltf = document(&uri, NODES_TRS).replace("\"NORMAL\": 1, ", "");
+        let model = load(&[("no_normals.gltf", gltf.as_bytes())]).unwrap();
+        // flat normals by the specification
+        assert_eq!(model.vertex_buffer.len(), 3);
+        for v in &model.vertex_buffer {
+            assert_eq!(v.normal, Vector3::new(0.0_f32, 0.0_f32, 1.0_f32));
+        }
+
+        // zero normals in the file are generated too, tangents for the normal map are calculated by them
+        let mut data = buffer();
+        for v in &mut data[36..72] {
+            *v = 0;
+        }
+        let gltf = document("\"uri\": \"zero_normals.bin\", ", NODES_TRS).replace("\"alphaMode\": \"BLEND\"}],",
+            "\"alphaMode\": \"BLEND\", \"normalTexture\": {\"index\": 0}}],
+            \"textures\": [{\"source\": 0}], \"images\": [{\"uri\": \"normal.png\"}],");
+        let model = load(&[("zero_normals.gltf", gltf.as_bytes()), ("zero_normals.bin", &data),
+                           ("normal.png", &imagefile::encode_png(1, 1, &[0x8080FF]))]).unwrap();
+        assert!(model.material_list[model.mesh_list[0].material_id].texture_bump.is_some());
+        for v in &model.vertex_buffer {
+            assert_eq!(v.normal, Vector3::new(0.0_f32, 0.0_f32, 1.0_f32));
+            assert!((v.tangent.truncate().length() - 1.0_f32).abs() < 0.001_f32);
+            assert!(v.tangent.truncate().dot(v.normal).abs() < 0.001_f32);
+        }
+    }
+
+    #[test]
+    fn tangents_of_file_are_kept() {
+        // two primitives with the normal map, only the first one has tangents in the file
+        let mut data = buffer();
+        for v in &[0.0_f32, 1.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, 1.0_f32] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        let gltf = "{\"asset\": {\"version\": \"2.0\"}, \"scene\": 0, \"scenes\": [{\"nodes\": [0]}], \"nodes\": [{\"mesh\": 0}],
+            \"meshes\": [{\"primitives\": [
+                {\"attributes\": {\"POSITION\": 0, \"NORMAL\": 1, \"TEXCOORD_0\": 2, \"TANGENT\": 4}, \"indices\": 3, \"material\": 0},
+                {\"attributes\": {\"POSITION\": 0, \"NORMAL\": 1, \"TEXCOORD_0\": 2}, \"indices\": 3, \"material\": 0}]}],
+            \"materials\": [{\"normalTexture\": {\"index\": 0}}],
+            \"textures\": [{\"source\": 0}], \"images\": [{\"uri\": \"normal.png\"}],
+            \"buffers\": [{\"uri\": \"tangents.bin\", \"byteLength\": 152}],
+            \"bufferViews\": [{\"buffer\": 0, \"byteLength\": 96}, {\"buffer\": 0, \"byteOffset\": 96, \"byteLength\": 6},
+                {\"buffer\": 0, \"byteOffset\": 104, \"byteLength\": 48}],
+            \"accessors\": [{\"bufferView\": 0, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC3\"},
+                {\"bufferView\": 0, \"byteOffset\": 36, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC3\"},
+                {\"bufferView\": 0, \"byteOffset\": 72, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC2\"},
+                {\"bufferView\": 1, \"componentType\": 5123, \"count\": 3, \"type\": \"SCALAR\"},
+                {\"bufferView\": 2, \"componentType\": 5126, \"count\": 3, \"type\": \"VEC4\"}]}";
+        let model = load(&[("tangents.gltf", gltf.as_bytes()), ("tangents.bin", &data),
+                           ("normal.png", &imagefile::encode_png(1, 1, &[0x8080FF]))]).unwrap();
+        assert_eq!(model.mesh_list.len(), 2);
+        for ind in &model.mesh_list[0].index_buffer {
+            assert_eq!(model.vertex_buffer[*ind as usize].tangent, Vector4::new(0.0_f32, 1.0_f32, 0.0_f32, 1.0_f32));
+        }
+        // tangents of the second one go along u
+        for ind in &model.mesh_list[1].index_buffer {
+            let tangent = model.vertex_buffer[*ind as usize].tangent.truncate();
+            assert!(tangent.sub(Vector3::new(1.0_f32, 0.0_f32, 0.0_f32)).length() < 0.001_f32, "{:?}", tangent);
+        }
+    }
+
+    #[test]
+    fn external_buffer() {
+        let data = buffer();
+        let model = load(&[("external.gltf", document("\"uri\": \"external%20buffer.bin\", ", NODES_TRS).as_bytes()),
+                           ("external buffer.bin", &data)]).unwrap();
+        assert_eq!(position(&model, 1), Vector3::new(3.0_f32, 2.0_f32, 3.0_f32));
+
+        assert!(load(&[("missing.gltf", document("\"uri\": \"missing.bin\", ", NODES_TRS).as_bytes())]).is_err());
+    }
+
+    #[test]
+    fn glb() {
+        // mirrored node changes the order of vertices
+        let nodes = "[{\"matrix\": [-1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1], \"mesh\": 0}]";
+        let mut json = document("", nodes).into_bytes();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        let bin = buffer();
+        let mut data = Vec::<u8>::new();
+        data.extend_from_slice(b"glTF");
+        data.extend_from_slice(&2_u32.to_le_bytes());
+        data.extend_from_slice(&((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        data.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"JSON");
+        data.extend_from_slice(&json);
+        data.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"BIN\0");
+        data.extend_from_slice(&bin);
+
+        let model = load(&[("binary.glb", &data)]).unwrap();
+        assert_eq!(model.mesh_list[0].index_buffer, vec![0, 2, 1]);
+        assert_eq!(model.vertex_buffer[1].position, Vector3::new(-1.0_f32, 0.0_f32, 0.0_f32));
+    }
+}
+
+#[cfg(test)]
+mod importstl {
+    use std;
+    use std::io::Write;
+    use cgmath::*;
+    use mesh::Model;
+    use importstl::ModelStl;
+
+    const SQUARE: &'static str = "solid square
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid square
+";
+
+    fn load(name: &str, data: &[u8], smooth_normals: bool) -> Model {
+        let path = std::env::temp_dir().join(name);
+        std::fs::File::create(&path).unwrap().write_all(data).unwrap();
+
+        ModelStl::load(&path, smooth_normals).unwrap()
+    }
+
+    #[test]
+    fn ascii() {
+        let model = load("software_render_ascii.stl", SQUARE.as_bytes(), true);
+        assert_eq!(model.vertex_buffer.len(), 4);
+        assert_eq!(model.mesh_list[0].index_buffer, vec![0, 1, 2, 0, 2, 3]);
+        for v in &model.vertex_buffer {
+            assert_eq!(v.normal, Vector3::new(0.0_f32, 0.0_f32, 1.0_f32));
+        }
+
+        let model = load("software_render_ascii_flat.stl", SQUARE.as_bytes(), false);
+        assert_eq!(model.vertex_buffer.len(), 6);
+        assert_eq!(model.mesh_list[0].index_buffer, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(model.vertex_buffer[4].position, Vector3::new(1.0_f32, 1.0_f32, 0.0_f32));
+    }
+
+    #[test]
+    fn binary() {
+        // the header starts with "solid" as in files of some exporters
+        let mut data = Vec::<u8>::new();
+        data.extend_from_slice(b"solid binary");
+        data.resize(80, 0);
+        data.extend_from_slice(&1_u32.to_le_bytes());
+        for v in &[0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, 1.0_f32, 0.0_f32] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend_from_slice(&[0_u8, 0_u8]);
+
+        let model = load("software_render_binary.stl", &data, true);
+        assert_eq!(model.vertex_buffer.len(), 3);
+        assert_eq!(model.vertex_buffer[1].position, Vector3::new(0.0_f32, 0.0_f32, 1.0_f32));
+        assert_eq!(model.vertex_buffer[0].normal, Vector3::new(-1.0_f32, 0.0_f32, 0.0_f32));
+    }
+}
+
+#[cfg(test)]
+mod importply {
+    use std;
+    use std::io::Write;
+    use cgmath::*;
+    use mesh::Model;
+    use importply::ModelPly;
+
+    fn load(name: &str, data: &[u8]) -> Result<Model, String> {
+        let path = std::env::temp_dir().join(name);
+        std::fs::File::create(&path).unwrap().write_all(data).unwrap();
+
+        ModelPly::load(&path, true)
+    }
+
+    #[test]
+    fn ascii_colors() {
+        let data = "ply\nformat ascii 1.0\ncomment quad\nelement vertex 4\n\
+                    property float x\nproperty float y\nproperty float z\n\
+                    property uchar red\nproperty uchar green\nproperty uchar blue\n\
+                    element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+                    0 0 0 255 0 0\n1 0 0 0 255 0\n1 1 0 0 0 255\n0 1 0 10 20 30\n4 0 1 2 3\n";
+        let model = load("software_render_colors.ply", data.as_bytes()).unwrap();
+        assert_eq!(model.mesh_list[0].index_buffer, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(model.vertex_buffer[3].color, Vector3::new(10.0_f32, 20.0_f32, 30.0_f32));
+        assert_eq!(model.vertex_buffer[2].normal, Vector3::new(0.0_f32, 0.0_f32, 1.0_f32));
+        assert!(model.material_list[0].vertex_color);
+    }
+
+    #[test]
+    fn binary_big_endian() {
+        let mut data = Vec::<u8>::new();
+        data.extend_from_slice(b"ply\r\nformat binary_big_endian 1.0\r\nelement vertex 3\r\n\
+                                 property float x\r\nproperty float y\r\nproperty float z\r\n\
+                                 property float nx\r\nproperty float ny\r\nproperty float nz\r\n\
+                                 element face 1\r\nproperty uchar flags\r\nproperty list uchar uint vertex_index\r\nend_header\r\n");
+        for v in &[0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, -1.0_f32,
+                   0.0_f32, 1.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, -1.0_f32,
+                   1.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, -1.0_f32] {
+            data.extend_from_slice(&v.to_bits().to_be_bytes());
+        }
+        data.extend_from_slice(&[7_u8, 3_u8]);
+        for ind in &[0_u32, 1_u32, 2_u32] {
+            data.extend_from_slice(&ind.to_be_bytes());
+        }
+
+        let model = load("software_render_binary.ply", &data).unwrap();
+        assert_eq!(model.mesh_list[0].index_buffer, vec![0, 1, 2]);
+        assert_eq!(model.vertex_buffer[1].position, Vector3::new(0.0_f32, 1.0_f32, 0.0_f32));
+        // normals from the file
+        assert_eq!(model.vertex_buffer[2].normal, Vector3::new(0.0_f32, 0.0_f32, -1.0_f32));
+        assert!(!model.material_list[0].vertex_color);
+
+        data.truncate(data.len() - 1);
+        assert!(load("software_render_short.ply", &data).is_err());
+    }
+}
+
+#[cfg(test)]
+mod exportobj {
+    use std;
+    use std::sync::Arc;
+    use cgmath::*;
+    use utils;
+    use exportobj;
+    use generator;
+    use imagefile;
+    use std::ops::Sub;
+    use mesh::{Model, Mesh};
+    use material::Material;
+    use texture::Texture;
+    use importobj::ModelObj;
+
+    fn round_trip(name: &str, model: &Model) -> Model {
+        let dir = std::env::temp_dir().join("software_render_exportobj");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.obj", name));
+        exportobj::save(model, &path).unwrap();
+
+        ModelObj::load(&path).unwrap()
+    }
+
+    #[test]
+    fn geometry() {
+        let model = generator::generate_sphere(8, Material::new()).unwrap();
+        let loaded = round_trip("sphere", &model);
+        assert_eq!(loaded.mesh_list.len(), 1);
+        let (src, dst) = (&model.mesh_list[0].index_buffer, &loaded.mesh_list[0].index_buffer);
+        assert_eq!(src.len(), dst.len());
+        for (a, b) in src.iter().zip(dst.iter()) {
+            let (a, b) = (&model.vertex_buffer[*a as usize], &loaded.vertex_buffer[*b as usize]);
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.normal, b.normal);
+            // ModelObj wraps texture coordinates to [0; 1)
+            assert_eq!(Vector2::new(a.tex.x % 1.0_f32, a.tex.y % 1.0_f32), b.tex);
+        }
+    }
+
+    #[test]
+    fn materials() {
+        let mut model = generator::generate_plane(Material::new()).unwrap();
+        let mut second = model.mesh_list[0].index_buffer.split_off(3);
+        model.mesh_list.push(Mesh::new());
+        model.mesh_list[1].index_buffer.append(&mut second);
+        model.mesh_list[1].material_id = 1;
+
+        let mut mat = Material::new();
+        mat.ambient = Vector3::new(0.0_f32, 51.0_f32, 255.0_f32);
+        mat.diffuse = Vector3::new(127.5_f32, 255.0_f32, 0.0_f32);
+        mat.specular = Vector3::new(255.0_f32, 255.0_f32, 255.0_f32);
+        mat.emissive = Vector3::new(0.0_f32, 0.0_f32, 127.5_f32);
+        mat.shininess = 96.0_f32;
+        mat.shininess_given = true;
+        mat.alpha = 0.25_f32;
+        mat.illumination = 1;
+        let lego = utils::get_base_dir().unwrap().join("lego.png");
+        mat.create_texture(&lego).unwrap();
+        mat.texture_bump = mat.texture.clone();
+        mat.bump_scale = 0.5_f32;
+        // a texture without a file is saved to png
+        let pixels = [0xFF0000_u32, 0x00FF00_u32, 0x0000FF_u32, 0xFFFFFF_u32];
+        let png = imagefile::encode_png(2, 2, &pixels);
+        mat.texture_specular = Some(Arc::new(Texture::from_memory(&png, "memory").unwrap()));
+        model.material_list.push(mat);
+
+        let loaded = round_trip("materials", &model);
+        assert_eq!(loaded.mesh_list.len(), 2);
+        let mat = &loaded.material_list[loaded.mesh_list[1].material_id];
+        assert!(mat.ambient.sub(Vector3::new(0.0_f32, 51.0_f32, 255.0_f32)).length() < 0.001_f32);
+        assert_eq!(mat.diffuse, Vector3::new(127.5_f32, 255.0_f32, 0.0_f32));
+        assert_eq!(mat.specular, Vector3::new(255.0_f32, 255.0_f32, 255.0_f32));
+        assert_eq!(mat.emissive, Vector3::new(0.0_f32, 0.0_f32, 127.5_f32));
+        assert_eq!(mat.shininess, 96.0_f32);
+        assert_eq!(mat.alpha, 0.25_f32);
+        assert_eq!(mat.illumination, 1);
+        assert_eq!(mat.texture.as_ref().and_then(|t| t.path()), Some(lego.as_path()));
+        let specular = mat.texture_specular.as_ref().unwrap();
+        assert_eq!(specular.size, Vector2::new(2.0_f32, 2.0_f32));
+        let surface = specular.get_surface(0);
+        assert_eq!(surface.data[1], Vector3::new(0.0_f32, 255.0_f32, 0.0_f32));
+        assert_eq!(mat.texture_bump.as_ref().and_then(|t| t.path()), Some(lego.as_path()));
+        assert_eq!(mat.bump_scale, 0.5_f32);
+        assert!(mat.texture_alpha.is_none());
+
+        let mat = &loaded.material_list[loaded.mesh_list[0].material_id];
+        assert!(mat.texture.is_none());
+        // the default exponent is not written
+        assert!(!mat.shininess_given);
+    }
+}
+
+// Renders complete scenes headlessly and compares them with reference images from "media/golden".
+// A missing reference fails the test, run tests with GOLDEN_UPDATE=1 to create new references
+// or to re-create all of them after an intended change of the output.
+// On failure actual and diff images are saved to "media/golden/failed".
+#[cfg(test)]
+mod golden {
+    use std;
+    use cgmath::*;
+    use generator;
+    use imagefile;
+    use utils;
+    use std::path::Path;
+    use std::ops::Mul;
+    use mesh::Model;
+    use scene::Scene;
+    use light::Light;
+    use shadow::ShadowMap;
+    use material::Material;
+    use importobj::ModelObj;
+    use framebuffer::{FrameBuffer, Antialiasing};
+    use tiler::RasterizerType;
+    use shader::{Shader, ShaderType};
+
+    const X_SIZE: u32 = 128;
+    const Y_SIZE: u32 = 96;
+    const CHANNEL_TOLERANCE: i32 = 4;
+    const MAX_BAD_PIXELS: usize = 8;
+
+    enum TestModel {
+        Plane,
+        PlaneCrossNear,
+        Sphere,
+        SphereCubemap,
+        SphereMetal,
+        Monster,
+        Ring,
+    }
+
+    // returns model, eye position and world matrix
+    fn load_model(test_model: TestModel) -> (Model, Point3<f32>, Matrix4<f32>) {
+        let rotation = Matrix4::from(Matrix3::from_angle_y(rad(0.5_f32)));
+        let base_dir = utils::get_base_dir().unwrap();
+        match test_model {
+            TestModel::Plane | TestModel::PlaneCrossNear => {
+                let mut mat = Material::new();
+                mat.specular = Vector3::new(64.0_f32, 64.0_f32, 64.0_f32);
+                mat.create_texture(base_dir.join("lego.png").as_path()).unwrap();
+                let model = generator::generate_plane(mat).unwrap();
+                let mut world = Matrix4::from(Matrix3::from_angle_x(rad(std::f32::consts::PI * 0.25_f32)));
+                if let TestModel::PlaneCrossNear = test_model {
+                    world = rotation.mul(&world);
+                }
+                (model, Point3::new(0.0_f32, 0.0_f32, -0.5_f32), world)
+            },
+            TestModel::Sphere | TestModel::SphereCubemap => {
+                let mut mat = Material::new();
+                mat.ambient = Vector3::new(25.0_f32, 10.0_f32, 5.0_f32);
+                mat.diffuse = Vector3::new(200.0_f32, 100.0_f32, 50.0_f32);
+                mat.specular = Vector3::new(255.0_f32, 255.0_f32, 255.0_f32);
+                let mut model = generator::generate_sphere(30, mat).unwrap();
+                if let TestModel::SphereCubemap = test_model {
+                    model.add_texture_cube(&base_dir.join("cubemap/"), "jpg").unwrap();
+                }
+                let world = rotation.mul(&model.to_center_matrix());
+                (model, Point3::new(0.0_f32, 1.1_f32, -1.1_f32), world)
+            },
+            TestModel::SphereMetal => {
+                let mut mat = Material::new();
+                mat.diffuse = Vector3::new(230.0_f32, 190.0_f32, 120.0_f32);
+                mat.ambient = mat.diffuse;
+                mat.metallic = 1.0_f32;
+                mat.roughness = 0.3_f32;
+                let mut model = generator::generate_sphere(30, mat).unwrap();
+                model.add_texture_cube(&base_dir.join("cubemap/"), "jpg").unwrap();
+                let world = rotation.mul(&model.to_center_matrix());
+                (model, Point3::new(0.0_f32, 1.1_f32, -1.1_f32), world)
+            },
+            TestModel::Monster => {
+                let model = ModelObj::load(Path::new("monster/monster.obj")).unwrap();
+                let world = rotation.mul(&model.to_center_matrix());
+                (model, Point3::new(0.0_f32, 0.3_f32, 0.6_f32), world)
+            },
+            TestModel::Ring => {
+                let model = ModelObj::load(Path::new("ring/ring.obj")).unwrap();
+                let world = rotation.mul(&model.to_center_matrix());
+                (model, Point3::new(0.0_f32, 0.25_f32, 0.75_f32), world)
+            },
+        }
+    }
+
+    fn compare(name: &str, framebuffer: &FrameBuffer) {
+        let dir = utils::get_base_dir().unwrap().join("golden");
+        let path = dir.join(format!("{}.ppm", name));
+        if std::env::var("GOLDEN_UPDATE").is_ok() {
+            std::fs::create_dir_all(&dir).unwrap();
+            framebuffer.save_color(&path).unwrap();
+            return;
+        }
+        assert!(path.exists(), "reference image \"{}\" is missing, run tests with GOLDEN_UPDATE=1 to create it",
+                path.display());
+
+        let (x_size, y_size, expected) = imagefile::load_ppm(&path).unwrap();
+        assert!(x_size == framebuffer.x_size && y_size == framebuffer.y_size,
+                "size of reference image \"{}\" is {}x{}", path.display(), x_size, y_size);
+
+        let mut cnt_bad = 0;
+        let mut diff = vec![0_u32; expected.len()];
+        for (ind, (real, except)) in framebuffer.cbuffer.iter().zip(expected.iter()).enumerate() {
+            let mut max_delta = 0;
+            for shift in &[0, 8, 16] {
+                let delta = ((real >> shift) & 0xFF) as i32 - ((except >> shift) & 0xFF) as i32;
+                max_delta = std::cmp::max(max_delta, delta.abs());
+            }
+            diff[ind] = if max_delta > CHANNEL_TOLERANCE {
+                cnt_bad += 1;
+                0xFF0000
+            } else {
+                (real >> 2) & 0x3F3F3F
+            };
+        }
+
+        if cnt_bad > MAX_BAD_PIXELS {
+            let failed_dir = dir.join("failed");
+            std::fs::create_dir_all(&failed_dir).unwrap();
+            framebuffer.save_color(&failed_dir.join(format!("{}.ppm", name))).unwrap();
+            imagefile::save(&failed_dir.join(format!("{}_diff.ppm", name)), x_size, y_size, &diff).unwrap();
+            panic!("{} pixels differ from reference image \"{}\", see \"{}\"",
+                   cnt_bad, path.display(), failed_dir.display());
+        }
+    }
+
+    fn render(name: &str, model: TestModel, shader_type: ShaderType) {
+        render_with(name, model, shader_type, RasterizerType::Scanline);
+    }
+
+    fn render_with(name: &str, model: TestModel, shader_type: ShaderType, rasterizer: RasterizerType) {
+        let (model, eye, world) = load_model(model);
+        let mut shader = Shader::new(shader_type);
+        let mut scene = Scene::headless(X_SIZE, Y_SIZE);
+        scene.rasterizer(rasterizer)
+            .proj(deg(100.0_f32), 0.1_f32, 100.0_f32)
+            .view(eye, Point3::new(0.0_f32, 0.0_f32, 0.0_f32), Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+            .light(Vector3::new(1.0_f32, 1.0_f32, -1.0_f32))
+            .ambient_intensity(0.2_f32);
+
+        assert!(scene.start(0xAAAAAA));
+        scene.draw(&model, world, &mut shader);
+        compare(name, scene.framebuffer());
+    }
+
+    #[test]
+    fn ring_ortho() {
+        let (model, _, world) = load_model(TestModel::Ring);
+        let mut shader = Shader::new(ShaderType::PhongBlinn);
+        let mut scene = Scene::headless(X_SIZE, Y_SIZE);
+        scene.ortho(0.4_f32, 0.1_f32, 10.0_f32)
+            .view(Point3::new(0.0_f32, 1.0_f32, 1.5_f32), Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                  Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+            .light(Vector3::new(1.0_f32, 1.0_f32, -1.0_f32))
+            .ambient_intensity(0.2_f32);
+
+        assert!(scene.start(0xAAAAAA));
+        scene.draw(&model, world, &mut shader);
+        compare("ring_ortho", scene.framebuffer());
+    }
+
+    // empty frames of all antialiasing modes are drawn and resolved without panics
+    #[test]
+    fn empty_framebuffer() {
+        let model = generator::generate_plane(Material::new()).unwrap();
+        let eye = Point3::new(0.0_f32, 0.0_f32, -2.0_f32);
+        let world = Matrix4::from_scale(1.0_f32);
+        let mut shader = Shader::new(ShaderType::Default);
+        for &(x_size, y_size) in &[(0, 0), (4, 0), (0, 4)] {
+            for mode in &[Antialiasing::None, Antialiasing::Msaa(4), Antialiasing::Ssaa(2)] {
+                let mut scene = Scene::headless(x_size, y_size);
+                scene.antialiasing(*mode)
+                    .view(eye, Point3::new(0.0_f32, 0.0_f32, 0.0_f32), Vector3::new(0.0_f32, 1.0_f32, 0.0_f32));
+                assert!(scene.start(0));
+                scene.draw(&model, world, &mut shader)
+                    .draw_line(Point3::new(0.0_f32, 0.0_f32, 0.0_f32), eye, 0xFFFFFF)
+                    .resolve();
+                assert!(scene.framebuffer().cbuffer.is_empty());
+            }
+        }
+    }
+
+    // tiles with any number of threads give the same bits as the whole framebuffer drawn as one tile
+    #[test]
+    fn threads_are_bit_identical() {
+        let mut buffers = Vec::<FrameBuffer>::new();
+        for &(threads, tile_rows) in &[(1, Y_SIZE as usize), (1, 16), (3, 16), (3, 5)] {
+            let (model, eye, world) = load_model(TestModel::Ring);
+            let mut shader = Shader::new(ShaderType::CookTorrance);
+            let mut scene = Scene::headless(X_SIZE, Y_SIZE);
+            scene.threads(threads)
+                .tile_rows(tile_rows)
+                .proj(deg(100.0_f32), 0.1_f32, 100.0_f32)
+                .view(eye, Point3::new(0.0_f32, 0.0_f32, 0.0_f32), Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+                .light(Vector3::new(1.0_f32, 1.0_f32, -1.0_f32));
+            assert!(scene.start(0xAAAAAA));
+            scene.draw(&model, world, &mut shader);
+            buffers.push(scene.framebuffer().clone());
+        }
+        for framebuffer in &buffers[1..] {
+            assert!(framebuffer.cbuffer == buffers[0].cbuffer);
+            assert!(framebuffer.zbuffer == buffers[0].zbuffer);
+        }
+    }
+
+    // directional, point and spot lights of different colors
+    fn render_lights(name: &str, model: TestModel, shader_type: ShaderType) {
+        let (model, eye, world) = load_model(model);
+        let mut shader = Shader::new(shader_type);
+        let mut scene = Scene::headless(X_SIZE, Y_SIZE);
+        scene.proj(deg(100.0_f32), 0.1_f32, 100.0_f32)
+            .view(eye, Point3::new(0.0_f32, 0.0_f32, 0.0_f32), Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+            .add_light(Light::directional(Vector3::new(0.0_f32, -1.0_f32, 0.0_f32),
+                                          Vector3::new(0.3_f32, 0.3_f32, 0.3_f32), 1.0_f32))
+            .add_light(Light::point(Vector3::new(1.0_f32, 0.5_f32, -0.5_f32),
+                                    Vector3::new(1.0_f32, 0.2_f32, 0.2_f32), 1.5_f32)
+                       .with_attenuation(1.0_f32, 0.0_f32, 1.0_f32))
+            .add_light(Light::spot(Vector3::new(-0.5_f32, 1.0_f32, -1.0_f32), Vector3::new(0.5_f32, -1.0_f32, 1.0_f32),
+                                   deg(10.0_f32), deg(15.0_f32), Vector3::new(0.2_f32, 0.4_f32, 1.0_f32), 2.0_f32))
+            .ambient_intensity(0.1_f32);
+
+        assert!(scene.start(0x404040));
+        scene.draw(&model, world, &mut shader);
+        compare(name, scene.framebuffer());
+    }
+
+    // sphere above the ground lit by a shadow casting light
+    fn render_shadow(name: &str, light: Light, shader_type: ShaderType) {
+        let mut mat = Material::new();
+        mat.ambient = Vector3::new(40.0_f32, 40.0_f32, 40.0_f32);
+        mat.diffuse = Vector3::new(200.0_f32, 200.0_f32, 200.0_f32);
+        mat.specular = Vector3::new(64.0_f32, 64.0_f32, 64.0_f32);
+        // front side of the plane is opposite to its normal
+        let mut ground = generator::generate_plane(mat.clone()).unwrap();
+        for v in &mut ground.vertex_buffer {
+            v.normal = Vector3::new(0.0_f32, 0.0_f32, -1.0_f32);
+        }
+        let ground_world = Matrix4::from_scale(3.0_f32)
+            .mul(&Matrix4::from(Matrix3::from_angle_x(rad(std::f32::consts::PI * 0.5_f32))));
+        let sphere = generator::generate_sphere(30, mat).unwrap();
+        let sphere_world = Matrix4::from_translation(Vector3::new(0.0_f32, 0.5_f32, 0.0_f32))
+            .mul(&Matrix4::from_scale(0.4_f32));
+
+        let mut shader = Shader::new(shader_type);
+        let mut scene = Scene::headless(X_SIZE, Y_SIZE);
+        scene.proj(deg(60.0_f32), 0.1_f32, 100.0_f32)
+            .view(Point3::new(0.0_f32, 2.0_f32, -2.5_f32), Point3::new(0.0_f32, 0.0_f32, 0.0_f32),
+                  Vector3::new(0.0_f32, 1.0_f32, 0.0_f32))
+            .add_light(light)
+            .ambient_intensity(0.2_f32);
+
+        assert!(scene.start(0x404040));
+        scene.draw_shadow(&ground, ground_world)
+            .draw_shadow(&sphere, sphere_world)
+            .draw(&ground, ground_world, &mut shader)
+            .draw(&sphere, sphere_world, &mut shader);
+        compare(name, scene.framebuffer());
+    }
+
+    #[test]
+    fn shadow_directional_lambert() {
+        let light = Light::directional(Vector3::new(0.8_f32, -1.0_f32, -0.6_f32), Vector3::new(1.0_f32, 1.0_f32, 1.0_f32), 1.0_f32)
+            .with_shadow(ShadowMap::new(256).with_bounds(Vector3::new(0.0_f32, 0.0_f32, 0.0_f32), 2.5_f32));
+        render_shadow("shadow_directional_lambert", light, ShaderType::Lambert);
+    }
+
+    #[test]
+    fn shadow_spot_phong_blinn() {
+        let light = Light::spot(Vector3::new(-1.0_f32, 2.0_f32, 0.5_f32), Vector3::new(1.0_f32, -2.0_f32, -0.5_f32),
+                                deg(25.0_f32), deg(35.0_f32), Vector3::new(1.0_f32, 0.9_f32, 0.7_f32), 2.0_f32)
+            .with_attenuation(1.0_f32, 0.0_f32, 0.2_f32)
+            .with_shadow(ShadowMap::new(256).with_range(0.5_f32, 6.0_f32).with_pcf(2));
+        render_shadow("shadow_spot_phong_blinn", light, ShaderType::PhongBlinn);
+    }
+
+    #[test]
+    fn sphere_lights_lambert() {
+        render_lights("sphere_lights_lambert", TestModel::Sphere, ShaderType::Lambert);
+    }
+
+    #[test]
+    fn sphere_lights_phong_blinn() {
+        render_lights("sphere_lights_phong_blinn", TestModel::Sphere, ShaderType::PhongBlinn);
+    }
+
+    #[test]
+    fn sphere_lights_cook_torrance() {
+        render_lights("sphere_lights_cook_torrance", TestModel::Sphere, ShaderType::CookTorrance);
+    }
+
+    #[test]
+    fn sphere_lights_pbr() {
+        render_lights("sphere_lights_pbr", TestModel::Sphere, ShaderType::Pbr);
+    }
+
+    #[test]
+    fn monster_lambert_halfspace() {
+        render_with("monster_lambert_halfspace", TestModel::Monster, ShaderType::Lambert, RasterizerType::HalfSpace);
+    }
+
+    #[test]
+    fn ring_cook_torrance_halfspace() {
+        render_with("ring_cook_torrance_halfspace", TestModel::Ring, ShaderType::CookTorrance, RasterizerType::HalfSpace);
+    }
+
+    macro_rules! golden_test {
+        ($name:ident, $model:expr, $shader:expr) => {
+            #[test]
+            fn $name() {
+                render(stringify!($name), $model, $shader);
+            }
+        }
+    }
+
+    golden_test!(plane_default, TestModel::Plane, ShaderType::Default);
+    golden_test!(plane_normal, TestModel::Plane, ShaderType::Normal);
+    golden_test!(plane_lambert, TestModel::Plane, ShaderType::Lambert);
+    golden_test!(plane_phong_blinn, TestModel::Plane, ShaderType::PhongBlinn);
+    golden_test!(plane_cook_torrance, TestModel::Plane, ShaderType::CookTorrance);
+    golden_test!(plane_cross_near_lambert, TestModel::PlaneCrossNear, ShaderType::Lambert);
+
+    golden_test!(sphere_default, TestModel::Sphere, ShaderType::Default);
+    golden_test!(sphere_normal, TestModel::Sphere, ShaderType::Normal);
+    golden_test!(sphere_lambert, TestModel::Sphere, ShaderType::Lambert);
+    golden_test!(sphere_phong_blinn, TestModel::Sphere, ShaderType::PhongBlinn);
+    golden_test!(sphere_cook_torrance, TestModel::Sphere, ShaderType::CookTorrance);
+    golden_test!(sphere_pbr, TestModel::Sphere, ShaderType::Pbr);
+    golden_test!(sphere_cubemap_phong_blinn, TestModel::SphereCubemap, ShaderType::PhongBlinn);
+    golden_test!(sphere_cubemap_pbr, TestModel::SphereCubemap, ShaderType::Pbr);
+    golden_test!(sphere_metal_pbr, TestModel::SphereMetal, ShaderType::Pbr);
+
+    golden_test!(monster_default, TestModel::Monster, ShaderType::Default);
+    golden_test!(monster_normal, TestModel::Monster, ShaderType::Normal);
+    golden_test!(monster_lambert, TestModel::Monster, ShaderType::Lambert);
+    golden_test!(monster_phong_blinn, TestModel::Monster, ShaderType::PhongBlinn);
+    golden_test!(monster_cook_torrance, TestModel::Monster, ShaderType::CookTorrance);
+    golden_test!(monster_pbr, TestModel::Monster, ShaderType::Pbr);
+
+    golden_test!(ring_default, TestModel::Ring, ShaderType::Default);
+    golden_test!(ring_normal, TestModel::Ring, ShaderType::Normal);
+    golden_test!(ring_lambert, TestModel::Ring, ShaderType::Lambert);
+    golden_test!(ring_phong_blinn, TestModel::Ring, ShaderType::PhongBlinn);
+    golden_test!(ring_cook_torrance, TestModel::Ring, ShaderType::CookTorrance);
 }
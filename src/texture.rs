@@ -1,9 +1,11 @@
 use std;
 use sdl2;
 use cgmath::*;
-use std::rc::Rc;
-use std::path::Path;
-use sdl2_image::LoadSurface;
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use imagefile;
+use memory::vector3_to_u32;
+use sdl2_image::{LoadSurface, ImageRWops};
 use std::ops::{Add, Mul, Div};
 
 pub struct Surface {
@@ -13,12 +15,31 @@ pub struct Surface {
 }
 
 pub struct Texture {
-    levels: Vec<Rc<Surface>>,
+    levels: Vec<Arc<Surface>>,
+    path: Option<PathBuf>,         // None for textures loaded from memory
+    alpha: Option<Arc<Texture>>,   // alpha channel in all components, None for opaque images
     pub size: Vector2<f32>,
 }
 
 pub struct TextureCube {
     textures: [Texture; 6],
+    irradiance: [Vector3<f32>; 9], // spherical harmonics of the diffuse light, see TextureCube::irradiance
+}
+
+// size of the mip level used for the diffuse light
+const IRRADIANCE_SIZE: usize = 32;
+
+// 9 spherical harmonics basis functions for the normalized direction
+fn sh_basis(d: Vector3<f32>) -> [f32; 9] {
+    [0.282095_f32,
+     0.488603_f32 * d.y,
+     0.488603_f32 * d.z,
+     0.488603_f32 * d.x,
+     1.092548_f32 * d.x * d.y,
+     1.092548_f32 * d.y * d.z,
+     0.315392_f32 * (3.0_f32 * d.z * d.z - 1.0_f32),
+     1.092548_f32 * d.x * d.z,
+     0.546274_f32 * (d.x * d.x - d.y * d.y)]
 }
 
 impl Surface {
@@ -70,15 +91,35 @@ impl Surface {
 impl Texture {
     pub fn new(path: &Path) -> Result<Texture, String> {
         let surface = try!(Texture::load_surface(path));
+        let mut texture = try!(Texture::from_surface(surface, &path.display().to_string()));
+        texture.path = Some(path.to_path_buf());
+
+        Ok(texture)
+    }
+
+    // image file (png, jpg, ...) in memory, name is used in error messages
+    pub fn from_memory(data: &[u8], name: &str) -> Result<Texture, String> {
+        println!("load texture: \"{}\"", name);
+
+        let surface_load = match sdl2::rwops::RWops::from_bytes(data).and_then(|rwops| rwops.load()) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("can't load texture \"{}\", error = \"{}\"", name, e))
+        };
 
+        let surface = try!(Texture::convert_surface(surface_load, name));
+        Texture::from_surface(surface, name)
+    }
+
+    fn from_surface(surface: sdl2::surface::Surface, name: &str) -> Result<Texture, String> {
         let size_x = surface.as_ref().width() as usize;
         let size_y = surface.as_ref().height() as usize;
         let data_u8: &[u8] = match surface.as_ref().without_lock() {
             Some(v) => v,
-            None => return Err(format!("can't lock surface for texture {}", path.display()))
+            None => return Err(format!("can't lock surface for texture {}", name))
         };
 
         let mut lvl0 = Surface::new(size_x, size_y);
+        let mut alpha = Surface::new(size_x, size_y);
         for y in 0..size_y {
             for x in 0..size_x {
                 let ind = ((size_y - y - 1) * size_x + x) * 4;
@@ -86,41 +127,81 @@ impl Texture {
                     data_u8[ind + 2] as f32,
                     data_u8[ind + 1] as f32,
                     data_u8[ind + 0] as f32));
+                alpha.data.push(Vector3::from_value(data_u8[ind + 3] as f32));
             }}
 
+        let mut this = Texture::from_level(lvl0);
+        if alpha.data.iter().any(|a| a.x < 255.0_f32) {
+            this.alpha = Some(Arc::new(Texture::from_level(alpha)));
+        }
+
+        Ok(this)
+    }
+
+    fn from_level(lvl0: Surface) -> Texture {
+        let size = Vector2::new(lvl0.size_x as f32, lvl0.size_y as f32);
         let mut this = Texture {
-            levels: vec![Rc::new(lvl0)],
-            size: Vector2::new(size_x as f32, size_y as f32),
+            levels: vec![Arc::new(lvl0)],
+            path: None,
+            alpha: None,
+            size: size,
         };
         this.gen_mipmap();
 
-        Ok(this)
+        this
+    }
+
+    // file of the texture
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_ref().map(|p| p.as_path())
     }
 
-    pub fn get_surface(&self, mip_lvl: usize) -> Rc<Surface> {
+    // saves the first mip level, see imagefile::save for formats
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let surface = &self.levels[0];
+        let pixels: Vec<u32> = surface.data.iter().map(vector3_to_u32).collect();
+
+        imagefile::save(path, surface.size_x, surface.size_y, &pixels)
+    }
+
+    // alpha channel of the image in all components, None if the image is opaque
+    pub fn alpha(&self) -> Option<&Arc<Texture>> {
+        self.alpha.as_ref()
+    }
+
+    pub fn mip_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn get_surface(&self, mip_lvl: usize) -> Arc<Surface> {
         self.levels[std::cmp::min(mip_lvl, self.levels.len() - 1)].clone()
     }
 
     fn load_surface<'a>(path: &Path) -> Result<sdl2::surface::Surface<'a>, String> {
         println!("load texture: \"{}\"", path.display());
 
-        let standart =
-            match sdl2::surface::Surface::new(1, 1, sdl2::pixels::PixelFormatEnum::ARGB8888) {
-                Ok(v) => v,
-                Err(e) => return Err(format!("can't create standart surface for texture \"{}\", error = \"{}\"",
-                                             path.display(), e))
-            };
-
         let surface_load = match sdl2::surface::Surface::from_file(path) {
             Ok(v) => v,
             Err(e) => return Err(format!("can't load texture \"{}\", error = \"{}\"",
                                          path.display(), e))
         };
 
+        Texture::convert_surface(surface_load, &path.display().to_string())
+    }
+
+    // to ARGB8888
+    fn convert_surface<'a>(surface_load: sdl2::surface::Surface, name: &str) -> Result<sdl2::surface::Surface<'a>, String> {
+        let standart =
+            match sdl2::surface::Surface::new(1, 1, sdl2::pixels::PixelFormatEnum::ARGB8888) {
+                Ok(v) => v,
+                Err(e) => return Err(format!("can't create standart surface for texture \"{}\", error = \"{}\"",
+                                             name, e))
+            };
+
         match surface_load.as_ref().convert(&standart.as_ref().pixel_format()) {
             Ok(v) => Ok(v),
             Err(e) => return Err(format!("can't convert surface for texture \"{}\", error = \"{}\"",
-                                         path.display(), e))
+                                         name, e))
         }
     }
 
@@ -176,7 +257,7 @@ impl Texture {
                 }
             }
 
-            self.levels.push(Rc::new(s));
+            self.levels.push(Arc::new(s));
             size_x = next_size_x;
             size_y = next_size_y;
             surface_ind += 1;
@@ -193,10 +274,124 @@ impl TextureCube {
             try!(Texture::new(&dir_path.join(format!("negy.{}", image_extension)))),
             try!(Texture::new(&dir_path.join(format!("posz.{}", image_extension)))),
             try!(Texture::new(&dir_path.join(format!("negz.{}", image_extension))))];
-        Ok(TextureCube {textures: textures})
+        Ok(TextureCube::from_textures(textures))
+    }
+
+    // faces in order: posx, negx, posy, negy, posz, negz
+    pub fn from_textures(textures: [Texture; 6]) -> TextureCube {
+        let irradiance = TextureCube::calc_irradiance(&textures);
+        TextureCube {
+            textures: textures,
+            irradiance: irradiance,
+        }
     }
 
     pub fn get_texture(&self, index: usize) -> &Texture {
         &self.textures[index]
     }
+
+    pub fn mip_count(&self) -> usize {
+        self.textures.iter().map(Texture::mip_count).min().unwrap_or(1)
+    }
+
+    // index of the face and texture coordinates on it for the direction
+    pub fn face_coord(dir: Vector3<f32>) -> (usize, Vector2<f32>) {
+        let (ax, ay, az) = (dir.x.abs(), dir.y.abs(), dir.z.abs());
+        let (index, sc, tc, ma) =
+            if (ax > az) && (ax > ay) {
+                if dir.x.is_sign_positive() { (0, -dir.z, dir.y, ax) } else { (1, dir.z, dir.y, ax) }
+            } else if ay > az {
+                if dir.y > 0.0_f32 { (2, dir.x, -dir.z, ay) } else { (3, dir.x, dir.z, ay) }
+            } else {
+                if dir.z.is_sign_positive() { (4, dir.x, dir.y, az) } else { (5, -dir.x, dir.y, az) }
+            };
+
+        (index, Vector2::new((sc / ma + 1.0_f32) / 2.0_f32, (tc / ma + 1.0_f32) / 2.0_f32))
+    }
+
+    // inverse of face_coord, the direction is not normalized
+    fn face_dir(index: usize, coord: Vector2<f32>) -> Vector3<f32> {
+        let s = coord.x * 2.0_f32 - 1.0_f32;
+        let t = coord.y * 2.0_f32 - 1.0_f32;
+        match index {
+            0 => Vector3::new(1.0_f32, t, -s),
+            1 => Vector3::new(-1.0_f32, t, s),
+            2 => Vector3::new(s, 1.0_f32, -t),
+            3 => Vector3::new(s, -1.0_f32, t),
+            4 => Vector3::new(s, t, 1.0_f32),
+            _ => Vector3::new(-s, t, -1.0_f32),
+        }
+    }
+
+    // color in the direction, lod - mip level, fractional levels are interpolated,
+    // blurred levels approximate the reflection of rough surfaces
+    pub fn sample(&self, dir: Vector3<f32>, lod: f32) -> Vector3<f32> {
+        let (index, coord) = TextureCube::face_coord(dir);
+        let texture = &self.textures[index];
+        let lod = lod.max(0.0_f32).min((texture.mip_count() - 1) as f32);
+        let level = lod as usize;
+        // samples do not cross the edge of the face
+        let sample = |level: usize| {
+            let surface = texture.get_surface(level);
+            let max = Vector2::new((surface.size_x - 1) as f32 / surface.size_x as f32,
+                                   (surface.size_y - 1) as f32 / surface.size_y as f32);
+            surface.tex_2d_bilinear(Vector2::new(coord.x.max(0.0_f32).min(max.x), coord.y.max(0.0_f32).min(max.y)))
+        };
+
+        let k = lod - level as f32;
+        if k == 0.0_f32 {
+            sample(level)
+        } else {
+            sample(level).mul(1.0_f32 - k).add(&sample(level + 1).mul(k))
+        }
+    }
+
+    // Diffuse light for the normal: the cosine weighted average of the cubemap, {r, g, b}, [0; 255].
+    // The lambert surface of the color c in the cubemap has the color c * irradiance / 255.
+    pub fn irradiance(&self, normal: Vector3<f32>) -> Vector3<f32> {
+        let basis = sh_basis(normal.normalize());
+        let mut result = Vector3::<f32>::zero();
+        for (coeff, y) in self.irradiance.iter().zip(basis.iter()) {
+            result = result.add(&coeff.mul(*y));
+        }
+
+        Vector3::new(result.x.max(0.0_f32), result.y.max(0.0_f32), result.z.max(0.0_f32))
+    }
+
+    // projection to spherical harmonics convolved with the cosine (Ramamoorthi, Hanrahan)
+    fn calc_irradiance(textures: &[Texture; 6]) -> [Vector3<f32>; 9] {
+        let mut coeffs = [Vector3::<f32>::zero(); 9];
+        let mut weight_sum = 0.0_f32;
+        for (index, texture) in textures.iter().enumerate() {
+            let level = (0..texture.mip_count())
+                .find(|lvl| texture.get_surface(*lvl).size_x <= IRRADIANCE_SIZE)
+                .unwrap_or(texture.mip_count() - 1);
+            let surface = texture.get_surface(level);
+            for y in 0..surface.size_y {
+                for x in 0..surface.size_x {
+                    let coord = Vector2::new((x as f32 + 0.5_f32) / surface.size_x as f32,
+                                             (y as f32 + 0.5_f32) / surface.size_y as f32);
+                    let dir = TextureCube::face_dir(index, coord);
+                    // solid angle of the texel
+                    let len = dir.length();
+                    let weight = 1.0_f32 / (len * len * len * (surface.size_x * surface.size_y) as f32);
+                    let color = surface.data[y * surface.size_x + x].mul(weight);
+                    for (coeff, y) in coeffs.iter_mut().zip(sh_basis(dir.div(len)).iter()) {
+                        *coeff = coeff.add(&color.mul(*y));
+                    }
+                    weight_sum += weight;
+                }
+            }
+        }
+
+        // the sum of solid angles is 4 * PI, the cosine lobe for bands 0, 1, 2 divided by PI
+        let bands = [1.0_f32, 2.0_f32 / 3.0_f32, 2.0_f32 / 3.0_f32, 2.0_f32 / 3.0_f32,
+                     0.25_f32, 0.25_f32, 0.25_f32, 0.25_f32, 0.25_f32];
+        let k = 4.0_f32 * std::f32::consts::PI / weight_sum;
+        for (coeff, band) in coeffs.iter_mut().zip(bands.iter()) {
+            *coeff = coeff.mul(k * band);
+        }
+
+        coeffs
+    }
 }
@@ -0,0 +1,381 @@
+use std;
+use std::cmp;
+use std::sync::Mutex;
+use cgmath::Vector4;
+use rasterization;
+use halfspace;
+use line;
+use line::LineRasterizer;
+use framebuffer::{FrameBuffer, Tile};
+use shader::{Shader, MAX_OUT_VALUES};
+
+// tile is a band of rows with full screen width,
+// so every thread owns a continuous part of the framebuffer
+const TILE_ROWS: usize = 16; // default for Tiler::set_tile_rows
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RasterizerType {
+    // scanline, see rasterization::triangle, HalfSpace is used for multisampled framebuffers
+    Scanline,
+    // edge functions with top-left fill rule, see halfspace::triangle
+    HalfSpace,
+}
+
+// how triangles are drawn
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PolygonMode {
+    Fill,
+    // edges with Tiler::wire_color
+    Line,
+    // vertices with Tiler::wire_color
+    Point,
+    // filled triangles with edges over them
+    FillLine,
+}
+
+pub struct ScreenTriangle {
+    pub points: [Vector4<f32>; 3], // see rasterization::triangle
+    pub vertex_data: [[f32; MAX_OUT_VALUES]; 3],
+    pub lod: usize, // mip level of material textures
+    pub state: usize,
+    pub front_facing: bool,
+}
+
+// points are the same as for ScreenTriangle, w is not used
+pub struct ScreenLine {
+    pub points: [Vector4<f32>; 2],
+    pub color: u32,
+}
+
+pub struct ScreenPoint {
+    pub position: Vector4<f32>,
+    pub color: u32,
+}
+
+// triangles of one transparent mesh, drawn by Tiler::flush_transparent
+struct TransparentMesh {
+    triangles: Vec<ScreenTriangle>, // state - index in Tiler::transparent_states
+}
+
+// sum of depths of vertices, bigger is nearer
+fn triangle_depth(t: &ScreenTriangle) -> f32 {
+    t.points[0].z + t.points[1].z + t.points[2].z
+}
+
+impl TransparentMesh {
+    // average depth of vertices, bigger is nearer
+    fn depth(&self) -> f32 {
+        let sum = self.triangles.iter().fold(0.0_f32, |sum, t| sum + triangle_depth(t));
+
+        sum / cmp::max(self.triangles.len() * 3, 1) as f32
+    }
+
+    // triangles from back to front, so the far side of a closed mesh is under the near one,
+    // intersecting triangles can still be blended in the wrong order
+    fn sort(&mut self) {
+        self.triangles.sort_by(|a, b| {
+            triangle_depth(a).partial_cmp(&triangle_depth(b)).unwrap_or(cmp::Ordering::Equal)
+        });
+    }
+}
+
+// indexes of primitives which touch a tile
+#[derive(Clone)]
+struct Bin {
+    triangles: Vec<u32>,
+    lines: Vec<u32>,
+    points: Vec<u32>,
+}
+
+impl Bin {
+    fn new() -> Bin {
+        Bin {
+            triangles: Vec::<u32>::new(),
+            lines: Vec::<u32>::new(),
+            points: Vec::<u32>::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.triangles.clear();
+        self.lines.clear();
+        self.points.clear();
+    }
+}
+
+// Collects triangles after the vertex stage, bins them per tile
+// and rasterizes tiles in parallel. Triangles in a tile are drawn in
+// the order of addition, so the output does not depend on the number of threads.
+// Lines and points are drawn after triangles of the tile.
+pub struct Tiler {
+    threads: usize,
+    tile_rows: usize,
+    rasterizer: RasterizerType,
+    polygon_mode: PolygonMode,
+    line_rasterizer: LineRasterizer,
+    wire_color: u32,
+    point_size: u32,
+    states: Vec<Shader>,
+    triangles: Vec<ScreenTriangle>,
+    lines: Vec<ScreenLine>,
+    points: Vec<ScreenPoint>,
+    bins: Vec<Bin>,
+    is_transparent: bool, // between begin_transparent and end_transparent
+    transparent_states: Vec<Shader>,
+    transparent: Vec<TransparentMesh>,
+}
+
+impl Tiler {
+    pub fn new(threads: usize) -> Tiler {
+        Tiler {
+            threads: cmp::max(threads, 1),
+            tile_rows: TILE_ROWS,
+            rasterizer: RasterizerType::Scanline,
+            polygon_mode: PolygonMode::Fill,
+            line_rasterizer: LineRasterizer::Bresenham,
+            wire_color: 0xFFFFFF,
+            point_size: 3,
+            states: Vec::<Shader>::new(),
+            triangles: Vec::<ScreenTriangle>::new(),
+            lines: Vec::<ScreenLine>::new(),
+            points: Vec::<ScreenPoint>::new(),
+            bins: Vec::<Bin>::new(),
+            is_transparent: false,
+            transparent_states: Vec::<Shader>::new(),
+            transparent: Vec::<TransparentMesh>::new(),
+        }
+    }
+
+    pub fn default_threads() -> usize {
+        match std::thread::available_parallelism() {
+            Ok(n) => n.get(),
+            Err(_) => 1,
+        }
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = cmp::max(threads, 1);
+    }
+
+    // height of tiles, one tile not smaller than the framebuffer draws it without splitting
+    pub fn set_tile_rows(&mut self, rows: usize) {
+        self.tile_rows = cmp::max(rows, 1);
+    }
+
+    pub fn set_rasterizer(&mut self, rasterizer: RasterizerType) {
+        self.rasterizer = rasterizer;
+    }
+
+    pub fn polygon_mode(&self) -> PolygonMode {
+        self.polygon_mode
+    }
+
+    pub fn set_polygon_mode(&mut self, mode: PolygonMode) {
+        self.polygon_mode = mode;
+    }
+
+    pub fn set_line_rasterizer(&mut self, rasterizer: LineRasterizer) {
+        self.line_rasterizer = rasterizer;
+    }
+
+    // color of edges and vertices in PolygonMode::Line, Point and FillLine
+    pub fn set_wire_color(&mut self, color: u32) {
+        self.wire_color = color;
+    }
+
+    // size of points in pixels
+    pub fn set_point_size(&mut self, size: u32) {
+        self.point_size = cmp::max(size, 1);
+    }
+
+    // snapshot of shader state for pixel stage, returns index for ScreenTriangle::state
+    pub fn add_state(&mut self, shader: &Shader) -> usize {
+        let states = if self.is_transparent { &mut self.transparent_states } else { &mut self.states };
+        states.push(shader.clone());
+        states.len() - 1
+    }
+
+    // the triangle is drawn by the polygon mode
+    pub fn add_triangle(&mut self, triangle: ScreenTriangle) {
+        let color = self.wire_color;
+        match self.polygon_mode {
+            PolygonMode::Line | PolygonMode::FillLine => {
+                for ind in 0..3 {
+                    self.add_line([triangle.points[ind], triangle.points[(ind + 1) % 3]], color);
+                }
+            },
+            PolygonMode::Point => {
+                for ind in 0..3 {
+                    self.add_point(triangle.points[ind], color);
+                }
+            },
+            PolygonMode::Fill => {},
+        }
+        match self.polygon_mode {
+            PolygonMode::Fill | PolygonMode::FillLine if self.is_transparent =>
+                self.transparent.last_mut().unwrap().triangles.push(triangle),
+            PolygonMode::Fill | PolygonMode::FillLine => self.triangles.push(triangle),
+            _ => {},
+        }
+    }
+
+    pub fn add_line(&mut self, points: [Vector4<f32>; 2], color: u32) {
+        self.lines.push(ScreenLine {
+            points: points,
+            color: color,
+        });
+    }
+
+    pub fn add_point(&mut self, position: Vector4<f32>, color: u32) {
+        self.points.push(ScreenPoint {
+            position: position,
+            color: color,
+        });
+    }
+
+    // states and triangles until end_transparent are one transparent mesh,
+    // it is not drawn by flush, but waits for flush_transparent
+    pub fn begin_transparent(&mut self) {
+        self.is_transparent = true;
+        self.transparent.push(TransparentMesh {
+            triangles: Vec::<ScreenTriangle>::new(),
+        });
+    }
+
+    pub fn end_transparent(&mut self) {
+        self.is_transparent = false;
+    }
+
+    // draws transparent meshes and their triangles from back to front over the framebuffer, after all opaque ones
+    pub fn flush_transparent(&mut self, framebuffer: &mut FrameBuffer) {
+        self.flush(framebuffer);
+        let mut meshes = std::mem::replace(&mut self.transparent, Vec::<TransparentMesh>::new());
+        // stable sort keeps the order of addition for meshes with equal depth
+        meshes.sort_by(|a, b| a.depth().partial_cmp(&b.depth()).unwrap_or(cmp::Ordering::Equal));
+        std::mem::swap(&mut self.states, &mut self.transparent_states);
+        for mut mesh in meshes {
+            mesh.sort();
+            self.triangles.extend(mesh.triangles);
+        }
+        self.flush(framebuffer);
+    }
+
+    // drops transparent meshes which were not flushed
+    pub fn clear_transparent(&mut self) {
+        self.transparent_states.clear();
+        self.transparent.clear();
+    }
+
+    // tiles with rows [y_begin; y_end)
+    fn tiles(y_begin: f32, y_end: f32, y_size: usize, tile_rows: usize) -> ::std::ops::Range<usize> {
+        if y_size == 0 {
+            return 0..0;
+        }
+        let y_begin = cmp::min(cmp::max(y_begin as i32, 0) as usize, y_size - 1);
+        let y_end = cmp::min(cmp::max(y_end as i32, 0) as usize, y_size);
+        if y_end <= y_begin {
+            return 0..0;
+        }
+
+        (y_begin / tile_rows)..((y_end - 1) / tile_rows + 1)
+    }
+
+    fn bin(&mut self, y_size: usize, cnt_tiles: usize) {
+        self.bins.resize(cnt_tiles, Bin::new());
+        for (ind, t) in self.triangles.iter().enumerate() {
+            let min_y = t.points[0].y.min(t.points[1].y).min(t.points[2].y);
+            let max_y = t.points[0].y.max(t.points[1].y).max(t.points[2].y);
+            // rows of pixel centers as in rasterization::triangle,
+            // and half of pixel more for samples of multisampling
+            for tile_ind in Tiler::tiles(min_y, max_y + 1.0_f32, y_size, self.tile_rows) {
+                self.bins[tile_ind].triangles.push(ind as u32);
+            }
+        }
+        // anti-aliased lines cover one more row on both sides
+        for (ind, l) in self.lines.iter().enumerate() {
+            let min_y = l.points[0].y.min(l.points[1].y);
+            let max_y = l.points[0].y.max(l.points[1].y);
+            for tile_ind in Tiler::tiles(min_y - 1.0_f32, max_y + 2.0_f32, y_size, self.tile_rows) {
+                self.bins[tile_ind].lines.push(ind as u32);
+            }
+        }
+        let half_size = self.point_size as f32 * 0.5_f32 + 1.0_f32;
+        for (ind, p) in self.points.iter().enumerate() {
+            for tile_ind in Tiler::tiles(p.position.y - half_size, p.position.y + half_size, y_size, self.tile_rows) {
+                self.bins[tile_ind].points.push(ind as u32);
+            }
+        }
+    }
+
+    fn render_tile(&self, tile: &mut Tile, bin: &Bin, states: &mut Vec<Shader>) {
+        let rasterizer = if tile.samples > 1 { RasterizerType::HalfSpace } else { self.rasterizer };
+        for ind in &bin.triangles {
+            let t = &self.triangles[*ind as usize];
+            let shader = &mut states[t.state];
+            shader.set_mip_level(t.lod);
+            shader.uniforms.front_facing = t.front_facing;
+            match rasterizer {
+                RasterizerType::Scanline => rasterization::triangle(tile, t.points, t.vertex_data, shader),
+                RasterizerType::HalfSpace => halfspace::triangle(tile, t.points, t.vertex_data, shader),
+            }
+        }
+        for ind in &bin.lines {
+            let l = &self.lines[*ind as usize];
+            match self.line_rasterizer {
+                LineRasterizer::Bresenham => line::bresenham(tile, l.points[0], l.points[1], l.color),
+                LineRasterizer::Wu => line::wu(tile, l.points[0], l.points[1], l.color),
+            }
+        }
+        for ind in &bin.points {
+            let p = &self.points[*ind as usize];
+            line::point(tile, p.position, self.point_size, p.color);
+        }
+    }
+
+    pub fn flush(&mut self, framebuffer: &mut FrameBuffer) {
+        // an empty framebuffer has no tiles
+        let is_empty = framebuffer.x_size == 0 || framebuffer.y_size == 0;
+        if !is_empty && (!self.triangles.is_empty() || !self.lines.is_empty() || !self.points.is_empty()) {
+            let tile_rows = cmp::min(self.tile_rows, framebuffer.y_size);
+            let cnt_tiles = (framebuffer.y_size + tile_rows - 1) / tile_rows;
+            self.bin(framebuffer.y_size, cnt_tiles);
+
+            let tiles = framebuffer.tiles(tile_rows);
+            let threads = cmp::min(self.threads, cnt_tiles);
+            let this = &*self;
+            let states = &this.states;
+            if threads == 1 {
+                let mut local_states = states.clone();
+                for (ind, mut tile) in tiles.into_iter().enumerate() {
+                    this.render_tile(&mut tile, &this.bins[ind], &mut local_states);
+                }
+            } else {
+                let queue = Mutex::new(tiles.into_iter().enumerate());
+                let queue = &queue;
+                std::thread::scope(|scope| {
+                    for _ in 0..threads {
+                        scope.spawn(move || {
+                            let mut local_states = states.clone();
+                            loop {
+                                let next = queue.lock().unwrap().next();
+                                match next {
+                                    Some((ind, mut tile)) =>
+                                        this.render_tile(&mut tile, &this.bins[ind], &mut local_states),
+                                    None => break,
+                                }
+                            }
+                        });
+                    }
+                });
+            }
+        }
+
+        self.states.clear();
+        self.triangles.clear();
+        self.lines.clear();
+        self.points.clear();
+        for bin in &mut self.bins {
+            bin.clear();
+        }
+    }
+}
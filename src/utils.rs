@@ -1,4 +1,5 @@
 use std;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 const BASE_DIR_NAME: &'static str = "media";
@@ -24,3 +25,11 @@ pub fn get_base_dir() -> Result<PathBuf, String> {
     }
     Err(format!("not found base direcory: {}", BASE_DIR_NAME))
 }
+
+pub fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    let mut data = Vec::<u8>::new();
+    match std::fs::File::open(path).and_then(|mut f| f.read_to_end(&mut data)) {
+        Ok(_) => Ok(data),
+        Err(e) => Err(format!("can't read file \"{}\", error = \"{}\"", path.display(), e)),
+    }
+}